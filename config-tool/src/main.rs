@@ -0,0 +1,723 @@
+//! Host side converter of the config images (see room_pill::config_format)
+//!
+//!   template <out.toml>         the defaults of the floor unit
+//!   encode <in.toml> <out.bin>  only the sections of the text are written into the image
+//!   decode <in.bin> <out.toml>  the images of the earlier schema versions are migrated
+//!   frames <in.bin> [id]        the CAN frames of the image for cansend (0x00D by default)
+//!
+//! The parent crate builds for the MCU by default, so the host target should be given:
+//!
+//!   cargo run --release --target x86_64-unknown-linux-gnu -- decode unit.bin unit.toml
+//!
+mod text;
+
+use onewire::temperature::Temperature;
+use room_pill::{
+    codec::{Reader, Writer},
+    config_format::{self, Image, ImageWriter, Section, MAX_IMAGE_SIZE, SCHEMA_VERSION},
+    floor_heating::{self, Config, Control, Mode, Policy, ERROR_REASON_COUNT},
+    heating_curve::{self, Modulation},
+    ir_remote::{IrCommands, KeyMap, COMMANDS},
+    pid::PidConfig,
+    schedule::{Schedule, SwitchPoint, DAYS_PER_WEEK},
+    sensors::{Registry, Role, ROLES},
+    temperature::FixedPoint,
+    timing::{Duration, Seconds},
+};
+use std::{env, fmt::Write as _, fs, process};
+use text::{Document, Value};
+
+type HeatingConfig = Config<Temperature, Duration<u32, Seconds>>;
+
+const DEFAULT_CAN_ID: u16 = 0x00D; //can_reconfigure_id of the floor unit
+
+const DAY_NAMES: [&str; DAYS_PER_WEEK] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+const HEATING_KEYS: [&str; 31] = [
+    "control",
+    "mode",
+    "floor_target",
+    "floor_min",
+    "floor_histeresis",
+    "pid_kp",
+    "pid_ki",
+    "pid_kd",
+    "pid_cycle_period",
+    "pid_min_pulse",
+    "curve_enabled",
+    "curve_slope",
+    "curve_offset",
+    "curve_room_factor",
+    "curve_min_forward",
+    "curve_histeresis",
+    "curve_modulation",
+    "max_forward",
+    "max_floor",
+    "temperature_histeresis",
+    "freeze_min",
+    "freeze_safe",
+    "freeze_check_interval",
+    "freeze_check_duration",
+    "pre_circulation",
+    "after_circulation",
+    "error_policies",
+    "error_min_hold",
+    "overheat_margin",
+    "circulation_difference",
+    "circulation_timeout",
+];
+
+/// The decoded sections of an image
+struct Sections {
+    heating: Option<HeatingConfig>,
+    program: Option<Schedule<Temperature>>,
+    keys: Option<KeyMap>,
+    sensors: Option<Registry>,
+}
+
+//names in the text
+
+fn control_name(control: Control) -> &'static str {
+    match control {
+        Control::Histeresis => "histeresis",
+        Control::Pid => "pid",
+    }
+}
+
+fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Air => "air",
+        Mode::Floor => "floor",
+        Mode::AirMinFloor => "air_min_floor",
+    }
+}
+
+fn modulation_name(modulation: Modulation) -> &'static str {
+    match modulation {
+        Modulation::HeatRequest => "heat_request",
+        Modulation::Valve => "valve",
+    }
+}
+
+fn policy_name(policy: Policy) -> &'static str {
+    match policy {
+        Policy::Latching => "latching",
+        Policy::Retry => "retry",
+    }
+}
+
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Forward => "forward",
+        Role::Return => "return",
+        Role::Floor => "floor",
+        Role::Air => "air",
+        Role::Outdoor => "outdoor",
+    }
+}
+
+fn command_name(command: IrCommands) -> String {
+    format!("{:?}", command).to_lowercase()
+}
+
+/// Looks up the name in the variants
+fn from_name<T: Copy>(
+    name: &str,
+    variants: &[T],
+    variant_name: fn(T) -> &'static str,
+) -> Result<T, String> {
+    variants
+        .iter()
+        .copied()
+        .find(|variant| variant_name(*variant) == name)
+        .ok_or_else(|| {
+            let names: Vec<&str> = variants.iter().map(|v| variant_name(*v)).collect();
+            format!("unknown {}, expected one of {}", name, names.join(", "))
+        })
+}
+
+//formatting of the values
+
+fn format_temperature(temperature: &Temperature) -> String {
+    format!("{:?}", temperature.to_sixteenths() as f32 / 16.0)
+}
+
+fn format_duration(duration: Duration<u32, Seconds>) -> String {
+    let (hours, minutes, seconds) = duration.to_hms();
+    format!("\"{}:{:02}:{:02}\"", hours, minutes, seconds)
+}
+
+fn format_rom(rom: &[u8; 8]) -> String {
+    rom.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+fn format_heating(out: &mut String, config: &HeatingConfig) {
+    let t = format_temperature;
+    let d = format_duration;
+    let policies: Vec<String> = config
+        .error
+        .policies
+        .iter()
+        .map(|policy| format!("\"{}\"", policy_name(*policy)))
+        .collect();
+    let lines = [
+        ("control", format!("\"{}\"", control_name(config.control))),
+        ("mode", format!("\"{}\"", mode_name(config.mode))),
+        ("floor_target", t(&config.floor.target_temperature)),
+        ("floor_min", t(&config.floor.min_temperature)),
+        ("floor_histeresis", t(&config.floor.histeresis)),
+        ("pid_kp", config.pid.kp.to_string()),
+        ("pid_ki", config.pid.ki.to_string()),
+        ("pid_kd", config.pid.kd.to_string()),
+        ("pid_cycle_period", d(config.pid.cycle_period)),
+        ("pid_min_pulse", d(config.pid.min_pulse)),
+        ("curve_enabled", config.heating_curve.enabled.to_string()),
+        ("curve_slope", config.heating_curve.slope.to_string()),
+        ("curve_offset", t(&config.heating_curve.offset)),
+        (
+            "curve_room_factor",
+            config.heating_curve.room_factor.to_string(),
+        ),
+        (
+            "curve_min_forward",
+            t(&config.heating_curve.min_forward_temperature),
+        ),
+        ("curve_histeresis", t(&config.heating_curve.histeresis)),
+        (
+            "curve_modulation",
+            format!("\"{}\"", modulation_name(config.heating_curve.modulation)),
+        ),
+        ("max_forward", t(&config.max_forward_temperature)),
+        ("max_floor", t(&config.max_floor_temperature)),
+        ("temperature_histeresis", t(&config.temperature_histeresis)),
+        ("freeze_min", t(&config.freeze_protection.min_temperature)),
+        ("freeze_safe", t(&config.freeze_protection.safe_temperature)),
+        (
+            "freeze_check_interval",
+            d(config.freeze_protection.check_interval),
+        ),
+        (
+            "freeze_check_duration",
+            d(config.freeze_protection.check_duration),
+        ),
+        ("pre_circulation", d(config.pre_circulation_duration)),
+        ("after_circulation", d(config.after_circulation_duration)),
+        ("error_policies", format!("[{}]", policies.join(", "))),
+        ("error_min_hold", d(config.error.min_hold)),
+        ("overheat_margin", t(&config.error.overheat_margin)),
+        (
+            "circulation_difference",
+            t(&config.error.circulation_difference),
+        ),
+        ("circulation_timeout", d(config.error.circulation_timeout)),
+    ];
+    out.push_str("[heating]\n");
+    out.push_str("# control: histeresis, pid; mode: air, floor, air_min_floor\n");
+    out.push_str("# temperatures in degree Celsius, durations as \"H:MM:SS\"\n");
+    for (key, value) in lines.iter() {
+        let _ = writeln!(out, "{} = {}", key, value);
+    }
+    out.push('\n');
+}
+
+fn format_program(out: &mut String, program: &Schedule<Temperature>) {
+    out.push_str("[program]\n");
+    out.push_str("# the switch points of the days: \"HH:MM target\"\n");
+    for (weekday, name) in DAY_NAMES.iter().enumerate() {
+        let points: Vec<String> = program
+            .day(weekday as u8)
+            .iter()
+            .map(|point| {
+                format!(
+                    "\"{:02}:{:02} {}\"",
+                    point.hour,
+                    point.min,
+                    format_temperature(&point.target)
+                )
+            })
+            .collect();
+        let _ = writeln!(out, "{} = [{}]", name, points.join(", "));
+    }
+    out.push('\n');
+}
+
+fn format_keys(out: &mut String, keys: &KeyMap) {
+    out.push_str("[keys]\n");
+    out.push_str("# the NEC address and command of the remote key = the command\n");
+    for (code, command) in keys.iter() {
+        let _ = writeln!(out, "\"0x{:06X}\" = \"{}\"", code, command_name(*command));
+    }
+    out.push('\n');
+}
+
+fn format_sensors(out: &mut String, sensors: &Registry) {
+    out.push_str("[sensors]\n");
+    out.push_str("# the 1-wire ROM codes in hex, empty if not assigned\n");
+    for role in ROLES.iter() {
+        let rom = sensors.rom(*role).map(format_rom).unwrap_or_default();
+        let _ = writeln!(out, "{} = \"{}\"", role_name(*role), rom);
+    }
+    out.push('\n');
+}
+
+fn format_text(sections: &Sections, version: u8) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# room-pill config, schema version {}\n", version);
+    if let Some(heating) = &sections.heating {
+        format_heating(&mut out, heating);
+    }
+    if let Some(program) = &sections.program {
+        format_program(&mut out, program);
+    }
+    if let Some(keys) = &sections.keys {
+        format_keys(&mut out, keys);
+    }
+    if let Some(sensors) = &sections.sensors {
+        format_sensors(&mut out, sensors);
+    }
+    out
+}
+
+//parsing of the values
+
+fn as_text<'a>(key: &str, value: &'a Value) -> Result<&'a str, String> {
+    match value {
+        Value::Text(text) => Ok(text),
+        _ => Err(format!("{}: expected a \"string\"", key)),
+    }
+}
+
+fn as_bool(key: &str, value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Bool(value) => Ok(*value),
+        _ => Err(format!("{}: expected true or false", key)),
+    }
+}
+
+fn as_list<'a>(key: &str, value: &'a Value) -> Result<&'a [Value], String> {
+    match value {
+        Value::List(items) => Ok(items),
+        _ => Err(format!("{}: expected a [list]", key)),
+    }
+}
+
+fn as_integer(key: &str, value: &Value) -> Result<i32, String> {
+    match value {
+        Value::Number(number) => number
+            .parse::<i32>()
+            .ok()
+            .filter(|value| *value >= i16::MIN as i32 && *value <= i16::MAX as i32)
+            .ok_or_else(|| format!("{}: {} is not an integer in the i16 range", key, number)),
+        _ => Err(format!("{}: expected an integer", key)),
+    }
+}
+
+fn parse_temperature(key: &str, text: &str) -> Result<Temperature, String> {
+    let celsius = text
+        .parse::<f32>()
+        .map_err(|_| format!("{}: {} is not a temperature", key, text))?;
+    let sixteenths = (celsius * 16.0).round();
+    if celsius.is_nan() || sixteenths <= i16::MIN as f32 || sixteenths > i16::MAX as f32 {
+        return Err(format!("{}: {} is out of range", key, text));
+    }
+    Ok(Temperature::from_sixteenths(sixteenths as i32))
+}
+
+fn as_temperature(key: &str, value: &Value) -> Result<Temperature, String> {
+    match value {
+        Value::Number(number) => parse_temperature(key, number),
+        _ => Err(format!("{}: expected a temperature", key)),
+    }
+}
+
+/// "H:MM:SS", "H:MM" or plain seconds
+fn as_duration(key: &str, value: &Value) -> Result<Duration<u32, Seconds>, String> {
+    let error = || format!("{}: expected \"H:MM:SS\"", key);
+    let seconds = match value {
+        Value::Number(number) => number.parse::<u32>().map_err(|_| error())?,
+        Value::Text(text) => {
+            let parts = text
+                .split(':')
+                .map(|part| part.trim().parse::<u32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| error())?;
+            match parts.as_slice() {
+                [hours, minutes, seconds] if *minutes < 60 && *seconds < 60 => {
+                    hours * 3600 + minutes * 60 + seconds
+                }
+                [hours, minutes] if *minutes < 60 => hours * 3600 + minutes * 60,
+                _ => return Err(error()),
+            }
+        }
+        _ => return Err(error()),
+    };
+    Ok(Duration::from(seconds))
+}
+
+fn parse_code(text: &str) -> Result<u32, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse::<u32>(),
+    };
+    parsed.map_err(|_| format!("{} is not a key code", text))
+}
+
+fn parse_rom(key: &str, text: &str) -> Result<Option<[u8; 8]>, String> {
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let error = || format!("{}: expected 16 hex digits", key);
+    if text.len() != 16 || !text.is_ascii() {
+        return Err(error());
+    }
+    let mut rom = [0u8; 8];
+    for (i, byte) in rom.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[2 * i..2 * i + 2], 16).map_err(|_| error())?;
+    }
+    Ok(Some(rom))
+}
+
+/// Rejects the unknown keys, they are most likely typos
+fn check_keys(section: &text::Section, keys: &[&str]) -> Result<(), String> {
+    match section
+        .entries
+        .iter()
+        .find(|entry| !keys.contains(&entry.key.as_str()))
+    {
+        Some(entry) => Err(format!(
+            "line {}: unknown key {} in [{}]",
+            entry.line, entry.key, section.name
+        )),
+        None => Ok(()),
+    }
+}
+
+fn parse_heating(section: &text::Section) -> Result<HeatingConfig, String> {
+    check_keys(section, &HEATING_KEYS)?;
+
+    let value = |key: &str| section.get(key);
+    let temperature = |key: &str| as_temperature(key, value(key)?);
+    let duration = |key: &str| as_duration(key, value(key)?);
+    let integer = |key: &str| as_integer(key, value(key)?);
+    let name = |key: &str| as_text(key, value(key)?).map(str::to_string);
+
+    let policy_items = as_list("error_policies", value("error_policies")?)?;
+    if policy_items.len() != ERROR_REASON_COUNT {
+        return Err(format!(
+            "error_policies: expected {} policies",
+            ERROR_REASON_COUNT
+        ));
+    }
+    let mut policies = [Policy::Latching; ERROR_REASON_COUNT];
+    for (policy, item) in policies.iter_mut().zip(policy_items.iter()) {
+        *policy = from_name(
+            as_text("error_policies", item)?,
+            &[Policy::Latching, Policy::Retry],
+            policy_name,
+        )?;
+    }
+
+    Ok(Config {
+        control: from_name(
+            &name("control")?,
+            &[Control::Histeresis, Control::Pid],
+            control_name,
+        )?,
+        mode: from_name(
+            &name("mode")?,
+            &[Mode::Air, Mode::Floor, Mode::AirMinFloor],
+            mode_name,
+        )?,
+        floor: floor_heating::FloorConfig {
+            target_temperature: temperature("floor_target")?,
+            min_temperature: temperature("floor_min")?,
+            histeresis: temperature("floor_histeresis")?,
+        },
+        pid: PidConfig {
+            kp: integer("pid_kp")?,
+            ki: integer("pid_ki")?,
+            kd: integer("pid_kd")?,
+            cycle_period: duration("pid_cycle_period")?,
+            min_pulse: duration("pid_min_pulse")?,
+        },
+        heating_curve: heating_curve::Config {
+            enabled: as_bool("curve_enabled", value("curve_enabled")?)?,
+            slope: integer("curve_slope")?,
+            offset: temperature("curve_offset")?,
+            room_factor: integer("curve_room_factor")?,
+            min_forward_temperature: temperature("curve_min_forward")?,
+            histeresis: temperature("curve_histeresis")?,
+            modulation: from_name(
+                &name("curve_modulation")?,
+                &[Modulation::HeatRequest, Modulation::Valve],
+                modulation_name,
+            )?,
+        },
+        max_forward_temperature: temperature("max_forward")?,
+        max_floor_temperature: temperature("max_floor")?,
+        target_air_temperature: None,
+        temperature_histeresis: temperature("temperature_histeresis")?,
+        freeze_protection: floor_heating::FreezeProtectionConfig {
+            min_temperature: temperature("freeze_min")?,
+            safe_temperature: temperature("freeze_safe")?,
+            check_interval: duration("freeze_check_interval")?,
+            check_duration: duration("freeze_check_duration")?,
+        },
+        pre_circulation_duration: duration("pre_circulation")?,
+        after_circulation_duration: duration("after_circulation")?,
+        error: floor_heating::ErrorConfig {
+            policies: policies,
+            min_hold: duration("error_min_hold")?,
+            overheat_margin: temperature("overheat_margin")?,
+            circulation_difference: temperature("circulation_difference")?,
+            circulation_timeout: duration("circulation_timeout")?,
+        },
+    })
+}
+
+/// "HH:MM target"
+fn parse_switch_point(day: &str, text: &str) -> Result<SwitchPoint<Temperature>, String> {
+    let error = || format!("{}: expected \"HH:MM target\" instead of \"{}\"", day, text);
+    let mut parts = text.split_whitespace();
+    let (time, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(time), Some(target), None) => (time, target),
+        _ => return Err(error()),
+    };
+    let mut time = time.split(':').map(|part| part.parse::<u8>());
+    let (hour, min) = match (time.next(), time.next(), time.next()) {
+        (Some(Ok(hour)), Some(Ok(min)), None) if hour < 24 && min < 60 => (hour, min),
+        _ => return Err(error()),
+    };
+    Ok(SwitchPoint::new(hour, min, parse_temperature(day, target)?))
+}
+
+fn parse_program(section: &text::Section) -> Result<Schedule<Temperature>, String> {
+    check_keys(section, &DAY_NAMES)?;
+    let mut program = Schedule::new();
+    for (weekday, name) in DAY_NAMES.iter().enumerate() {
+        let day = program.day_mut(weekday as u8);
+        for item in as_list(name, section.get(name)?)? {
+            let point = parse_switch_point(name, as_text(name, item)?)?;
+            day.add(point).map_err(|_| {
+                format!(
+                    "{}: too many or duplicate switch points ({:02}:{:02})",
+                    name, point.hour, point.min
+                )
+            })?;
+        }
+    }
+    Ok(program)
+}
+
+fn parse_keys(section: &text::Section) -> Result<KeyMap, String> {
+    let mut keys = KeyMap::new();
+    for entry in section.entries.iter() {
+        let error = |message: String| format!("line {}: {}", entry.line, message);
+        let code = parse_code(&entry.key).map_err(error)?;
+        let name = as_text(&entry.key, &entry.value).map_err(error)?;
+        let command = COMMANDS
+            .iter()
+            .copied()
+            .find(|command| command_name(*command) == name)
+            .ok_or_else(|| error(format!("unknown command {}", name)))?;
+        if !keys.set(code, command) {
+            return Err(error("too many keys".to_string()));
+        }
+    }
+    Ok(keys)
+}
+
+fn parse_sensors(section: &text::Section) -> Result<Registry, String> {
+    let names: Vec<&str> = ROLES.iter().map(|role| role_name(*role)).collect();
+    check_keys(section, &names)?;
+    let mut sensors = Registry::new();
+    for role in ROLES.iter() {
+        let name = role_name(*role);
+        //a missing role is not assigned
+        if let Ok(value) = section.get(name) {
+            if let Some(rom) = parse_rom(name, as_text(name, value)?)? {
+                sensors.assign(*role, &rom);
+            }
+        }
+    }
+    Ok(sensors)
+}
+
+fn parse_text(document: &Document) -> Result<Sections, String> {
+    const NAMES: [&str; 4] = ["heating", "program", "keys", "sensors"];
+    if let Some(section) = document
+        .sections
+        .iter()
+        .find(|section| !NAMES.contains(&section.name.as_str()))
+    {
+        return Err(format!("unknown section [{}]", section.name));
+    }
+    Ok(Sections {
+        heating: document.section("heating").map(parse_heating).transpose()?,
+        program: document.section("program").map(parse_program).transpose()?,
+        keys: document.section("keys").map(parse_keys).transpose()?,
+        sensors: document.section("sensors").map(parse_sensors).transpose()?,
+    })
+}
+
+//the binary image
+
+fn encode_image(sections: &Sections) -> Result<Vec<u8>, config_format::Error> {
+    let mut buffer = [0u8; MAX_IMAGE_SIZE];
+    let mut image = ImageWriter::new(&mut buffer)?;
+    if let Some(heating) = &sections.heating {
+        image.section(Section::Heating, |w: &mut Writer| heating.encode(w))?;
+    }
+    if let Some(program) = &sections.program {
+        image.section(Section::Program, |w: &mut Writer| program.encode(w))?;
+    }
+    if let Some(keys) = &sections.keys {
+        image.section(Section::Keys, |w: &mut Writer| keys.encode(w))?;
+    }
+    if let Some(sensors) = &sections.sensors {
+        image.section(Section::Sensors, |w: &mut Writer| sensors.encode(w))?;
+    }
+    let len = image.finish()?;
+    Ok(buffer[..len].to_vec())
+}
+
+fn decode_section<T, D: FnOnce(&mut Reader) -> Option<T>>(
+    image: &Image,
+    section: Section,
+    decode: D,
+) -> Result<Option<T>, config_format::Error> {
+    match image.section(section) {
+        Some(mut reader) => decode(&mut reader)
+            .map(Some)
+            .ok_or(config_format::Error::Invalid(section)),
+        None => Ok(None),
+    }
+}
+
+fn decode_image(data: &[u8]) -> Result<(Sections, u8), config_format::Error> {
+    let image = Image::parse(data)?;
+    let version = image.version();
+    let sections = Sections {
+        heating: decode_section(&image, Section::Heating, |r| Config::decode(r, version))?,
+        program: decode_section(&image, Section::Program, Schedule::decode)?,
+        keys: decode_section(&image, Section::Keys, KeyMap::decode)?,
+        sensors: decode_section(&image, Section::Sensors, Registry::decode)?,
+    };
+    Ok((sections, version))
+}
+
+//the commands
+
+fn read_text(path: &str) -> Result<Sections, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let document = text::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+    parse_text(&document).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn read_image(path: &str) -> Result<(Sections, u8), String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    decode_image(&data).map_err(|e| format!("{}: invalid image ({:?})", path, e))
+}
+
+fn write(path: &str, data: &[u8]) -> Result<(), String> {
+    fs::write(path, data).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// The defaults of the floor unit.
+/// The sensors are assigned on the unit, the template does not overwrite them.
+fn defaults() -> Sections {
+    Sections {
+        heating: Some(Config::with_defaults()),
+        program: Some(Schedule::with_defaults()),
+        keys: Some(KeyMap::with_defaults()),
+        sensors: None,
+    }
+}
+
+fn template(path: &str) -> Result<(), String> {
+    write(path, format_text(&defaults(), SCHEMA_VERSION).as_bytes())
+}
+
+fn encode(input: &str, output: &str) -> Result<(), String> {
+    let sections = read_text(input)?;
+    let image = encode_image(&sections).map_err(|e| format!("{}: {:?}", input, e))?;
+    println!("{} bytes, schema version {}", image.len(), SCHEMA_VERSION);
+    write(output, &image)
+}
+
+fn decode(input: &str, output: &str) -> Result<(), String> {
+    let (sections, version) = read_image(input)?;
+    if version != SCHEMA_VERSION {
+        println!(
+            "migrated from schema version {} to {}",
+            version, SCHEMA_VERSION
+        );
+    }
+    write(output, format_text(&sections, SCHEMA_VERSION).as_bytes())
+}
+
+fn frames(input: &str, id: Option<&str>) -> Result<(), String> {
+    let id = match id {
+        Some(id) => parse_code(id).map_err(|e| format!("id: {}", e))?,
+        None => DEFAULT_CAN_ID as u32,
+    };
+    //checked, so the unit does not get a broken image
+    read_image(input)?;
+    let data = fs::read(input).map_err(|e| format!("{}: {}", input, e))?;
+    for frame in config_format::frames(&data) {
+        let payload: String = frame.iter().map(|byte| format!("{:02X}", byte)).collect();
+        println!("{:03X}#{}", id, payload);
+    }
+    Ok(())
+}
+
+fn usage() -> ! {
+    eprintln!("usage: config-tool template <out.toml>");
+    eprintln!("       config-tool encode <in.toml> <out.bin>");
+    eprintln!("       config-tool decode <in.bin> <out.toml>");
+    eprintln!("       config-tool frames <in.bin> [can id]");
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["template", output] => template(output),
+        ["encode", input, output] => encode(input, output),
+        ["decode", input, output] => decode(input, output),
+        ["frames", input] => frames(input, None),
+        ["frames", input, id] => frames(input, Some(id)),
+        _ => usage(),
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_encodes_to_the_default_image() {
+        let template = format_text(&defaults(), SCHEMA_VERSION);
+        let sections = parse_text(&text::parse(&template).unwrap()).unwrap();
+        let firmware = Sections {
+            heating: Some(Config::with_defaults()),
+            program: Some(Schedule::with_defaults()),
+            keys: Some(KeyMap::with_defaults()),
+            sensors: None,
+        };
+        assert!(encode_image(&sections).unwrap() == encode_image(&firmware).unwrap());
+    }
+}
@@ -0,0 +1,134 @@
+//! Minimal reader of the TOML-like config text: [sections], key = value lines and # comments.
+//! The values are numbers, "strings", true / false or one line [arrays] of them.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(String), //parsed by the user (decimal, hex)
+    Text(String),
+    Bool(bool),
+    List(Vec<Value>),
+}
+
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+pub struct Section {
+    pub name: String,
+    pub entries: Vec<Entry>,
+}
+
+impl Section {
+    pub fn get(&self, key: &str) -> Result<&Value, String> {
+        self.entries
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| &entry.value)
+            .ok_or_else(|| format!("[{}]: missing {}", self.name, key))
+    }
+}
+
+pub struct Document {
+    pub sections: Vec<Section>,
+}
+
+impl Document {
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+}
+
+/// The # starts a comment outside of the strings
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits by the commas outside of the strings
+fn split_list(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&text[start..]);
+    items
+        .into_iter()
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let text = text.trim();
+    if text.starts_with('"') {
+        if text.len() < 2 || !text.ends_with('"') {
+            return Err(format!("unterminated string: {}", text));
+        }
+        Ok(Value::Text(text[1..text.len() - 1].to_string()))
+    } else if text.starts_with('[') {
+        if !text.ends_with(']') {
+            return Err(format!("unterminated list: {}", text));
+        }
+        split_list(&text[1..text.len() - 1])
+            .into_iter()
+            .map(parse_value)
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::List)
+    } else if text == "true" || text == "false" {
+        Ok(Value::Bool(text == "true"))
+    } else if text.is_empty() {
+        Err("missing value".to_string())
+    } else {
+        Ok(Value::Number(text.to_string()))
+    }
+}
+
+pub fn parse(text: &str) -> Result<Document, String> {
+    let mut sections: Vec<Section> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') && !line.contains('=') {
+            sections.push(Section {
+                name: line[1..line.len() - 1].trim().to_string(),
+                entries: Vec::new(),
+            });
+            continue;
+        }
+        let section = sections
+            .last_mut()
+            .ok_or_else(|| format!("line {}: value outside of the sections", number))?;
+        let equal = line
+            .find('=')
+            .ok_or_else(|| format!("line {}: expected key = value", number))?;
+        let value = parse_value(&line[equal + 1..])
+            .map_err(|error| format!("line {}: {}", number, error))?;
+        section.entries.push(Entry {
+            key: line[..equal].trim().trim_matches('"').to_string(), //the quoted keys too
+            value: value,
+            line: number,
+        });
+    }
+    Ok(Document { sections: sections })
+}
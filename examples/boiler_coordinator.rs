@@ -0,0 +1,339 @@
+//! Central heat source coordinator
+//!
+//! The zones (floor_unit, manifold_unit) publish their heat demand in ID_HEAT_DEMAND + zone messages,
+//! the boiler is switched on while any of the zones has heat demand, with min on and off times.
+//! The supply status (boiler state, forward temperature setpoint, heating and cooling zones) is broadcasted
+//! in ID_SUPPLY messages periodically and on every switch. The zones drive their own heat request
+//! signal while this status is missing.
+//!
+//! Boiler heat request signal (open collector NPN transistor) on B11
+//!
+//! RGB led on PB13, PB14, PB15 as push pull output
+//!
+//! Hx1230 lcd display conected to SPI1 and some gpio port:
+//!   PA5 = Display SPI clock
+//!   PA7 = Display SPI data
+//!   PA2 = Display Chip Select^
+//!   PA1 = Display Reset^
+//!   B12 = Display Backlight^ (with a PNP transistor) - use open drain output!
+//!
+//! PA11, PA12 = CAN RX, TX
+//!
+//#![deny(unsafe_code)]
+//#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use cortex_m;
+use cortex_m_rt;
+use embedded_hal;
+use lcd_hal;
+use onewire;
+use panic_halt as _;
+use room_pill;
+use stm32f1xx_hal;
+
+use cortex_m_rt::{entry, exception, ExceptionFrame};
+use embedded_hal::{digital::v2::OutputPin, watchdog::WatchdogEnable};
+use lcd_hal::{hx1230, hx1230::Hx1230, Display};
+use onewire::temperature::Temperature;
+use room_pill::{
+    display::*,
+    heat_demand::{Coordinator, CoordinatorConfig, Demand, Relay, MAX_ZONES},
+    messenger::{heat_demand_id, ID_SUPPLY},
+    rgb::*,
+    temperature::FixedPoint,
+    timing::{Duration, Seconds, SysTicks, Ticker, Time, TimeExt},
+};
+use stm32f1xx_hal::{
+    afio::AfioExt, can::*, delay::Delay, flash::FlashExt, gpio::GpioExt, prelude::*, rcc::RccExt,
+    rtc, watchdog::IndependentWatchdog,
+};
+
+const STATUS_PERIOD: u32 = 10; //seconds between the supply status broadcasts
+
+struct Model {
+    //config:
+    config: CoordinatorConfig<Temperature, Duration<u32, Seconds>>,
+
+    //state:
+    coordinator: Coordinator<Duration<u32, Seconds>>,
+    status_age: Duration<u32, Seconds>, //since the last broadcast
+    time: Time<u32, Seconds>,           //rtc based, ever increasing, in seconds
+}
+
+impl Model {
+    fn new() -> Self {
+        Model {
+            config: CoordinatorConfig {
+                min_on: Duration::<u32, Seconds>::from_hms(0, 5, 0),
+                min_off: Duration::<u32, Seconds>::from_hms(0, 10, 0),
+                zone_timeout: Duration::<u32, Seconds>::from_hms(0, 2, 0),
+                default_forward_temperature: Temperature::from_celsius(35, 0),
+                max_forward_temperature: Temperature::from_celsius(45, 0),
+            },
+
+            coordinator: Coordinator::new(),
+            status_age: 0.s(),
+            time: Time::<u32, Seconds>::from_sec(0),
+        }
+    }
+
+    //the zone is given by the index of the matching filter
+    fn can_demand_received(&mut self, zone: usize, data: u64) {
+        self.coordinator.demand_received(zone, Demand::decode(data));
+    }
+
+    //update by real time clock
+    fn update_time(&mut self, time: Time<u32, Seconds>) {
+        if self.time != time {
+            let delta_time = time - self.time;
+            self.time = time;
+
+            self.status_age = self.status_age + delta_time;
+            if self.coordinator.update(&self.config, delta_time) {
+                //broadcast the switch immediately
+                self.status_age = STATUS_PERIOD.s();
+            }
+        }
+    }
+
+    /// The supply status to broadcast, if it is due
+    fn status_payload(&mut self) -> Option<[u8; 8]> {
+        if self.status_age >= STATUS_PERIOD.s() {
+            self.status_age = 0.s();
+            Some(self.coordinator.supply(&self.config).encode())
+        } else {
+            None
+        }
+    }
+
+    fn refresh_display<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        //the boiler state and the time since the last switch
+        display.set_position(0, 0)?;
+        let since = match self.coordinator.relay {
+            Relay::On(since) => {
+                display.print(b"Kazan be  ")?;
+                since
+            }
+            Relay::Off(since) => {
+                display.print(b"Kazan ki  ")?;
+                since
+            }
+        };
+        print_hms(display, &since)?;
+
+        print_temp(
+            display,
+            1,
+            b"Elore:  ",
+            &self
+                .coordinator
+                .forward_temperature(&self.config)
+                .map(Temperature::from_sixteenths),
+        )?;
+
+        //two zones per row: number, demand (H = heat, C = cool, - = none, ? = silent), forward temperature
+        for i in 0..MAX_ZONES {
+            display.set_position(if i % 2 == 0 { 0 } else { 66 }, 2 + (i / 2) as u8)?;
+            display.print_char('0' as u8 + i as u8)?;
+            match self.coordinator.zone(i) {
+                Some(demand) => {
+                    display.print_char(if demand.heat {
+                        'H'
+                    } else if demand.cool {
+                        'C'
+                    } else {
+                        '-'
+                    } as u8)?;
+                    match demand.forward_temperature {
+                        Some(forward) => display
+                            .print(unsafe { fmt_temp(Temperature::from_sixteenths(forward)) })?,
+                        None => display.print(b" ----")?,
+                    }
+                }
+                None => display.print(b"?      ")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let device = stm32f1xx_hal::pac::Peripherals::take().unwrap();
+    let mut rcc = device.RCC.constrain();
+    let mut watchdog = IndependentWatchdog::new(device.IWDG);
+    watchdog.start(stm32f1xx_hal::time::U32Ext::ms(2_000u32));
+
+    let mut flash = device.FLASH.constrain();
+
+    let clocks = rcc
+        .cfgr
+        .use_hse(8.mhz())
+        .sysclk(72.mhz())
+        .hclk(72.mhz())
+        .pclk1(36.mhz())
+        .pclk2(72.mhz())
+        .freeze(&mut flash.acr);
+    watchdog.feed();
+
+    // real time clock
+    let rtc = {
+        let mut pwr = device.PWR;
+        let mut backup_domain = rcc.bkp.constrain(device.BKP, &mut rcc.apb1, &mut pwr);
+        rtc::Rtc::rtc(device.RTC, &mut backup_domain)
+    };
+    watchdog.feed();
+
+    let mut afio = device.AFIO.constrain(&mut rcc.apb2);
+
+    //configure pins:
+    let mut gpioa = device.GPIOA.split(&mut rcc.apb2);
+    let mut gpiob = device.GPIOB.split(&mut rcc.apb2);
+    let mut gpioc = device.GPIOC.split(&mut rcc.apb2);
+
+    // RGB led:
+    let mut rgb = RgbLed::new(
+        gpiob.pb13.into_open_drain_output(&mut gpiob.crh),
+        gpiob.pb14.into_open_drain_output(&mut gpiob.crh),
+        gpiob.pb15.into_open_drain_output(&mut gpiob.crh),
+    );
+
+    let mut heat_request = gpiob.pb11.into_push_pull_output(&mut gpiob.crh);
+    heat_request.set_low().unwrap();
+
+    // on board led^:
+    let mut led = gpioc.pc13.into_push_pull_output(&mut gpioc.crh);
+
+    // setup SPI for the lcd display:
+    let sck = gpioa.pa5.into_push_pull_output(&mut gpioa.crl);
+    let mosi = gpioa.pa7.into_push_pull_output(&mut gpioa.crl);
+
+    let mut backlight = gpiob.pb12.into_open_drain_output(&mut gpiob.crh);
+    backlight.set_low().unwrap();
+
+    let cs = gpioa.pa2.into_push_pull_output(&mut gpioa.crl);
+    let mut rst = gpioa.pa1.into_push_pull_output(&mut gpioa.crl);
+
+    let core = cortex_m::Peripherals::take().unwrap();
+    let mut delay = Delay::new(core.SYST, clocks);
+    let mut display = hx1230::gpio::Hx1230Gpio::new(sck, mosi, cs, &mut rst, &mut delay).unwrap();
+    display.init().unwrap();
+    display.set_contrast(7).unwrap();
+    display.clear().unwrap();
+
+    watchdog.feed();
+
+    let tick = Ticker::new(core.DWT, core.DCB, clocks);
+
+    let canrx = gpioa.pa11.into_floating_input(&mut gpioa.crh);
+    let cantx = gpioa.pa12.into_alternate_push_pull(&mut gpioa.crh);
+    let mut can = Can::can1(
+        device.CAN1,
+        (cantx, canrx),
+        &mut afio.mapr,
+        &mut rcc.apb1,
+        device.USB,
+    );
+
+    let mut model = Model::new();
+    can.configure(&Configuration {
+        time_triggered_communication_mode: false,
+        automatic_bus_off_management: true,
+        automatic_wake_up_mode: true,
+        no_automatic_retransmission: false,
+        receive_fifo_locked_mode: false,
+        transmit_fifo_priority: false,
+        silent_mode: false,
+        loopback_mode: false,
+        synchronisation_jump_width: 1,
+        bit_segment_1: 3,
+        bit_segment_2: 2,
+        time_quantum_length: 6,
+    });
+
+    //4 zones per filter bank, the match index is the zone
+    for bank in 0..(MAX_ZONES / 4) as u8 {
+        let zone = bank * 4;
+        can.configure_filter_bank(
+            bank,
+            &FilterBankConfiguration {
+                mode: FilterMode::List,
+                info: FilterInfo::Halves((
+                    FilterData {
+                        id: heat_demand_id(zone),
+                        mask_or_id2: heat_demand_id(zone + 1),
+                    },
+                    FilterData {
+                        id: heat_demand_id(zone + 2),
+                        mask_or_id2: heat_demand_id(zone + 3),
+                    },
+                )),
+                fifo_assignment: 0,
+                active: true,
+            },
+        );
+    }
+
+    let (tx, rx) = can.split();
+    let (mut tx0, mut _tx1, mut _tx2) = tx.split();
+    let (mut rx0, mut _rx1) = rx.split();
+
+    watchdog.feed();
+
+    let mut last_time = tick.now();
+
+    loop {
+        watchdog.feed();
+
+        let now = tick.now();
+
+        //receive the demands of the zones
+        if let Ok((filter_match_index, _time, frame)) = rx0.read() {
+            model.can_demand_received(filter_match_index as usize, frame.data().data_as_u64());
+        }
+
+        let delta = now - last_time;
+
+        if u32::from(delta) < tick.frequency {
+            continue;
+        }
+
+        led.toggle().unwrap();
+
+        let delta_sec = u32::from(delta) / tick.frequency;
+        last_time = last_time + Duration::<u32, SysTicks>::from(delta_sec * tick.frequency);
+
+        model.update_time(Time::<u32, Seconds>::from_sec(rtc.current_time()));
+
+        // drive outputs, send messages:
+        if model.coordinator.boiler_on() {
+            heat_request.set_high().unwrap();
+            rgb.color(Colors::Red).unwrap();
+        } else {
+            heat_request.set_low().unwrap();
+            //demand waiting for the min off time
+            rgb.color(if model.coordinator.demanding() > 0 {
+                Colors::Yellow
+            } else {
+                Colors::Green
+            })
+            .unwrap();
+        }
+
+        if let Some(payload) = model.status_payload() {
+            let _ = tx0.request_transmit(&Frame::new(ID_SUPPLY, Payload::new(&payload)));
+        }
+
+        model.refresh_display(&mut display).unwrap();
+    }
+}
+
+#[exception]
+fn HardFault(_ef: &ExceptionFrame) -> ! {
+    loop {}
+}
+
+#[exception]
+fn DefaultHandler(_irqn: i16) {}
@@ -20,6 +20,21 @@
 //!
 //! Heat request signal (open collector NPN transistor) on B11
 //!
+//! The heat demand is published to the heat source coordinator over CAN,
+//! the heat request signal is driven only while the coordinator is missing.
+//!
+//! The screed functional heating / drying program drives the forward temperature day by day,
+//! its progress is kept in the backup registers of the rtc over the resets, its daily log in the flash.
+//!
+//! The settings are stored in the last 4 flash pages (excluded in memory.x) a minute after the last change,
+//! and loaded at the boot. The defaults are kept if a record is missing, corrupted or of an unknown version.
+//!
+//! A config image (see room_pill::config_format, prepared by the config-tool) can be pushed over CAN
+//! in the reconfigure frames, the result is replied in the reconfigured frame.
+//!
+//! The time offset, the floor heating state, the boot and reset counters are kept in the uninitialized RAM
+//! over the resets (e.g. by the watchdog), so the control continues where it was interrupted.
+//!
 //! The remote changes the default config, the state displayed on the rgb led.
 //! Controls the floor heating accordig to the config.
 //!
@@ -39,6 +54,7 @@ use panic_halt as _;
 use room_pill;
 use stm32f1xx_hal;
 
+use core::mem::{discriminant, MaybeUninit};
 use cortex_m_rt::{entry, exception, ExceptionFrame};
 use embedded_hal::{
     digital::v2::{InputPin, OutputPin},
@@ -47,13 +63,30 @@ use embedded_hal::{
 use lcd_hal::{hx1230, hx1230::Hx1230, Display};
 use onewire::{ds18x20::*, temperature::Temperature, *};
 use room_pill::{
+    backlight,
+    codec::{crc16_update, Overflow, Reader, Writer, CRC_INIT},
+    config_format::{self, Assembler, Image, Section, MIN_SCHEMA_VERSION, SCHEMA_VERSION},
+    config_store::{self, Deferred, Flash, Stm32Flash, Store},
+    cooling::{self, Season, Switchover},
     display::*,
-    floor_heating, ir,
+    exercise, floor_heating, heat_demand, heating_curve, ir,
     ir::NecReceiver,
     ir_remote::*,
     menu::*,
+    messenger::{
+        decode_humidity, decode_open, decode_temperature, heat_demand_id, ID_HUMIDITY, ID_MOVEMENT,
+        ID_OPEN, ID_SUPPLY, ID_TEMPERATURE, LOCATION_OUTDOOR,
+    },
+    open_window, optimum_start, plausibility,
     pump::*,
+    retained::{ResetReason, Retained, RETAINED_WORDS},
     rgb::*,
+    schedule::{Mode, Schedule, Slot, SwitchPoint, MAX_SWITCH_POINTS},
+    screed::{self, Phase, Program, PROGRESS_WORDS},
+    screens::{self, error_text},
+    sensors::{Readings, Registry, Role, Rom},
+    statistics::{Statistics, HISTORY_DAYS, STATE_COUNT},
+    temperature::FixedPoint,
     timing::{Duration, Seconds, SysTicks, Ticker, Time, TimeExt, WeekTime},
     valve::*,
 };
@@ -64,6 +97,10 @@ use stm32f1xx_hal::{
 //use cortex_m_semihosting::hio;
 //use core::fmt::Write;
 
+//not initialized at the boot, so it keeps the state of the previous run over the resets
+#[link_section = ".uninit.RETAINED"]
+static mut RETAINED: MaybeUninit<[u32; RETAINED_WORDS]> = MaybeUninit::uninit();
+
 static MENU: Menu<Model, IrCommands> = Menu {
     rows: &[
         Row {
@@ -75,6 +112,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_time_weekday,
                             view: view_time_weekday,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 1,
+                                max: 7,
+                                set: enter_time_weekday,
+                            }),
                         }),
                     },
                     Row {
@@ -82,6 +125,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_time_hour,
                             view: view_time_hour,
+                            entry: Some(NumericEntry {
+                                format: Format::Time,
+                                min: 0,
+                                max: 23 * 60 + 59,
+                                set: enter_time_hour_min,
+                            }),
                         }),
                     },
                     Row {
@@ -89,6 +138,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_time_min,
                             view: view_time_min,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 0,
+                                max: 59,
+                                set: enter_time_min,
+                            }),
                         }),
                     },
                 ],
@@ -103,6 +158,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_program_day_index,
                             view: view_program_day_index,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 1,
+                                max: DAYS_PER_WEEK as i32,
+                                set: enter_program_day_index,
+                            }),
                         }),
                     },
                     Row {
@@ -110,6 +171,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_program_index,
                             view: view_program_index,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 1,
+                                max: MAX_SWITCH_POINTS as i32,
+                                set: enter_program_index,
+                            }),
                         }),
                     },
                     Row {
@@ -117,6 +184,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_program_start_hour,
                             view: view_program_start_hour,
+                            entry: Some(NumericEntry {
+                                format: Format::Time,
+                                min: 0,
+                                max: 23 * 60 + 59,
+                                set: enter_program_start_hour_min,
+                            }),
                         }),
                     },
                     Row {
@@ -124,6 +197,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_program_start_min,
                             view: view_program_start_min,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 0,
+                                max: 59,
+                                set: enter_program_start_min,
+                            }),
                         }),
                     },
                     Row {
@@ -131,6 +210,36 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_program_target_temp,
                             view: view_program_target_temp,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 50,
+                                max: 300,
+                                set: enter_program_target_temp,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Pontok",
+                        content: Content::MenuItem(Item {
+                            update: set_program_points,
+                            view: view_program_points,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Masol ide",
+                        content: Content::MenuItem(Item {
+                            update: set_program_copy_day,
+                            view: view_program_copy_day,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Masol H-P",
+                        content: Content::MenuItem(Item {
+                            update: set_program_copy_weekdays,
+                            view: view_program_copy_weekdays,
+                            entry: None,
                         }),
                     },
                 ],
@@ -145,6 +254,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_freeze_warning,
                             view: view_freeze_warning,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 0,
+                                max: 150,
+                                set: enter_freeze_warning,
+                            }),
                         }),
                     },
                     Row {
@@ -152,6 +267,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_freeze_stop,
                             view: view_freeze_stop,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 0,
+                                max: 150,
+                                set: enter_freeze_stop,
+                            }),
                         }),
                     },
                     Row {
@@ -159,6 +280,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_forward_max,
                             view: view_forward_max,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 200,
+                                max: 600,
+                                set: enter_forward_max,
+                            }),
                         }),
                     },
                     Row {
@@ -166,6 +293,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_floor_max,
                             view: view_floor_max,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 200,
+                                max: 350,
+                                set: enter_floor_max,
+                            }),
                         }),
                     },
                     Row {
@@ -173,6 +306,119 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_histeresis,
                             view: view_histeresis,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 1,
+                                max: 30,
+                                set: enter_histeresis,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Mod",
+                        content: Content::MenuItem(Item {
+                            update: set_mode,
+                            view: view_mode,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Padlo cel",
+                        content: Content::MenuItem(Item {
+                            update: set_floor_target,
+                            view: view_floor_target,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 150,
+                                max: 350,
+                                set: enter_floor_target,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Padlo min",
+                        content: Content::MenuItem(Item {
+                            update: set_floor_min,
+                            view: view_floor_min,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 150,
+                                max: 350,
+                                set: enter_floor_min,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Padlo hiszt",
+                        content: Content::MenuItem(Item {
+                            update: set_floor_histeresis,
+                            view: view_floor_histeresis,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 1,
+                                max: 30,
+                                set: enter_floor_histeresis,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Szabalyozas",
+                        content: Content::MenuItem(Item {
+                            update: set_control,
+                            view: view_control,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"PID Kp",
+                        content: Content::MenuItem(Item {
+                            update: set_pid_kp,
+                            view: view_pid_kp,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 0,
+                                max: 9999,
+                                set: enter_pid_kp,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"PID Ki",
+                        content: Content::MenuItem(Item {
+                            update: set_pid_ki,
+                            view: view_pid_ki,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 0,
+                                max: 9999,
+                                set: enter_pid_ki,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"PID Kd",
+                        content: Content::MenuItem(Item {
+                            update: set_pid_kd,
+                            view: view_pid_kd,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 0,
+                                max: 9999,
+                                set: enter_pid_kd,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"PID ciklus",
+                        content: Content::MenuItem(Item {
+                            update: set_pid_cycle,
+                            view: view_pid_cycle,
+                            entry: Some(NumericEntry {
+                                format: Format::Time,
+                                min: 10,
+                                max: 23 * 60 + 59,
+                                set: enter_pid_cycle,
+                            }),
                         }),
                     },
                     Row {
@@ -180,6 +426,12 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_after_circulation,
                             view: view_after_circulation,
+                            entry: Some(NumericEntry {
+                                format: Format::Time,
+                                min: 15,
+                                max: 23 * 60 + 59,
+                                set: enter_after_circulation,
+                            }),
                         }),
                     },
                     Row {
@@ -187,394 +439,2100 @@ static MENU: Menu<Model, IrCommands> = Menu {
                         content: Content::MenuItem(Item {
                             update: set_pre_circulation,
                             view: view_pre_circulation,
+                            entry: Some(NumericEntry {
+                                format: Format::Time,
+                                min: 15,
+                                max: 23 * 60 + 59,
+                                set: enter_pre_circulation,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Kikapcsol",
+                        content: Content::MenuItem(Item {
+                            update: set_backlight_off,
+                            view: view_backlight_off,
+                            entry: Some(NumericEntry {
+                                format: Format::Time,
+                                min: 15,
+                                max: 23 * 60 + 59,
+                                set: enter_backlight_off,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Mozgasra",
+                        content: Content::MenuItem(Item {
+                            update: set_wake_on_movement,
+                            view: view_wake_on_movement,
+                            entry: None,
                         }),
                     },
                 ],
             }),
         },
-    ],
-};
-
-fn set_time_weekday(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model.weektime = WeekTime {
-                weekday: (model.weektime.weekday + 1) % DAYS_PER_WEEK,
-                ..model.weektime
-            };
-            model.update_time_offset();
-        }
-        IrCommands::Left => {
-            model.weektime = WeekTime {
-                weekday: (model.weektime.weekday - 1) % DAYS_PER_WEEK,
-                ..model.weektime
-            };
-            model.update_time_offset();
-        }
-        _ => {}
-    };
-}
-
-fn set_program_day_index(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model.programmed_index =
-                (model.programmed_index + PROGRAMS_PER_DAY) % (DAYS_PER_WEEK * PROGRAMS_PER_DAY);
-        }
-        IrCommands::Left => {
-            model.programmed_index =
-                (model.programmed_index - PROGRAMS_PER_DAY) % (DAYS_PER_WEEK * PROGRAMS_PER_DAY);
-        }
-        _ => {}
-    };
-}
-fn set_program_index(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model.programmed_index =
-                if model.programmed_index % PROGRAMS_PER_DAY == PROGRAMS_PER_DAY - 1 {
-                    model.programmed_index - (PROGRAMS_PER_DAY - 1)
-                } else {
-                    model.programmed_index + 1
-                }
-        }
-        IrCommands::Left => {
-            model.programmed_index = if model.programmed_index % PROGRAMS_PER_DAY == 0 {
-                model.programmed_index + (PROGRAMS_PER_DAY - 1)
-            } else {
-                model.programmed_index - 1
-            }
-        }
-        _ => {}
-    };
-}
-
-fn set_program_start_hour(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            let wt = WeekTime::from(model.program[model.programmed_index as usize].start_time);
-            let wt = WeekTime {
-                hour: (wt.hour + 1) % 24,
-                ..wt
-            };
-            model.program[model.programmed_index as usize].start_time =
-                Time::<u32, Seconds>::from(wt);
-        }
-        IrCommands::Left => {
-            let wt = WeekTime::from(model.program[model.programmed_index as usize].start_time);
-            let wt = WeekTime {
-                hour: (wt.hour - 1) % 24,
-                ..wt
-            };
-            model.program[model.programmed_index as usize].start_time =
-                Time::<u32, Seconds>::from(wt);
-        }
-        _ => {
-            return;
-        }
-    }
-}
-fn set_program_start_min(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            let wt = WeekTime::from(model.program[model.programmed_index as usize].start_time);
-            let wt = WeekTime {
-                min: (wt.min + 10) % 60,
-                ..wt
-            };
-            model.program[model.programmed_index as usize].start_time =
-                Time::<u32, Seconds>::from(wt);
-        }
-        IrCommands::Left => {
-            let wt = WeekTime::from(model.program[model.programmed_index as usize].start_time);
-            let wt = WeekTime {
-                min: (wt.min - 10) % 60,
-                ..wt
-            };
-            model.program[model.programmed_index as usize].start_time =
-                Time::<u32, Seconds>::from(wt);
-        }
-        _ => {
-            return;
-        }
-    }
-}
-fn set_program_target_temp(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model.program[model.programmed_index as usize].target_air_temperature =
-                model.program[model.programmed_index as usize].target_air_temperature
-                    + Temperature::from_celsius(0, 2);
-        }
-        IrCommands::Left => {
-            model.program[model.programmed_index as usize].target_air_temperature =
-                model.program[model.programmed_index as usize].target_air_temperature
-                    - Temperature::from_celsius(0, 2);
-        }
-        _ => {
-            return;
-        }
-    }
-}
-
-fn view_program_day_index(model: &Model) -> &'static [u8] {
-    fmt_weekday(model.programmed_index / PROGRAMS_PER_DAY)
-}
-fn view_program_index(model: &Model) -> &'static [u8] {
-    unsafe { fmt_nn(((model.programmed_index % PROGRAMS_PER_DAY) + 1) as u8) }
-}
-fn view_program_start_hour(model: &Model) -> &'static [u8] {
-    unsafe {
-        fmt_nn(WeekTime::from(model.program[model.programmed_index as usize].start_time).hour)
-    }
-}
-fn view_program_start_min(model: &Model) -> &'static [u8] {
-    unsafe { fmt_nn(WeekTime::from(model.program[model.programmed_index as usize].start_time).min) }
-}
-fn view_program_target_temp(model: &Model) -> &'static [u8] {
-    unsafe { fmt_temp(model.program[model.programmed_index as usize].target_air_temperature) }
-}
-
-fn view_time_weekday(model: &Model) -> &'static [u8] {
-    fmt_weekday(model.weektime.weekday)
-}
-fn view_time_hour(model: &Model) -> &'static [u8] {
-    unsafe { fmt_nn(model.weektime.hour) }
-}
-fn view_time_min(model: &Model) -> &'static [u8] {
-    unsafe { fmt_nn(model.weektime.min) }
-}
-
-fn set_time_hour(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model.weektime = WeekTime {
-                hour: (model.weektime.hour + 1) % 24,
-                ..model.weektime
-            }
-        }
-        IrCommands::Left => {
-            model.weektime = WeekTime {
-                hour: (model.weektime.hour - 1) % 24,
-                ..model.weektime
-            }
-        }
-        _ => {
-            return;
-        }
-    }
-    model.update_time_offset();
-}
-fn set_time_min(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model.weektime = WeekTime {
-                min: (model.weektime.min + 1) % 60,
-                sec: 0,
-                ..model.weektime
-            }
-        }
-        IrCommands::Left => {
-            model.weektime = WeekTime {
-                min: (model.weektime.min - 1) % 60,
-                sec: 0,
-                ..model.weektime
-            }
-        }
-        _ => {
-            return;
-        }
-    }
-    model.update_time_offset();
-}
-
-fn set_after_circulation(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model.floor_heating_config.after_circulation_duration =
-                model.floor_heating_config.after_circulation_duration + 15u32.s();
-        }
-        IrCommands::Left => {
-            if model.floor_heating_config.after_circulation_duration > Duration::from_hms(0, 0, 15)
-            {
-                model.floor_heating_config.after_circulation_duration =
-                    model.floor_heating_config.after_circulation_duration
-                        - Duration::from_hms(0, 0, 15);
-            }
-        }
-        _ => {}
-    }
-}
-fn set_pre_circulation(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model.floor_heating_config.pre_circulation_duration =
-                model.floor_heating_config.pre_circulation_duration + 15.s();
-        }
-        IrCommands::Left => {
-            if model.floor_heating_config.pre_circulation_duration > 15.s() {
-                model.floor_heating_config.pre_circulation_duration =
-                    model.floor_heating_config.pre_circulation_duration - 15.s();
-            }
-        }
-        _ => {}
-    }
-}
-
-fn view_after_circulation(model: &Model) -> &'static [u8] {
-    unsafe { fmt_duration(&model.floor_heating_config.after_circulation_duration) }
-}
-fn view_pre_circulation(model: &Model) -> &'static [u8] {
-    unsafe { fmt_duration(&model.floor_heating_config.pre_circulation_duration) }
-}
-
-fn set_freeze_warning(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model
+        Row {
+            text: b"Futesgorbe",
+            content: Content::SubMenu(Menu {
+                rows: &[
+                    Row {
+                        text: b"Bekapcsolva",
+                        content: Content::MenuItem(Item {
+                            update: set_curve_enabled,
+                            view: view_curve_enabled,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Meredekseg",
+                        content: Content::MenuItem(Item {
+                            update: set_curve_slope,
+                            view: view_curve_slope,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 1,
+                                max: 50,
+                                set: enter_curve_slope,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Eltolas",
+                        content: Content::MenuItem(Item {
+                            update: set_curve_offset,
+                            view: view_curve_offset,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 0,
+                                max: 150,
+                                set: enter_curve_offset,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Szoba tenyezo",
+                        content: Content::MenuItem(Item {
+                            update: set_curve_room_factor,
+                            view: view_curve_room_factor,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 0,
+                                max: 100,
+                                set: enter_curve_room_factor,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Elore Min",
+                        content: Content::MenuItem(Item {
+                            update: set_curve_forward_min,
+                            view: view_curve_forward_min,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 150,
+                                max: 400,
+                                set: enter_curve_forward_min,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Hiszterezis",
+                        content: Content::MenuItem(Item {
+                            update: set_curve_histeresis,
+                            view: view_curve_histeresis,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 5,
+                                max: 100,
+                                set: enter_curve_histeresis,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Modulacio",
+                        content: Content::MenuItem(Item {
+                            update: set_curve_modulation,
+                            view: view_curve_modulation,
+                            entry: None,
+                        }),
+                    },
+                ],
+            }),
+        },
+        Row {
+            text: b"Szenzorok",
+            content: Content::SubMenu(Menu {
+                rows: &[
+                    Row {
+                        text: b"Elore",
+                        content: Content::MenuItem(Item {
+                            update: set_sensor_forward,
+                            view: view_sensor_forward,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Vissza",
+                        content: Content::MenuItem(Item {
+                            update: set_sensor_return,
+                            view: view_sensor_return,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Padlo",
+                        content: Content::MenuItem(Item {
+                            update: set_sensor_floor,
+                            view: view_sensor_floor,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Levego",
+                        content: Content::MenuItem(Item {
+                            update: set_sensor_air,
+                            view: view_sensor_air,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Kulso",
+                        content: Content::MenuItem(Item {
+                            update: set_sensor_outdoor,
+                            view: view_sensor_outdoor,
+                            entry: None,
+                        }),
+                    },
+                ],
+            }),
+        },
+        Row {
+            text: b"Atmozgatas",
+            content: Content::SubMenu(Menu {
+                rows: &[
+                    Row {
+                        text: b"Bekapcsolva",
+                        content: Content::MenuItem(Item {
+                            update: set_exercise_enabled,
+                            view: view_exercise_enabled,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Nap",
+                        content: Content::MenuItem(Item {
+                            update: set_exercise_weekday,
+                            view: view_exercise_weekday,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 1,
+                                max: 7,
+                                set: enter_exercise_weekday,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Ora",
+                        content: Content::MenuItem(Item {
+                            update: set_exercise_hour,
+                            view: view_exercise_hour,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 0,
+                                max: 23,
+                                set: enter_exercise_hour,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Idotartam",
+                        content: Content::MenuItem(Item {
+                            update: set_exercise_duration,
+                            view: view_exercise_duration,
+                            entry: Some(NumericEntry {
+                                format: Format::Time,
+                                min: 15,
+                                max: 23 * 60 + 59,
+                                set: enter_exercise_duration,
+                            }),
+                        }),
+                    },
+                ],
+            }),
+        },
+        Row {
+            text: b"Optimalis inditas",
+            content: Content::SubMenu(Menu {
+                rows: &[
+                    Row {
+                        text: b"Bekapcsolva",
+                        content: Content::MenuItem(Item {
+                            update: set_optimum_enabled,
+                            view: view_optimum_enabled,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Max elofutes",
+                        content: Content::MenuItem(Item {
+                            update: set_optimum_max_preheat,
+                            view: view_optimum_max_preheat,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 1,
+                                max: 12,
+                                set: enter_optimum_max_preheat,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"dT 0-5",
+                        content: Content::MenuItem(Item {
+                            update: set_none,
+                            view: view_optimum_rate_0,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"dT 5-10",
+                        content: Content::MenuItem(Item {
+                            update: set_none,
+                            view: view_optimum_rate_1,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"dT 10-15",
+                        content: Content::MenuItem(Item {
+                            update: set_none,
+                            view: view_optimum_rate_2,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"dT 15-20",
+                        content: Content::MenuItem(Item {
+                            update: set_none,
+                            view: view_optimum_rate_3,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"dT 20-25",
+                        content: Content::MenuItem(Item {
+                            update: set_none,
+                            view: view_optimum_rate_4,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"dT 25+",
+                        content: Content::MenuItem(Item {
+                            update: set_none,
+                            view: view_optimum_rate_5,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Tanult",
+                        content: Content::MenuItem(Item {
+                            update: set_optimum_learned,
+                            view: view_optimum_learned,
+                            entry: None,
+                        }),
+                    },
+                ],
+            }),
+        },
+        Row {
+            text: b"Tavollet",
+            content: Content::SubMenu(Menu {
+                rows: &[
+                    Row {
+                        text: b"Bekapcsolva",
+                        content: Content::MenuItem(Item {
+                            update: set_away,
+                            view: view_away,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Napok",
+                        content: Content::MenuItem(Item {
+                            update: set_away_days,
+                            view: view_away_days,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 0,
+                                max: 365,
+                                set: enter_away_days,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Ora",
+                        content: Content::MenuItem(Item {
+                            update: set_away_hour,
+                            view: view_away_hour,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 0,
+                                max: 23,
+                                set: enter_away_hour,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Tartas",
+                        content: Content::MenuItem(Item {
+                            update: set_away_setback,
+                            view: view_away_setback,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 50,
+                                max: 200,
+                                set: enter_away_setback,
+                            }),
+                        }),
+                    },
+                ],
+            }),
+        },
+        Row {
+            text: b"Hutes",
+            content: Content::SubMenu(Menu {
+                rows: &[
+                    Row {
+                        text: b"Evszak",
+                        content: Content::MenuItem(Item {
+                            update: set_season,
+                            view: view_season,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Cel",
+                        content: Content::MenuItem(Item {
+                            update: set_cooling_target,
+                            view: view_cooling_target,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 180,
+                                max: 320,
+                                set: enter_cooling_target,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Hiszterezis",
+                        content: Content::MenuItem(Item {
+                            update: set_cooling_histeresis,
+                            view: view_cooling_histeresis,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 1,
+                                max: 30,
+                                set: enter_cooling_histeresis,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Harmatpont +",
+                        content: Content::MenuItem(Item {
+                            update: set_dew_point_margin,
+                            view: view_dew_point_margin,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 0,
+                                max: 50,
+                                set: enter_dew_point_margin,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Hutes felett",
+                        content: Content::MenuItem(Item {
+                            update: set_cooling_above,
+                            view: view_cooling_above,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 100,
+                                max: 350,
+                                set: enter_cooling_above,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Futes alatt",
+                        content: Content::MenuItem(Item {
+                            update: set_heating_below,
+                            view: view_heating_below,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 0,
+                                max: 300,
+                                set: enter_heating_below,
+                            }),
+                        }),
+                    },
+                ],
+            }),
+        },
+        Row {
+            text: b"Esztrich",
+            content: Content::SubMenu(Menu {
+                rows: &[
+                    Row {
+                        text: b"Program",
+                        content: Content::MenuItem(Item {
+                            update: set_screed_program,
+                            view: view_screed_program,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Nap",
+                        content: Content::MenuItem(Item {
+                            update: set_screed_day,
+                            view: view_screed_day,
+                            entry: Some(NumericEntry {
+                                format: Format::Integer,
+                                min: 1,
+                                max: screed::MAX_LOG_DAYS as i32,
+                                set: enter_screed_day,
+                            }),
+                        }),
+                    },
+                ],
+            }),
+        },
+        Row {
+            text: b"Nyitott ablak",
+            content: Content::SubMenu(Menu {
+                rows: &[
+                    Row {
+                        text: b"Bekapcsolva",
+                        content: Content::MenuItem(Item {
+                            update: set_window_enabled,
+                            view: view_window_enabled,
+                            entry: None,
+                        }),
+                    },
+                    Row {
+                        text: b"Csokkenes",
+                        content: Content::MenuItem(Item {
+                            update: set_window_drop,
+                            view: view_window_drop,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 1,
+                                max: 50,
+                                set: enter_window_drop,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Idoszak",
+                        content: Content::MenuItem(Item {
+                            update: set_window_drop_period,
+                            view: view_window_drop_period,
+                            entry: Some(NumericEntry {
+                                format: Format::Time,
+                                min: 60,
+                                max: 59 * 60 + 59,
+                                set: enter_window_drop_period,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Emelkedes",
+                        content: Content::MenuItem(Item {
+                            update: set_window_recovery,
+                            view: view_window_recovery,
+                            entry: Some(NumericEntry {
+                                format: Format::Decimal,
+                                min: 1,
+                                max: 50,
+                                set: enter_window_recovery,
+                            }),
+                        }),
+                    },
+                    Row {
+                        text: b"Szunet",
+                        content: Content::MenuItem(Item {
+                            update: set_window_suspend,
+                            view: view_window_suspend,
+                            entry: Some(NumericEntry {
+                                format: Format::Time,
+                                min: 60,
+                                max: 59 * 60 + 59,
+                                set: enter_window_suspend,
+                            }),
+                        }),
+                    },
+                ],
+            }),
+        },
+    ],
+};
+
+fn set_time_weekday(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.weektime = WeekTime {
+                weekday: (model.weektime.weekday + 1) % DAYS_PER_WEEK,
+                ..model.weektime
+            };
+            model.update_time_offset();
+        }
+        IrCommands::Left => {
+            model.weektime = WeekTime {
+                weekday: (model.weektime.weekday - 1) % DAYS_PER_WEEK,
+                ..model.weektime
+            };
+            model.update_time_offset();
+        }
+        _ => {}
+    };
+}
+
+/// The selected switch point of the weekly program
+fn program_point(model: &Model) -> Option<SwitchPoint<Temperature>> {
+    model
+        .program
+        .day(model.programmed_day)
+        .get(model.programmed_index)
+        .copied()
+}
+
+/// Moves the selected switch point, the selection follows it within the sorted day.
+/// The program is unchanged if an other switch point starts at the same time.
+fn move_program_point(model: &mut Model, hour: u8, min: u8) {
+    if let Some(point) = program_point(model) {
+        let day = model.program.day_mut(model.programmed_day);
+        if let Ok(index) = day.set(
+            model.programmed_index,
+            SwitchPoint::new(hour, min, point.target),
+        ) {
+            model.programmed_index = index;
+        }
+    }
+}
+
+fn set_program_day_index(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.programmed_day = (model.programmed_day + 1) % DAYS_PER_WEEK;
+        }
+        IrCommands::Left => {
+            model.programmed_day = (model.programmed_day + DAYS_PER_WEEK - 1) % DAYS_PER_WEEK;
+        }
+        _ => return,
+    };
+    model.programmed_index = 0;
+}
+fn set_program_index(model: &mut Model, command: IrCommands) {
+    let count = model.program.day(model.programmed_day).len();
+    if count == 0 {
+        return;
+    }
+    match command {
+        IrCommands::Right => {
+            model.programmed_index = (model.programmed_index + 1) % count;
+        }
+        IrCommands::Left => {
+            model.programmed_index = (model.programmed_index + count - 1) % count;
+        }
+        _ => {}
+    };
+}
+
+fn set_program_start_hour(model: &mut Model, command: IrCommands) {
+    if let Some(point) = program_point(model) {
+        match command {
+            IrCommands::Right => move_program_point(model, (point.hour + 1) % 24, point.min),
+            IrCommands::Left => move_program_point(model, (point.hour + 23) % 24, point.min),
+            _ => {}
+        }
+    }
+}
+fn set_program_start_min(model: &mut Model, command: IrCommands) {
+    if let Some(point) = program_point(model) {
+        match command {
+            IrCommands::Right => move_program_point(model, point.hour, (point.min + 10) % 60),
+            IrCommands::Left => move_program_point(model, point.hour, (point.min + 50) % 60),
+            _ => {}
+        }
+    }
+}
+fn set_program_target_temp(model: &mut Model, command: IrCommands) {
+    if let Some(point) = program_point(model) {
+        let target = match command {
+            IrCommands::Right => point.target + Temperature::from_celsius(0, 2),
+            IrCommands::Left => point.target - Temperature::from_celsius(0, 2),
+            _ => return,
+        };
+        let _ = model
+            .program
+            .day_mut(model.programmed_day)
+            .set_target(model.programmed_index, target);
+    }
+}
+//right: new switch point an hour after the selected one, left: the selected one is deleted
+fn set_program_points(model: &mut Model, command: IrCommands) {
+    let day = model.program.day_mut(model.programmed_day);
+    match command {
+        IrCommands::Right => {
+            let point = match day.get(model.programmed_index) {
+                Some(point) => SwitchPoint::new((point.hour + 1) % 24, point.min, point.target),
+                None => SwitchPoint::new(6, 0, Temperature::from_celsius(20, 0)),
+            };
+            if let Ok(index) = day.add(point) {
+                model.programmed_index = index;
+            }
+        }
+        IrCommands::Left => {
+            if day.remove(model.programmed_index).is_ok() && model.programmed_index > 0 {
+                model.programmed_index -= 1;
+            }
+        }
+        _ => {}
+    }
+}
+//the day is copied to the next day, which gets selected, so the copy can be repeated
+fn set_program_copy_day(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            let next = (model.programmed_day + 1) % DAYS_PER_WEEK;
+            model.program.copy_day(model.programmed_day, next);
+            model.programmed_day = next;
+        }
+        _ => {}
+    }
+}
+fn set_program_copy_weekdays(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            model.program.copy_to_weekdays(model.programmed_day);
+        }
+        _ => {}
+    }
+}
+
+fn view_program_day_index(model: &Model) -> &'static [u8] {
+    fmt_weekday(model.programmed_day)
+}
+fn view_program_index(model: &Model) -> &'static [u8] {
+    match program_point(model) {
+        Some(_) => unsafe { fmt_nn((model.programmed_index + 1) as u8) },
+        None => b"--",
+    }
+}
+fn view_program_start_hour(model: &Model) -> &'static [u8] {
+    match program_point(model) {
+        Some(point) => unsafe { fmt_nn(point.hour) },
+        None => b"--",
+    }
+}
+fn view_program_start_min(model: &Model) -> &'static [u8] {
+    match program_point(model) {
+        Some(point) => unsafe { fmt_nn(point.min) },
+        None => b"--",
+    }
+}
+fn view_program_target_temp(model: &Model) -> &'static [u8] {
+    match program_point(model) {
+        Some(point) => unsafe { fmt_temp(point.target) },
+        None => b"--",
+    }
+}
+fn view_program_points(model: &Model) -> &'static [u8] {
+    unsafe { fmt_nn(model.program.day(model.programmed_day).len() as u8) }
+}
+fn view_program_copy_day(model: &Model) -> &'static [u8] {
+    fmt_weekday((model.programmed_day + 1) % DAYS_PER_WEEK)
+}
+fn view_program_copy_weekdays(_model: &Model) -> &'static [u8] {
+    b"H-P"
+}
+
+fn view_time_weekday(model: &Model) -> &'static [u8] {
+    fmt_weekday(model.weektime.weekday)
+}
+fn view_time_hour(model: &Model) -> &'static [u8] {
+    unsafe { fmt_nn(model.weektime.hour) }
+}
+fn view_time_min(model: &Model) -> &'static [u8] {
+    unsafe { fmt_nn(model.weektime.min) }
+}
+
+fn set_time_hour(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.weektime = WeekTime {
+                hour: (model.weektime.hour + 1) % 24,
+                ..model.weektime
+            }
+        }
+        IrCommands::Left => {
+            model.weektime = WeekTime {
+                hour: (model.weektime.hour - 1) % 24,
+                ..model.weektime
+            }
+        }
+        _ => {
+            return;
+        }
+    }
+    model.update_time_offset();
+}
+fn set_time_min(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.weektime = WeekTime {
+                min: (model.weektime.min + 1) % 60,
+                sec: 0,
+                ..model.weektime
+            }
+        }
+        IrCommands::Left => {
+            model.weektime = WeekTime {
+                min: (model.weektime.min - 1) % 60,
+                sec: 0,
+                ..model.weektime
+            }
+        }
+        _ => {
+            return;
+        }
+    }
+    model.update_time_offset();
+}
+
+fn set_after_circulation(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.after_circulation_duration =
+                model.floor_heating_config.after_circulation_duration + 15u32.s();
+        }
+        IrCommands::Left => {
+            if model.floor_heating_config.after_circulation_duration > Duration::from_hms(0, 0, 15)
+            {
+                model.floor_heating_config.after_circulation_duration =
+                    model.floor_heating_config.after_circulation_duration
+                        - Duration::from_hms(0, 0, 15);
+            }
+        }
+        _ => {}
+    }
+}
+fn set_pre_circulation(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.pre_circulation_duration =
+                model.floor_heating_config.pre_circulation_duration + 15.s();
+        }
+        IrCommands::Left => {
+            if model.floor_heating_config.pre_circulation_duration > 15.s() {
+                model.floor_heating_config.pre_circulation_duration =
+                    model.floor_heating_config.pre_circulation_duration - 15.s();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn set_backlight_off(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.backlight_config.off_timeout = model.backlight_config.off_timeout + 15.s();
+        }
+        IrCommands::Left => {
+            if model.backlight_config.off_timeout > 15.s() {
+                model.backlight_config.off_timeout = model.backlight_config.off_timeout - 15.s();
+            }
+        }
+        _ => {}
+    }
+}
+fn set_wake_on_movement(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            model.backlight_config.wake_on_movement = !model.backlight_config.wake_on_movement;
+        }
+        _ => {}
+    }
+}
+
+fn view_backlight_off(model: &Model) -> &'static [u8] {
+    unsafe { fmt_duration(&model.backlight_config.off_timeout) }
+}
+fn view_wake_on_movement(model: &Model) -> &'static [u8] {
+    if model.backlight_config.wake_on_movement {
+        b"Igen"
+    } else {
+        b"Nem"
+    }
+}
+
+fn view_after_circulation(model: &Model) -> &'static [u8] {
+    unsafe { fmt_duration(&model.floor_heating_config.after_circulation_duration) }
+}
+fn view_pre_circulation(model: &Model) -> &'static [u8] {
+    unsafe { fmt_duration(&model.floor_heating_config.pre_circulation_duration) }
+}
+
+fn set_freeze_warning(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model
+                .floor_heating_config
+                .freeze_protection
+                .safe_temperature = model
+                .floor_heating_config
+                .freeze_protection
+                .safe_temperature
+                + Temperature::from_celsius(0, 4);
+        }
+        IrCommands::Left => {
+            model
+                .floor_heating_config
+                .freeze_protection
+                .safe_temperature = model
+                .floor_heating_config
+                .freeze_protection
+                .safe_temperature
+                - Temperature::from_celsius(0, 4);
+        }
+        _ => {}
+    }
+}
+fn set_freeze_stop(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.freeze_protection.min_temperature =
+                model.floor_heating_config.freeze_protection.min_temperature
+                    + Temperature::from_celsius(0, 4);
+        }
+        IrCommands::Left => {
+            model.floor_heating_config.freeze_protection.min_temperature =
+                model.floor_heating_config.freeze_protection.min_temperature
+                    - Temperature::from_celsius(0, 4);
+        }
+        _ => {}
+    }
+}
+
+fn set_forward_max(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.max_forward_temperature =
+                model.floor_heating_config.max_forward_temperature
+                    + Temperature::from_celsius(0, 4);
+        }
+        IrCommands::Left => {
+            model.floor_heating_config.max_forward_temperature =
+                model.floor_heating_config.max_forward_temperature
+                    - Temperature::from_celsius(0, 4);
+        }
+        _ => {}
+    }
+}
+fn set_floor_max(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.max_floor_temperature =
+                model.floor_heating_config.max_floor_temperature + Temperature::from_celsius(0, 4);
+        }
+        IrCommands::Left => {
+            model.floor_heating_config.max_floor_temperature =
+                model.floor_heating_config.max_floor_temperature - Temperature::from_celsius(0, 4);
+        }
+        _ => {}
+    }
+}
+fn set_histeresis(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.temperature_histeresis =
+                model.floor_heating_config.temperature_histeresis + Temperature::from_celsius(0, 1);
+        }
+        IrCommands::Left => {
+            if model.floor_heating_config.temperature_histeresis > Temperature::from_celsius(0, 1) {
+                model.floor_heating_config.temperature_histeresis =
+                    model.floor_heating_config.temperature_histeresis
+                        - Temperature::from_celsius(0, 1);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn set_mode(model: &mut Model, command: IrCommands) {
+    let mode = match (model.floor_heating_config.mode, command) {
+        (floor_heating::Mode::Air, IrCommands::Right) => floor_heating::Mode::Floor,
+        (floor_heating::Mode::Floor, IrCommands::Right) => floor_heating::Mode::AirMinFloor,
+        (floor_heating::Mode::AirMinFloor, IrCommands::Right) => floor_heating::Mode::Air,
+        (floor_heating::Mode::Air, IrCommands::Left) => floor_heating::Mode::AirMinFloor,
+        (floor_heating::Mode::Floor, IrCommands::Left) => floor_heating::Mode::Air,
+        (floor_heating::Mode::AirMinFloor, IrCommands::Left) => floor_heating::Mode::Floor,
+        _ => return,
+    };
+    model.floor_heating_config.mode = mode;
+}
+fn set_floor_target(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.floor.target_temperature =
+                model.floor_heating_config.floor.target_temperature
+                    + Temperature::from_celsius(0, 8);
+        }
+        IrCommands::Left => {
+            if model.floor_heating_config.floor.target_temperature
+                > Temperature::from_celsius(15, 0)
+            {
+                model.floor_heating_config.floor.target_temperature =
+                    model.floor_heating_config.floor.target_temperature
+                        - Temperature::from_celsius(0, 8);
+            }
+        }
+        _ => {}
+    }
+}
+fn set_floor_min(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.floor.min_temperature =
+                model.floor_heating_config.floor.min_temperature + Temperature::from_celsius(0, 8);
+        }
+        IrCommands::Left => {
+            if model.floor_heating_config.floor.min_temperature > Temperature::from_celsius(15, 0) {
+                model.floor_heating_config.floor.min_temperature =
+                    model.floor_heating_config.floor.min_temperature
+                        - Temperature::from_celsius(0, 8);
+            }
+        }
+        _ => {}
+    }
+}
+fn set_floor_histeresis(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.floor.histeresis =
+                model.floor_heating_config.floor.histeresis + Temperature::from_celsius(0, 8);
+        }
+        IrCommands::Left => {
+            if model.floor_heating_config.floor.histeresis > Temperature::from_celsius(0, 8) {
+                model.floor_heating_config.floor.histeresis =
+                    model.floor_heating_config.floor.histeresis - Temperature::from_celsius(0, 8);
+            }
+        }
+        _ => {}
+    }
+}
+fn set_control(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            model.floor_heating_config.control = match model.floor_heating_config.control {
+                floor_heating::Control::Histeresis => floor_heating::Control::Pid,
+                floor_heating::Control::Pid => floor_heating::Control::Histeresis,
+            };
+        }
+        _ => {}
+    }
+}
+fn set_pid_kp(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => model.floor_heating_config.pid.kp += 50,
+        IrCommands::Left if model.floor_heating_config.pid.kp >= 50 => {
+            model.floor_heating_config.pid.kp -= 50
+        }
+        _ => {}
+    }
+}
+fn set_pid_ki(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => model.floor_heating_config.pid.ki += 10,
+        IrCommands::Left if model.floor_heating_config.pid.ki >= 10 => {
+            model.floor_heating_config.pid.ki -= 10
+        }
+        _ => {}
+    }
+}
+fn set_pid_kd(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => model.floor_heating_config.pid.kd += 50,
+        IrCommands::Left if model.floor_heating_config.pid.kd >= 50 => {
+            model.floor_heating_config.pid.kd -= 50
+        }
+        _ => {}
+    }
+}
+fn set_pid_cycle(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.pid.cycle_period =
+                model.floor_heating_config.pid.cycle_period + 60.s();
+        }
+        IrCommands::Left => {
+            if model.floor_heating_config.pid.cycle_period > 60.s() {
+                model.floor_heating_config.pid.cycle_period =
+                    model.floor_heating_config.pid.cycle_period - 60.s();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn view_mode(model: &Model) -> &'static [u8] {
+    match model.floor_heating_config.mode {
+        floor_heating::Mode::Air => b"Levego",
+        floor_heating::Mode::Floor => b"Padlo",
+        floor_heating::Mode::AirMinFloor => b"Lev+padlo",
+    }
+}
+fn view_floor_target(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.floor_heating_config.floor.target_temperature) }
+}
+fn view_floor_min(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.floor_heating_config.floor.min_temperature) }
+}
+fn view_floor_histeresis(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.floor_heating_config.floor.histeresis) }
+}
+fn view_control(model: &Model) -> &'static [u8] {
+    match model.floor_heating_config.control {
+        floor_heating::Control::Histeresis => b"Hiszt",
+        floor_heating::Control::Pid => b"PID",
+    }
+}
+fn view_pid_kp(model: &Model) -> &'static [u8] {
+    unsafe { fmt_u32(model.floor_heating_config.pid.kp as u32) }
+}
+fn view_pid_ki(model: &Model) -> &'static [u8] {
+    unsafe { fmt_u32(model.floor_heating_config.pid.ki as u32) }
+}
+fn view_pid_kd(model: &Model) -> &'static [u8] {
+    unsafe { fmt_u32(model.floor_heating_config.pid.kd as u32) }
+}
+fn view_pid_cycle(model: &Model) -> &'static [u8] {
+    unsafe { fmt_duration(&model.floor_heating_config.pid.cycle_period) }
+}
+
+fn view_freeze_warning(model: &Model) -> &'static [u8] {
+    unsafe {
+        fmt_temp(
+            model
                 .floor_heating_config
                 .freeze_protection
-                .safe_temperature = model
+                .safe_temperature,
+        )
+    }
+}
+fn view_freeze_stop(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.floor_heating_config.freeze_protection.min_temperature) }
+}
+fn view_forward_max(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.floor_heating_config.max_forward_temperature) }
+}
+fn view_floor_max(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.floor_heating_config.max_floor_temperature) }
+}
+fn view_histeresis(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.floor_heating_config.temperature_histeresis) }
+}
+
+fn set_curve_enabled(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            model.floor_heating_config.heating_curve.enabled =
+                !model.floor_heating_config.heating_curve.enabled;
+        }
+        _ => {}
+    }
+}
+fn set_curve_slope(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => model.floor_heating_config.heating_curve.slope += 1,
+        IrCommands::Left if model.floor_heating_config.heating_curve.slope > 1 => {
+            model.floor_heating_config.heating_curve.slope -= 1
+        }
+        _ => {}
+    }
+}
+fn set_curve_offset(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.heating_curve.offset =
+                model.floor_heating_config.heating_curve.offset + Temperature::from_celsius(0, 8);
+        }
+        IrCommands::Left => {
+            model.floor_heating_config.heating_curve.offset =
+                model.floor_heating_config.heating_curve.offset - Temperature::from_celsius(0, 8);
+        }
+        _ => {}
+    }
+}
+fn set_curve_room_factor(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => model.floor_heating_config.heating_curve.room_factor += 5,
+        IrCommands::Left if model.floor_heating_config.heating_curve.room_factor >= 5 => {
+            model.floor_heating_config.heating_curve.room_factor -= 5
+        }
+        _ => {}
+    }
+}
+fn set_curve_forward_min(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model
                 .floor_heating_config
-                .freeze_protection
-                .safe_temperature
-                + Temperature::from_celsius(0, 4);
+                .heating_curve
+                .min_forward_temperature = model
+                .floor_heating_config
+                .heating_curve
+                .min_forward_temperature
+                + Temperature::from_celsius(1, 0);
+        }
+        IrCommands::Left => {
+            model
+                .floor_heating_config
+                .heating_curve
+                .min_forward_temperature = model
+                .floor_heating_config
+                .heating_curve
+                .min_forward_temperature
+                - Temperature::from_celsius(1, 0);
+        }
+        _ => {}
+    }
+}
+fn set_curve_histeresis(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.floor_heating_config.heating_curve.histeresis =
+                model.floor_heating_config.heating_curve.histeresis
+                    + Temperature::from_celsius(0, 8);
+        }
+        IrCommands::Left => {
+            if model.floor_heating_config.heating_curve.histeresis > Temperature::from_celsius(1, 0)
+            {
+                model.floor_heating_config.heating_curve.histeresis =
+                    model.floor_heating_config.heating_curve.histeresis
+                        - Temperature::from_celsius(0, 8);
+            }
+        }
+        _ => {}
+    }
+}
+fn set_curve_modulation(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            model.floor_heating_config.heating_curve.modulation =
+                match model.floor_heating_config.heating_curve.modulation {
+                    heating_curve::Modulation::HeatRequest => heating_curve::Modulation::Valve,
+                    heating_curve::Modulation::Valve => heating_curve::Modulation::HeatRequest,
+                };
+        }
+        _ => {}
+    }
+}
+
+fn view_curve_enabled(model: &Model) -> &'static [u8] {
+    if model.floor_heating_config.heating_curve.enabled {
+        b"Igen"
+    } else {
+        b"Nem"
+    }
+}
+fn view_curve_slope(model: &Model) -> &'static [u8] {
+    unsafe { fmt_tenths(model.floor_heating_config.heating_curve.slope) }
+}
+fn view_curve_offset(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.floor_heating_config.heating_curve.offset) }
+}
+fn view_curve_room_factor(model: &Model) -> &'static [u8] {
+    unsafe { fmt_tenths(model.floor_heating_config.heating_curve.room_factor) }
+}
+fn view_curve_forward_min(model: &Model) -> &'static [u8] {
+    unsafe {
+        fmt_temp(
+            model
+                .floor_heating_config
+                .heating_curve
+                .min_forward_temperature,
+        )
+    }
+}
+fn view_curve_histeresis(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.floor_heating_config.heating_curve.histeresis) }
+}
+fn view_curve_modulation(model: &Model) -> &'static [u8] {
+    match model.floor_heating_config.heating_curve.modulation {
+        heating_curve::Modulation::HeatRequest => b"Hokeres",
+        heating_curve::Modulation::Valve => b"Szelep",
+    }
+}
+
+/// Selects the next/previous found sensor (or none) for the role
+fn cycle_sensor(model: &mut Model, role: Role, command: IrCommands) {
+    let count = model.rom_count;
+    let current = (0..count)
+        .find(|i| model.sensor_registry.rom(role) == Some(&model.roms[*i]))
+        .unwrap_or(count);
+    let next = match command {
+        IrCommands::Right => (current + 1) % (count + 1),
+        IrCommands::Left => (current + count) % (count + 1),
+        _ => return,
+    };
+    if next < count {
+        let rom = model.roms[next];
+        model.sensor_registry.assign(role, &rom);
+    } else {
+        model.sensor_registry.clear(role);
+    }
+}
+fn set_sensor_forward(model: &mut Model, command: IrCommands) {
+    cycle_sensor(model, Role::Forward, command);
+}
+fn set_sensor_return(model: &mut Model, command: IrCommands) {
+    cycle_sensor(model, Role::Return, command);
+}
+fn set_sensor_floor(model: &mut Model, command: IrCommands) {
+    cycle_sensor(model, Role::Floor, command);
+}
+fn set_sensor_air(model: &mut Model, command: IrCommands) {
+    cycle_sensor(model, Role::Air, command);
+}
+fn set_sensor_outdoor(model: &mut Model, command: IrCommands) {
+    cycle_sensor(model, Role::Outdoor, command);
+}
+
+/// The lower serial bytes of the assigned sensor
+fn view_sensor(model: &Model, role: Role) -> &'static [u8] {
+    if model
+        .sensor_registry
+        .is_missing(role, &model.roms[..model.rom_count])
+    {
+        b"hiany"
+    } else if let Some(rom) = model.sensor_registry.rom(role) {
+        unsafe { fmt_hex(&rom[1..3]) }
+    } else {
+        b"nincs"
+    }
+}
+fn view_sensor_forward(model: &Model) -> &'static [u8] {
+    view_sensor(model, Role::Forward)
+}
+fn view_sensor_return(model: &Model) -> &'static [u8] {
+    view_sensor(model, Role::Return)
+}
+fn view_sensor_floor(model: &Model) -> &'static [u8] {
+    view_sensor(model, Role::Floor)
+}
+fn view_sensor_air(model: &Model) -> &'static [u8] {
+    view_sensor(model, Role::Air)
+}
+fn view_sensor_outdoor(model: &Model) -> &'static [u8] {
+    view_sensor(model, Role::Outdoor)
+}
+
+fn set_exercise_enabled(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            model.exercise_config.enabled = !model.exercise_config.enabled;
+        }
+        _ => {}
+    }
+}
+fn set_exercise_weekday(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.exercise_config.weekday = (model.exercise_config.weekday + 1) % DAYS_PER_WEEK
+        }
+        IrCommands::Left => {
+            model.exercise_config.weekday =
+                (model.exercise_config.weekday + DAYS_PER_WEEK - 1) % DAYS_PER_WEEK
+        }
+        _ => {}
+    }
+}
+fn set_exercise_hour(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => model.exercise_config.hour = (model.exercise_config.hour + 1) % 24,
+        IrCommands::Left => model.exercise_config.hour = (model.exercise_config.hour + 23) % 24,
+        _ => {}
+    }
+}
+fn set_exercise_duration(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.exercise_config.duration = model.exercise_config.duration + 15.s();
         }
         IrCommands::Left => {
-            model
-                .floor_heating_config
-                .freeze_protection
-                .safe_temperature = model
-                .floor_heating_config
-                .freeze_protection
-                .safe_temperature
-                - Temperature::from_celsius(0, 4);
+            if model.exercise_config.duration > 15.s() {
+                model.exercise_config.duration = model.exercise_config.duration - 15.s();
+            }
         }
         _ => {}
     }
 }
-fn set_freeze_stop(model: &mut Model, command: IrCommands) {
+
+fn view_exercise_enabled(model: &Model) -> &'static [u8] {
+    if model.exercise_config.enabled {
+        b"Igen"
+    } else {
+        b"Nem"
+    }
+}
+fn view_exercise_weekday(model: &Model) -> &'static [u8] {
+    fmt_weekday(model.exercise_config.weekday)
+}
+fn view_exercise_hour(model: &Model) -> &'static [u8] {
+    unsafe { fmt_nn(model.exercise_config.hour) }
+}
+fn view_exercise_duration(model: &Model) -> &'static [u8] {
+    unsafe { fmt_duration(&model.exercise_config.duration) }
+}
+
+fn set_none(_model: &mut Model, _command: IrCommands) {}
+
+fn set_optimum_enabled(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            model.optimum_start_config.enabled = !model.optimum_start_config.enabled;
+        }
+        _ => {}
+    }
+}
+fn set_optimum_max_preheat(model: &mut Model, command: IrCommands) {
+    let hours = u32::from(model.optimum_start_config.max_preheat) / 3600;
+    let hours = match command {
+        IrCommands::Right if hours < 12 => hours + 1,
+        IrCommands::Left if hours > 1 => hours - 1,
+        _ => return,
+    };
+    model.optimum_start_config.max_preheat = Duration::<u32, Seconds>::from_hms(hours, 0, 0);
+}
+//the learned rates are forgotten by the arrows
+fn set_optimum_learned(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => model.optimum_start.reset(),
+        _ => {}
+    }
+}
+
+fn view_optimum_enabled(model: &Model) -> &'static [u8] {
+    if model.optimum_start_config.enabled {
+        b"Igen"
+    } else {
+        b"Nem"
+    }
+}
+fn view_optimum_max_preheat(model: &Model) -> &'static [u8] {
+    unsafe { fmt_u32(u32::from(model.optimum_start_config.max_preheat) / 3600) }
+}
+//learned heat-up rate in degree per hour
+fn view_optimum_rate(model: &Model, bucket: usize) -> &'static [u8] {
+    match model.optimum_start.learned(bucket) {
+        (Some(rate), _) => unsafe { fmt_tenths((rate * 10 + 8) / 16) },
+        (None, _) => b"-",
+    }
+}
+fn view_optimum_rate_0(model: &Model) -> &'static [u8] {
+    view_optimum_rate(model, 0)
+}
+fn view_optimum_rate_1(model: &Model) -> &'static [u8] {
+    view_optimum_rate(model, 1)
+}
+fn view_optimum_rate_2(model: &Model) -> &'static [u8] {
+    view_optimum_rate(model, 2)
+}
+fn view_optimum_rate_3(model: &Model) -> &'static [u8] {
+    view_optimum_rate(model, 3)
+}
+fn view_optimum_rate_4(model: &Model) -> &'static [u8] {
+    view_optimum_rate(model, 4)
+}
+fn view_optimum_rate_5(model: &Model) -> &'static [u8] {
+    view_optimum_rate(model, 5)
+}
+//number of the heat-ups learned
+fn view_optimum_learned(model: &Model) -> &'static [u8] {
+    if model.optimum_start.learning() {
+        return b"Meres";
+    }
+    let count: u32 = (0..optimum_start::BUCKET_COUNT)
+        .map(|bucket| model.optimum_start.learned(bucket).1 as u32)
+        .sum();
+    unsafe { fmt_u32(count) }
+}
+
+fn set_away(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            model.mode = if let Mode::Away(_) = model.mode {
+                Mode::Normal
+            } else {
+                Mode::Away((model.away_config.days, model.away_config.hour))
+            };
+            model.update_programmed_target(true);
+        }
+        _ => {}
+    }
+}
+fn set_away_days(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right if model.away_config.days < 365 => model.away_config.days += 1,
+        IrCommands::Left if model.away_config.days > 0 => model.away_config.days -= 1,
+        _ => return,
+    }
+    model.update_away();
+}
+fn set_away_hour(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => model.away_config.hour = (model.away_config.hour + 1) % 24,
+        IrCommands::Left => model.away_config.hour = (model.away_config.hour + 23) % 24,
+        _ => return,
+    }
+    model.update_away();
+}
+//below the min the setback is switched off: freeze protection only
+fn set_away_setback(model: &mut Model, command: IrCommands) {
+    model.away_config.setback = match (model.away_config.setback, command) {
+        (None, IrCommands::Right) => Some(Temperature::from_celsius(5, 0)),
+        (Some(setback), IrCommands::Right) if setback < Temperature::from_celsius(20, 0) => {
+            Some(setback + Temperature::from_celsius(0, 8))
+        }
+        (Some(setback), IrCommands::Left) if setback > Temperature::from_celsius(5, 0) => {
+            Some(setback - Temperature::from_celsius(0, 8))
+        }
+        (Some(_), IrCommands::Left) => None,
+        _ => return,
+    };
+    model.update_programmed_target(true);
+}
+
+fn set_season(model: &mut Model, command: IrCommands) {
+    let switchover = match (model.season.switchover, command) {
+        (Switchover::Auto, IrCommands::Right) => Switchover::Forced(Season::Heating),
+        (Switchover::Forced(Season::Heating), IrCommands::Right) => {
+            Switchover::Forced(Season::Cooling)
+        }
+        (Switchover::Forced(Season::Cooling), IrCommands::Right) => Switchover::Auto,
+        (Switchover::Auto, IrCommands::Left) => Switchover::Forced(Season::Cooling),
+        (Switchover::Forced(Season::Heating), IrCommands::Left) => Switchover::Auto,
+        (Switchover::Forced(Season::Cooling), IrCommands::Left) => {
+            Switchover::Forced(Season::Heating)
+        }
+        _ => return,
+    };
+    model.season.switchover = switchover;
+}
+fn set_cooling_target(model: &mut Model, command: IrCommands) {
     match command {
         IrCommands::Right => {
-            model.floor_heating_config.freeze_protection.min_temperature =
-                model.floor_heating_config.freeze_protection.min_temperature
-                    + Temperature::from_celsius(0, 4);
+            model.cooling_config.target_air_temperature =
+                model.cooling_config.target_air_temperature + Temperature::from_celsius(0, 8);
         }
         IrCommands::Left => {
-            model.floor_heating_config.freeze_protection.min_temperature =
-                model.floor_heating_config.freeze_protection.min_temperature
-                    - Temperature::from_celsius(0, 4);
+            if model.cooling_config.target_air_temperature > Temperature::from_celsius(18, 0) {
+                model.cooling_config.target_air_temperature =
+                    model.cooling_config.target_air_temperature - Temperature::from_celsius(0, 8);
+            }
+        }
+        _ => {}
+    }
+}
+fn set_cooling_histeresis(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.cooling_config.histeresis =
+                model.cooling_config.histeresis + Temperature::from_celsius(0, 2);
+        }
+        IrCommands::Left => {
+            if model.cooling_config.histeresis > Temperature::from_celsius(0, 2) {
+                model.cooling_config.histeresis =
+                    model.cooling_config.histeresis - Temperature::from_celsius(0, 2);
+            }
+        }
+        _ => {}
+    }
+}
+fn set_dew_point_margin(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.cooling_config.dew_point_margin =
+                model.cooling_config.dew_point_margin + Temperature::from_celsius(0, 8);
+        }
+        IrCommands::Left => {
+            if model.cooling_config.dew_point_margin > Temperature::from_celsius(0, 0) {
+                model.cooling_config.dew_point_margin =
+                    model.cooling_config.dew_point_margin - Temperature::from_celsius(0, 8);
+            }
+        }
+        _ => {}
+    }
+}
+fn set_cooling_above(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.switchover_config.cooling_above =
+                model.switchover_config.cooling_above + Temperature::from_celsius(0, 8);
+        }
+        IrCommands::Left => {
+            if model.switchover_config.cooling_above > model.switchover_config.heating_below {
+                model.switchover_config.cooling_above =
+                    model.switchover_config.cooling_above - Temperature::from_celsius(0, 8);
+            }
+        }
+        _ => {}
+    }
+}
+fn set_heating_below(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.switchover_config.heating_below =
+                model.switchover_config.heating_below + Temperature::from_celsius(0, 8);
+        }
+        IrCommands::Left => {
+            if model.switchover_config.heating_below > Temperature::from_celsius(0, 0) {
+                model.switchover_config.heating_below =
+                    model.switchover_config.heating_below - Temperature::from_celsius(0, 8);
+            }
         }
         _ => {}
     }
 }
 
-fn set_forward_max(model: &mut Model, command: IrCommands) {
+fn set_window_enabled(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right | IrCommands::Left => {
+            model.open_window_config.enabled = !model.open_window_config.enabled;
+        }
+        _ => {}
+    }
+}
+fn set_window_drop(model: &mut Model, command: IrCommands) {
     match command {
         IrCommands::Right => {
-            model.floor_heating_config.max_forward_temperature =
-                model.floor_heating_config.max_forward_temperature
-                    + Temperature::from_celsius(0, 4);
+            model.open_window_config.drop =
+                model.open_window_config.drop + Temperature::from_celsius(0, 8);
         }
         IrCommands::Left => {
-            model.floor_heating_config.max_forward_temperature =
-                model.floor_heating_config.max_forward_temperature
-                    - Temperature::from_celsius(0, 4);
+            if model.open_window_config.drop > Temperature::from_celsius(0, 8) {
+                model.open_window_config.drop =
+                    model.open_window_config.drop - Temperature::from_celsius(0, 8);
+            }
         }
         _ => {}
     }
 }
-fn set_floor_max(model: &mut Model, command: IrCommands) {
+fn set_window_drop_period(model: &mut Model, command: IrCommands) {
     match command {
         IrCommands::Right => {
-            model.floor_heating_config.max_floor_temperature =
-                model.floor_heating_config.max_floor_temperature + Temperature::from_celsius(0, 4);
+            model.open_window_config.drop_period = model.open_window_config.drop_period + 60.s();
         }
         IrCommands::Left => {
-            model.floor_heating_config.max_floor_temperature =
-                model.floor_heating_config.max_floor_temperature - Temperature::from_celsius(0, 4);
+            if model.open_window_config.drop_period > 60.s() {
+                model.open_window_config.drop_period =
+                    model.open_window_config.drop_period - 60.s();
+            }
+        }
+        _ => {}
+    }
+}
+fn set_window_recovery(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.open_window_config.recovery =
+                model.open_window_config.recovery + Temperature::from_celsius(0, 8);
+        }
+        IrCommands::Left => {
+            if model.open_window_config.recovery > Temperature::from_celsius(0, 8) {
+                model.open_window_config.recovery =
+                    model.open_window_config.recovery - Temperature::from_celsius(0, 8);
+            }
         }
         _ => {}
     }
 }
-fn set_histeresis(model: &mut Model, command: IrCommands) {
-    match command {
-        IrCommands::Right => {
-            model.floor_heating_config.temperature_histeresis =
-                model.floor_heating_config.temperature_histeresis + Temperature::from_celsius(0, 1);
+fn set_window_suspend(model: &mut Model, command: IrCommands) {
+    match command {
+        IrCommands::Right => {
+            model.open_window_config.suspend_duration =
+                model.open_window_config.suspend_duration + 60.s();
+        }
+        IrCommands::Left => {
+            if model.open_window_config.suspend_duration > 60.s() {
+                model.open_window_config.suspend_duration =
+                    model.open_window_config.suspend_duration - 60.s();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Off -> functional heating -> drying -> off, a program is always started from the first day
+fn set_screed_program(model: &mut Model, command: IrCommands) {
+    let program = match (command, model.screed.running()) {
+        (IrCommands::Right, None) | (IrCommands::Left, Some(Program::Drying)) => {
+            Some(Program::FunctionalHeating)
+        }
+        (IrCommands::Right, Some(Program::FunctionalHeating)) | (IrCommands::Left, None) => {
+            Some(Program::Drying)
+        }
+        (IrCommands::Right, Some(Program::Drying))
+        | (IrCommands::Left, Some(Program::FunctionalHeating)) => None,
+        _ => return,
+    };
+    match program {
+        Some(program) => model.screed.start(program),
+        None => model.screed.stop(),
+    }
+}
+fn set_screed_day(model: &mut Model, command: IrCommands) {
+    let day = model.screed.day();
+    match command {
+        IrCommands::Right => model.screed.set_day(day + 1),
+        IrCommands::Left if day > 0 => model.screed.set_day(day - 1),
+        _ => {}
+    }
+}
+
+fn view_away(model: &Model) -> &'static [u8] {
+    if let Mode::Away(_) = model.mode {
+        b"Igen"
+    } else {
+        b"Nem"
+    }
+}
+fn view_away_days(model: &Model) -> &'static [u8] {
+    unsafe { fmt_u32(model.away_config.days) }
+}
+fn view_away_hour(model: &Model) -> &'static [u8] {
+    unsafe { fmt_nn(model.away_config.hour) }
+}
+fn view_away_setback(model: &Model) -> &'static [u8] {
+    match model.away_config.setback {
+        Some(setback) => unsafe { fmt_temp(setback) },
+        None => b"Fagyved",
+    }
+}
+fn view_season(model: &Model) -> &'static [u8] {
+    match model.season.switchover {
+        Switchover::Auto => match model.season.season() {
+            Season::Heating => b"Auto futes",
+            Season::Cooling => b"Auto hutes",
+        },
+        Switchover::Forced(Season::Heating) => b"Futes",
+        Switchover::Forced(Season::Cooling) => b"Hutes",
+    }
+}
+fn view_cooling_target(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.cooling_config.target_air_temperature) }
+}
+fn view_cooling_histeresis(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.cooling_config.histeresis) }
+}
+fn view_dew_point_margin(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.cooling_config.dew_point_margin) }
+}
+fn view_cooling_above(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.switchover_config.cooling_above) }
+}
+fn view_heating_below(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.switchover_config.heating_below) }
+}
+
+fn view_screed_program(model: &Model) -> &'static [u8] {
+    screed_program_text(model.screed.running())
+}
+fn view_screed_day(model: &Model) -> &'static [u8] {
+    if model.screed.running().is_some() {
+        unsafe { fmt_u32(model.screed.day() as u32 + 1) }
+    } else {
+        b"-"
+    }
+}
+
+fn view_window_enabled(model: &Model) -> &'static [u8] {
+    if model.open_window_config.enabled {
+        b"Igen"
+    } else {
+        b"Nem"
+    }
+}
+fn view_window_drop(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.open_window_config.drop) }
+}
+fn view_window_drop_period(model: &Model) -> &'static [u8] {
+    unsafe { fmt_duration(&model.open_window_config.drop_period) }
+}
+fn view_window_recovery(model: &Model) -> &'static [u8] {
+    unsafe { fmt_temp(model.open_window_config.recovery) }
+}
+fn view_window_suspend(model: &Model) -> &'static [u8] {
+    unsafe { fmt_duration(&model.open_window_config.suspend_duration) }
+}
+
+//direct numeric entry by the digit keys:
+
+fn enter_time_weekday(model: &mut Model, value: i32) {
+    model.weektime = WeekTime {
+        weekday: (value - 1) as u8,
+        ..model.weektime
+    };
+    model.update_time_offset();
+}
+fn enter_time_hour_min(model: &mut Model, value: i32) {
+    model.weektime = WeekTime {
+        hour: (value / 60) as u8,
+        min: (value % 60) as u8,
+        sec: 0,
+        ..model.weektime
+    };
+    model.update_time_offset();
+}
+fn enter_time_min(model: &mut Model, value: i32) {
+    model.weektime = WeekTime {
+        min: value as u8,
+        sec: 0,
+        ..model.weektime
+    };
+    model.update_time_offset();
+}
+
+fn enter_program_day_index(model: &mut Model, value: i32) {
+    model.programmed_day = (value - 1) as u8;
+    model.programmed_index = 0;
+}
+fn enter_program_index(model: &mut Model, value: i32) {
+    if ((value - 1) as usize) < model.program.day(model.programmed_day).len() {
+        model.programmed_index = (value - 1) as usize;
+    }
+}
+fn enter_program_start_hour_min(model: &mut Model, value: i32) {
+    move_program_point(model, (value / 60) as u8, (value % 60) as u8);
+}
+fn enter_program_start_min(model: &mut Model, value: i32) {
+    if let Some(point) = program_point(model) {
+        move_program_point(model, point.hour, value as u8);
+    }
+}
+fn enter_program_target_temp(model: &mut Model, value: i32) {
+    let _ = model
+        .program
+        .day_mut(model.programmed_day)
+        .set_target(model.programmed_index, Temperature::from_tenths(value));
+}
+
+fn enter_freeze_warning(model: &mut Model, value: i32) {
+    model
+        .floor_heating_config
+        .freeze_protection
+        .safe_temperature = Temperature::from_tenths(value);
+}
+fn enter_freeze_stop(model: &mut Model, value: i32) {
+    model.floor_heating_config.freeze_protection.min_temperature = Temperature::from_tenths(value);
+}
+fn enter_forward_max(model: &mut Model, value: i32) {
+    model.floor_heating_config.max_forward_temperature = Temperature::from_tenths(value);
+}
+fn enter_floor_max(model: &mut Model, value: i32) {
+    model.floor_heating_config.max_floor_temperature = Temperature::from_tenths(value);
+}
+fn enter_histeresis(model: &mut Model, value: i32) {
+    model.floor_heating_config.temperature_histeresis = Temperature::from_tenths(value);
+}
+fn enter_floor_target(model: &mut Model, value: i32) {
+    model.floor_heating_config.floor.target_temperature = Temperature::from_tenths(value);
+}
+fn enter_floor_min(model: &mut Model, value: i32) {
+    model.floor_heating_config.floor.min_temperature = Temperature::from_tenths(value);
+}
+fn enter_floor_histeresis(model: &mut Model, value: i32) {
+    model.floor_heating_config.floor.histeresis = Temperature::from_tenths(value);
+}
+fn enter_after_circulation(model: &mut Model, value: i32) {
+    model.floor_heating_config.after_circulation_duration = (value as u32).s();
+}
+fn enter_pre_circulation(model: &mut Model, value: i32) {
+    model.floor_heating_config.pre_circulation_duration = (value as u32).s();
+}
+fn enter_backlight_off(model: &mut Model, value: i32) {
+    model.backlight_config.off_timeout = (value as u32).s();
+}
+
+fn enter_pid_kp(model: &mut Model, value: i32) {
+    model.floor_heating_config.pid.kp = value;
+}
+fn enter_pid_ki(model: &mut Model, value: i32) {
+    model.floor_heating_config.pid.ki = value;
+}
+fn enter_pid_kd(model: &mut Model, value: i32) {
+    model.floor_heating_config.pid.kd = value;
+}
+fn enter_pid_cycle(model: &mut Model, value: i32) {
+    model.floor_heating_config.pid.cycle_period = (value as u32).s();
+}
+
+fn enter_curve_slope(model: &mut Model, value: i32) {
+    model.floor_heating_config.heating_curve.slope = value;
+}
+fn enter_curve_offset(model: &mut Model, value: i32) {
+    model.floor_heating_config.heating_curve.offset = Temperature::from_tenths(value);
+}
+fn enter_curve_room_factor(model: &mut Model, value: i32) {
+    model.floor_heating_config.heating_curve.room_factor = value;
+}
+fn enter_curve_forward_min(model: &mut Model, value: i32) {
+    model
+        .floor_heating_config
+        .heating_curve
+        .min_forward_temperature = Temperature::from_tenths(value);
+}
+fn enter_curve_histeresis(model: &mut Model, value: i32) {
+    model.floor_heating_config.heating_curve.histeresis = Temperature::from_tenths(value);
+}
+
+fn enter_exercise_weekday(model: &mut Model, value: i32) {
+    model.exercise_config.weekday = (value - 1) as u8;
+}
+fn enter_exercise_hour(model: &mut Model, value: i32) {
+    model.exercise_config.hour = value as u8;
+}
+fn enter_exercise_duration(model: &mut Model, value: i32) {
+    model.exercise_config.duration = (value as u32).s();
+}
+
+fn enter_optimum_max_preheat(model: &mut Model, value: i32) {
+    model.optimum_start_config.max_preheat = Duration::<u32, Seconds>::from_hms(value as u32, 0, 0);
+}
+
+fn enter_away_days(model: &mut Model, value: i32) {
+    model.away_config.days = value as u32;
+    model.update_away();
+}
+fn enter_away_hour(model: &mut Model, value: i32) {
+    model.away_config.hour = value as u8;
+    model.update_away();
+}
+fn enter_away_setback(model: &mut Model, value: i32) {
+    model.away_config.setback = Some(Temperature::from_tenths(value));
+    model.update_programmed_target(true);
+}
+fn enter_cooling_target(model: &mut Model, value: i32) {
+    model.cooling_config.target_air_temperature = Temperature::from_tenths(value);
+}
+fn enter_cooling_histeresis(model: &mut Model, value: i32) {
+    model.cooling_config.histeresis = Temperature::from_tenths(value);
+}
+fn enter_dew_point_margin(model: &mut Model, value: i32) {
+    model.cooling_config.dew_point_margin = Temperature::from_tenths(value);
+}
+fn enter_cooling_above(model: &mut Model, value: i32) {
+    model.switchover_config.cooling_above = Temperature::from_tenths(value);
+}
+fn enter_heating_below(model: &mut Model, value: i32) {
+    model.switchover_config.heating_below = Temperature::from_tenths(value);
+}
+
+fn enter_window_drop(model: &mut Model, value: i32) {
+    model.open_window_config.drop = Temperature::from_tenths(value);
+}
+fn enter_window_drop_period(model: &mut Model, value: i32) {
+    model.open_window_config.drop_period = (value as u32).s();
+}
+fn enter_window_recovery(model: &mut Model, value: i32) {
+    model.open_window_config.recovery = Temperature::from_tenths(value);
+}
+fn enter_window_suspend(model: &mut Model, value: i32) {
+    model.open_window_config.suspend_duration = (value as u32).s();
+}
+
+fn enter_screed_day(model: &mut Model, value: i32) {
+    model.screed.set_day((value - 1) as u16);
+}
+
+fn enter_target_temp(model: &mut Model, value: i32) {
+    model.floor_heating_config.target_air_temperature = Some(Temperature::from_tenths(value));
+}
+
+const MAX_THERMOMETER_COUNT: usize = 5; //max number of thermometers, one for each role
+const VALVE_DEVICE: usize = 0; //index for the exercise
+const PUMP_DEVICE: usize = 1;
+const ROOM_LOCATION: u8 = 1; //of this unit in the window contact and humidity reports
+const OUTDOOR_TEMPERATURE_TIMEOUT: u32 = 30 * 60; //the CAN outdoor temperature is dropped after this many seconds
+const HUMIDITY_TIMEOUT: u32 = 30 * 60; //the CAN humidity is dropped after this many seconds, the cooling stops
+const DAYS_PER_WEEK: u8 = 7;
+const SCREED_REGISTER: usize = 0; //the first backup data register of the screed progress
+const CONFIG_FLASH_ADDRESS: usize = 0x0801_F000; //the last 4 pages of the 128K flash
+const CONFIG_PAGES: usize = 4;
+const CONFIG_SAVE_DELAY: u32 = 60; //seconds after the last change
+const RECORD_BUFFER_SIZE: usize = 256; //for the largest record (the program)
+
+//the keys of the config records:
+const RECORD_HEATING: u8 = 1; //floor heating config
+const RECORD_PROGRAM: u8 = 2; //weekly schedule
+const RECORD_MODE: u8 = 3; //program mode, time offset, away, season switchover
+const RECORD_SENSORS: u8 = 4; //sensor roles
+const RECORD_OPTIONS: u8 = 5; //backlight, exercise, optimum start, open window, cooling
+const RECORD_KEYS: u8 = 6; //key map of the remote
+const RECORD_SCREED_LOG: u8 = 7; //written at the day changes of the screed program, not a setting
+const RECORDS: [u8; 6] = [
+    RECORD_HEATING,
+    RECORD_PROGRAM,
+    RECORD_MODE,
+    RECORD_SENSORS,
+    RECORD_OPTIONS,
+    RECORD_KEYS,
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Page {
+    Overview,
+    Sensors,
+    Heating,
+    Errors,
+    Statistics,
+    Runtime,
+    Screed,
+}
+
+impl Page {
+    fn next(self) -> Self {
+        match self {
+            Page::Overview => Page::Sensors,
+            Page::Sensors => Page::Heating,
+            Page::Heating => Page::Errors,
+            Page::Errors => Page::Statistics,
+            Page::Statistics => Page::Runtime,
+            Page::Runtime => Page::Screed,
+            Page::Screed => Page::Overview,
         }
-        IrCommands::Left => {
-            if model.floor_heating_config.temperature_histeresis > Temperature::from_celsius(0, 1) {
-                model.floor_heating_config.temperature_histeresis =
-                    model.floor_heating_config.temperature_histeresis
-                        - Temperature::from_celsius(0, 1);
-            }
+    }
+
+    fn prev(self) -> Self {
+        match self {
+            Page::Overview => Page::Screed,
+            Page::Screed => Page::Runtime,
+            Page::Sensors => Page::Overview,
+            Page::Heating => Page::Sensors,
+            Page::Errors => Page::Heating,
+            Page::Statistics => Page::Errors,
+            Page::Runtime => Page::Statistics,
         }
-        _ => {}
     }
 }
 
-fn view_freeze_warning(model: &Model) -> &'static [u8] {
-    unsafe {
-        fmt_temp(
-            model
-                .floor_heating_config
-                .freeze_protection
-                .safe_temperature,
-        )
+/// The reason of the last reset
+fn reset_reason_text(reason: ResetReason) -> &'static [u8] {
+    match reason {
+        ResetReason::PowerOn => b"Tap",
+        ResetReason::Pin => b"Gmb",
+        ResetReason::Software => b"Szf",
+        ResetReason::IndependentWatchdog => b"Wdg",
+        ResetReason::WindowWatchdog => b"Wwd",
+        ResetReason::LowPower => b"Alv",
+        ResetReason::Unknown => b"Ism",
     }
 }
-fn view_freeze_stop(model: &Model) -> &'static [u8] {
-    unsafe { fmt_temp(model.floor_heating_config.freeze_protection.min_temperature) }
-}
-fn view_forward_max(model: &Model) -> &'static [u8] {
-    unsafe { fmt_temp(model.floor_heating_config.max_forward_temperature) }
-}
-fn view_floor_max(model: &Model) -> &'static [u8] {
-    unsafe { fmt_temp(model.floor_heating_config.max_floor_temperature) }
-}
-fn view_histeresis(model: &Model) -> &'static [u8] {
-    unsafe { fmt_temp(model.floor_heating_config.temperature_histeresis) }
+
+fn status_text(state: &floor_heating::State<Duration<u32, Seconds>>) -> &'static [u8] {
+    match state {
+        floor_heating::State::PrepareHeating((true, _)) => b"...Olvasztas",
+        floor_heating::State::PrepareHeating((false, _)) => b"...Futes",
+        floor_heating::State::Heating(true) => b"Olvasztas",
+        floor_heating::State::Heating(false) => b"Futes",
+        floor_heating::State::AfterCirculation(_) => b"Utokeringetes",
+        floor_heating::State::Standby(_) => b"Keszenlet",
+        floor_heating::State::FreezeProtectionCheckCirculation(_) => b"Fagyvizsgalat",
+        floor_heating::State::Error((reason, _, _)) => error_text(*reason),
+    }
 }
 
-const MAX_THERMOMETER_COUNT: usize = 4; //max number of thermometers
-const PROGRAMS_PER_DAY: u8 = 6;
-const DAYS_PER_WEEK: u8 = 7;
+fn screed_program_text(program: Option<Program>) -> &'static [u8] {
+    match program {
+        Some(Program::FunctionalHeating) => b"Funkcio",
+        Some(Program::Drying) => b"Szaritas",
+        None => b"Ki",
+    }
+}
 
-enum ProgramModes {
-    Normal,               //everythig works as programmed
-    Economy(Temperature), //target temp = Normal + the given offset (which is negative)
-    Party(u8),            //temp override is kept until midnight of the starting (stored) week day
-    Fix(Temperature),     //target temp = the given temperature
-                          //Away((u32, u8)),      //freeze protection will work for N days, until HH:00)
+fn phase_text(phase: Phase) -> &'static [u8] {
+    match phase {
+        Phase::HeatUp => b"Felfutes",
+        Phase::Hold => b"Tartas",
+        Phase::CoolDown => b"Lehutes",
+    }
 }
 
-struct ProgramEntry {
-    start_time: Time<u32, Seconds>,
-    target_air_temperature: Temperature,
+struct AwayConfig {
+    days: u32,                    //until the return, 0 = today
+    hour: u8,                     //of the return
+    setback: Option<Temperature>, //target while away, None = freeze protection only
 }
 
 struct Model<'a, 'b> {
     //config:
     can_config: Configuration,
     floor_heating_config: floor_heating::Config<Temperature, Duration<u32, Seconds>>,
-    backlight_timeout: Duration<u32, Seconds>, //time in seconds before backlight tuns off
-    time_offset: Duration<u32, Seconds>,       //used for rtc to weektime calibration
-    program: [ProgramEntry; (DAYS_PER_WEEK * PROGRAMS_PER_DAY) as usize],
+    backlight_config: backlight::Config<Duration<u32, Seconds>>,
+    exercise_config: exercise::Config<Duration<u32, Seconds>>,
+    optimum_start_config: optimum_start::Config<Duration<u32, Seconds>>,
+    open_window_config: open_window::Config<Temperature, Duration<u32, Seconds>>,
+    cooling_config: cooling::Config<Temperature, Duration<u32, Seconds>>,
+    switchover_config: cooling::SwitchoverConfig<Temperature, Duration<u32, Seconds>>,
+    demand_link_config: heat_demand::LinkConfig<Duration<u32, Seconds>>,
+    sensor_registry: Registry, //roles of the sensors by ROM
+    key_map: KeyMap,
+    plausibility_config: plausibility::Config<Temperature, Duration<u32, Seconds>>,
+    away_config: AwayConfig,
+    time_offset: Duration<u32, Seconds>, //used for rtc to weektime calibration
+    program: Schedule<Temperature>,
 
     //state:
-    mode: ProgramModes,
+    mode: Mode<Temperature>,
 
-    floor_heating_state: floor_heating::State<Duration<u32, Seconds>>,
-    temperatures: [Option<Temperature>; MAX_THERMOMETER_COUNT],
-    time: Time<u32, Seconds>, //rtc based, ever increasing, in seconds
-    weektime: WeekTime,       //redundant WeekTime::from(self.time + self.time_offset)
-    current_program_index: usize,
+    floor_heating: floor_heating::Controller<Duration<u32, Seconds>>,
+    state_since: Time<u32, Seconds>, //last change of the floor heating state
+    backlight: backlight::Backlight<Duration<u32, Seconds>>,
+    exerciser: exercise::Exerciser<Duration<u32, Seconds>>,
+    optimum_start: optimum_start::OptimumStart,
+    open_window: open_window::OpenWindow<Duration<u32, Seconds>>,
+    statistics: Statistics,
+    cooler: cooling::Cooler<Duration<u32, Seconds>>,
+    season: cooling::SeasonSelector<Duration<u32, Seconds>>,
+    can_humidity: Option<u8>,
+    can_humidity_age: Duration<u32, Seconds>, //since the last humidity message
+    demand_link: heat_demand::DemandLink<Duration<u32, Seconds>>,
+    screed: screed::Runner,
+    config_saver: Deferred<Duration<u32, Seconds>>,
+    retained: Retained<Duration<u32, Seconds>>,
+    reset_reason: ResetReason,
+    devices: [exercise::Device<Duration<u32, Seconds>>; 2], //valve, pump
+    roms: [Rom; MAX_THERMOMETER_COUNT],                     //found on the bus
+    rom_count: usize,
+    temperatures: [Option<Temperature>; MAX_THERMOMETER_COUNT], //checked, by the index of the found sensors
+    checks: [plausibility::Check<Duration<u32, Seconds>>; MAX_THERMOMETER_COUNT],
+    can_outdoor_temperature: Option<Temperature>,
+    can_outdoor_age: Duration<u32, Seconds>, //since the last outdoor temperature message
+    time: Time<u32, Seconds>,                //rtc based, ever increasing, in seconds
+    uptime: Duration<u32, Seconds>,
+    can_received: u32,
+    can_last_received: Option<Time<u32, Seconds>>,
+    weektime: WeekTime, //redundant WeekTime::from(self.time + self.time_offset)
+    current_slot: Option<Slot>, //of the program, the target was set by
+    early_start: bool,  //the current program was started before its time by the optimum start
 
     //UI state:
+    page: Page,
+    entry: Option<Entry<Duration<u32, Seconds>>>, //numeric entry in progress
     active_menu: Option<&'b Menu<'a, Model<'a, 'b>, IrCommands>>,
     selected_row: usize,
-    programmed_index: u8,
+    programmed_day: u8,      //in the program menu
+    programmed_index: usize, //within the programmed day
 }
 
 impl<'a, 'b> Model<'a, 'b> {
@@ -596,213 +2554,145 @@ impl<'a, 'b> Model<'a, 'b> {
             },
 
             //this config will be updated by IR remote or CAN messages
-            mode: ProgramModes::Economy(Temperature::from_celsius(-4, 0)),
+            mode: Mode::Economy(Temperature::from_celsius(-4, 0)),
 
             floor_heating_config: floor_heating::Config {
-                max_forward_temperature: Temperature::from_celsius(40, 0),
-                max_floor_temperature: Temperature::from_celsius(29, 0),
                 target_air_temperature: Some(Temperature::from_celsius(16, 0)),
-                temperature_histeresis: Temperature::from_celsius(0, 2),
-                freeze_protection: floor_heating::FreezeProtectionConfig {
-                    min_temperature: Temperature::from_celsius(5, 0),
-                    safe_temperature: Temperature::from_celsius(8, 0),
-                    check_interval: Duration::<u32, Seconds>::from_hms(4, 0, 0), //4 hour
-                    check_duration: Duration::<u32, Seconds>::from_hms(0, 4, 0), //4 min
-                },
-                pre_circulation_duration: Duration::<u32, Seconds>::from_hms(0, 4, 0),
-                after_circulation_duration: Duration::<u32, Seconds>::from_hms(0, 4, 0),
+                ..floor_heating::Config::with_defaults()
+            },
+
+            backlight_config: backlight::Config {
+                off_timeout: Duration::<u32, Seconds>::from_hms(0, 1, 0),
+                wake_on_movement: true,
+            },
+
+            sensor_registry: Registry::new(),
+            key_map: KeyMap::with_defaults(),
+            plausibility_config: plausibility::Config {
+                //forward, return, floor, air, outdoor:
+                limits: [
+                    plausibility::Limits {
+                        min: Temperature::from_celsius(0, 0),
+                        max: Temperature::from_celsius(90, 0),
+                        max_change: Temperature::from_celsius(10, 0),
+                    },
+                    plausibility::Limits {
+                        min: Temperature::from_celsius(0, 0),
+                        max: Temperature::from_celsius(90, 0),
+                        max_change: Temperature::from_celsius(5, 0),
+                    },
+                    plausibility::Limits {
+                        min: Temperature::from_celsius(0, 0),
+                        max: Temperature::from_celsius(50, 0),
+                        max_change: Temperature::from_celsius(1, 0),
+                    },
+                    plausibility::Limits {
+                        min: Temperature::from_celsius(0, 0),
+                        max: Temperature::from_celsius(45, 0),
+                        max_change: Temperature::from_celsius(1, 0),
+                    },
+                    plausibility::Limits {
+                        min: Temperature::from_celsius(-40, 0),
+                        max: Temperature::from_celsius(60, 0),
+                        max_change: Temperature::from_celsius(2, 0),
+                    },
+                ],
+                stuck_duration: Duration::<u32, Seconds>::from_hms(12, 0, 0),
+                max_read_errors: 3,
+            },
+
+            exercise_config: exercise::Config {
+                enabled: true,
+                weekday: 2, //wednesday
+                hour: 11,
+                min_idle: Duration::<u32, Seconds>::from_hms(72, 0, 0),
+                duration: Duration::<u32, Seconds>::from_hms(0, 5, 0), //the thermal actuator opens in 3-4 min
+            },
+
+            optimum_start_config: optimum_start::Config {
+                enabled: true,
+                max_preheat: Duration::<u32, Seconds>::from_hms(6, 0, 0),
+                default_rate: 8, //0.5 degree per hour until learned
+            },
+
+            open_window_config: open_window::Config {
+                enabled: true,
+                drop: Temperature::from_celsius(1, 0),
+                drop_period: Duration::<u32, Seconds>::from_hms(0, 5, 0),
+                recovery: Temperature::from_celsius(0, 8),
+                suspend_duration: Duration::<u32, Seconds>::from_hms(0, 30, 0),
+            },
+
+            cooling_config: cooling::Config {
+                target_air_temperature: Temperature::from_celsius(25, 0),
+                histeresis: Temperature::from_celsius(0, 8),
+                dew_point_margin: Temperature::from_celsius(2, 0),
+                min_stop: Duration::<u32, Seconds>::from_hms(0, 15, 0),
+            },
+
+            switchover_config: cooling::SwitchoverConfig {
+                cooling_above: Temperature::from_celsius(24, 0),
+                heating_below: Temperature::from_celsius(16, 0),
+                delay: Duration::<u32, Seconds>::from_hms(12, 0, 0),
+            },
+
+            demand_link_config: heat_demand::LinkConfig {
+                period: 30.s(),
+                coordinator_timeout: Duration::<u32, Seconds>::from_hms(0, 2, 0),
             },
 
-            backlight_timeout: Duration::<u32, Seconds>::from_hms(0, 1, 0),
+            away_config: AwayConfig {
+                days: 7,
+                hour: 16,
+                setback: Some(Temperature::from_celsius(12, 0)),
+            },
 
             time_offset: 0u32.s(),
 
-            program: [
-                //monday:
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(0, 6, 15, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(0, 7, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(0, 12, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(0, 14, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(18, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(0, 17, 00, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(0, 20, 0, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                //tuesday:
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(1, 6, 15, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(1, 7, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(1, 12, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(1, 14, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(18, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(1, 17, 00, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(1, 20, 0, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                //wednesday:
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(2, 6, 15, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(2, 7, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(2, 12, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(2, 14, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(18, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(2, 17, 00, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(2, 20, 0, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                //thursday:
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(3, 6, 15, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(3, 7, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(3, 12, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(3, 14, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(18, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(3, 17, 00, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(3, 20, 0, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                //friday:
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 6, 15, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 7, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 12, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 14, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(18, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 17, 00, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 21, 0, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                //saturday:
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 7, 15, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 7, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(19, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 11, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 14, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(19, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 17, 00, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 21, 0, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-                //sunday:
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 7, 15, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 7, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(19, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 11, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 14, 30, 0),
-                    target_air_temperature: Temperature::from_celsius(19, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 17, 00, 0),
-                    target_air_temperature: Temperature::from_celsius(20, 0),
-                },
-                ProgramEntry {
-                    start_time: Time::<u32, Seconds>::from_dhms(4, 20, 0, 0),
-                    target_air_temperature: Temperature::from_celsius(17, 0),
-                },
-            ],
+            program: Schedule::with_defaults(),
 
-            floor_heating_state: floor_heating::State::Standby(0.s()),
+            floor_heating: floor_heating::Controller::new(floor_heating::State::Standby(0.s())),
+            state_since: Time::<u32, Seconds>::from_sec(0),
+            backlight: backlight::Backlight::new(),
+            exerciser: exercise::Exerciser::new(),
+            optimum_start: optimum_start::OptimumStart::new(),
+            open_window: open_window::OpenWindow::new(),
+            statistics: Statistics::new(),
+            cooler: cooling::Cooler::new(),
+            season: cooling::SeasonSelector::new(),
+            can_humidity: None,
+            can_humidity_age: 0.s(),
+            demand_link: heat_demand::DemandLink::new(),
+            screed: screed::Runner::new(),
+            config_saver: Deferred::new(0),
+            retained: Retained::new(),
+            reset_reason: ResetReason::PowerOn,
+            devices: [exercise::Device::new(), exercise::Device::new()],
+            roms: [[0u8; 8]; MAX_THERMOMETER_COUNT],
+            rom_count: 0,
             temperatures: [None; MAX_THERMOMETER_COUNT],
+            checks: [
+                plausibility::Check::new(),
+                plausibility::Check::new(),
+                plausibility::Check::new(),
+                plausibility::Check::new(),
+                plausibility::Check::new(),
+            ],
+            can_outdoor_temperature: None,
+            can_outdoor_age: 0.s(),
             time: Time::<u32, Seconds>::from_sec(0),
+            uptime: 0.s(),
+            can_received: 0,
+            can_last_received: None,
             weektime: WeekTime::default(),
-            current_program_index: 0,
+            current_slot: None,
+            early_start: false,
 
+            page: Page::Overview,
+            entry: None,
             active_menu: None,
             selected_row: 0,
+            programmed_day: 0,
             programmed_index: 0,
         }
     }
@@ -817,110 +2707,589 @@ impl<'a, 'b> Model<'a, 'b> {
         self.time_offset = Time::<u32, Seconds>::from(self.weektime) - self.time;
     }
 
+    fn encode_record(&self, key: u8, writer: &mut Writer) -> Result<(), Overflow> {
+        match key {
+            RECORD_HEATING => self.floor_heating_config.encode(writer),
+            RECORD_PROGRAM => self.program.encode(writer),
+            RECORD_MODE => {
+                self.mode.encode(writer)?;
+                writer.duration(self.time_offset)?;
+                writer.u32(self.away_config.days)?;
+                writer.u8(self.away_config.hour)?;
+                writer.optional_temperature(&self.away_config.setback)?;
+                writer.u8(match self.season.switchover {
+                    Switchover::Auto => 0,
+                    Switchover::Forced(Season::Heating) => 1,
+                    Switchover::Forced(Season::Cooling) => 2,
+                })
+            }
+            RECORD_SENSORS => self.sensor_registry.encode(writer),
+            RECORD_KEYS => self.key_map.encode(writer),
+            RECORD_OPTIONS => {
+                let backlight = &self.backlight_config;
+                writer.duration(backlight.off_timeout)?;
+                writer.bool(backlight.wake_on_movement)?;
+
+                let exercise = &self.exercise_config;
+                writer.bool(exercise.enabled)?;
+                writer.u8(exercise.weekday)?;
+                writer.u8(exercise.hour)?;
+                writer.duration(exercise.min_idle)?;
+                writer.duration(exercise.duration)?;
+
+                let optimum_start = &self.optimum_start_config;
+                writer.bool(optimum_start.enabled)?;
+                writer.duration(optimum_start.max_preheat)?;
+                writer.i32(optimum_start.default_rate)?;
+
+                let open_window = &self.open_window_config;
+                writer.bool(open_window.enabled)?;
+                writer.temperature(&open_window.drop)?;
+                writer.duration(open_window.drop_period)?;
+                writer.temperature(&open_window.recovery)?;
+                writer.duration(open_window.suspend_duration)?;
+
+                let cooling = &self.cooling_config;
+                writer.temperature(&cooling.target_air_temperature)?;
+                writer.temperature(&cooling.histeresis)?;
+                writer.temperature(&cooling.dew_point_margin)?;
+                writer.duration(cooling.min_stop)?;
+
+                writer.temperature(&self.switchover_config.cooling_above)?;
+                writer.temperature(&self.switchover_config.heating_below)?;
+                writer.duration(self.switchover_config.delay)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The settings are changed only if the whole record is valid, the earlier versions are migrated
+    fn decode_record(&mut self, key: u8, version: u8, reader: &mut Reader) -> Option<()> {
+        match key {
+            RECORD_HEATING => {
+                let mut config = floor_heating::Config::decode(reader, version)?;
+                config.target_air_temperature = self.floor_heating_config.target_air_temperature;
+                self.floor_heating_config = config;
+            }
+            RECORD_PROGRAM => self.program = Schedule::decode(reader)?,
+            RECORD_MODE => {
+                let mode = Mode::decode(reader)?;
+                let time_offset = reader.duration()?;
+                let away_config = AwayConfig {
+                    days: reader.u32()?,
+                    hour: reader.u8()?,
+                    setback: reader.optional_temperature()?,
+                };
+                let switchover = match reader.u8()? {
+                    0 => Switchover::Auto,
+                    1 => Switchover::Forced(Season::Heating),
+                    2 => Switchover::Forced(Season::Cooling),
+                    _ => return None,
+                };
+                self.mode = mode;
+                self.time_offset = time_offset;
+                self.away_config = away_config;
+                self.season.switchover = switchover;
+            }
+            RECORD_SENSORS => self.sensor_registry = Registry::decode(reader)?,
+            RECORD_KEYS => self.key_map = KeyMap::decode(reader)?,
+            RECORD_OPTIONS => {
+                if version < 3 {
+                    reader.duration::<Duration<u32, Seconds>>()?; //the dropped dim timeout
+                }
+                let backlight_config = backlight::Config {
+                    off_timeout: reader.duration()?,
+                    wake_on_movement: reader.bool()?,
+                };
+                if !backlight_config.is_valid() {
+                    return None;
+                }
+                let exercise_config = exercise::Config {
+                    enabled: reader.bool()?,
+                    weekday: reader.u8()?,
+                    hour: reader.u8()?,
+                    min_idle: reader.duration()?,
+                    duration: reader.duration()?,
+                };
+                let optimum_start_config = optimum_start::Config {
+                    enabled: reader.bool()?,
+                    max_preheat: reader.duration()?,
+                    default_rate: reader.i32()?,
+                };
+                let open_window_config = open_window::Config {
+                    enabled: reader.bool()?,
+                    drop: reader.temperature()?,
+                    drop_period: reader.duration()?,
+                    recovery: reader.temperature()?,
+                    suspend_duration: reader.duration()?,
+                };
+                let cooling_config = cooling::Config {
+                    target_air_temperature: reader.temperature()?,
+                    histeresis: reader.temperature()?,
+                    dew_point_margin: reader.temperature()?,
+                    min_stop: reader.duration()?,
+                };
+                let switchover_config = cooling::SwitchoverConfig {
+                    cooling_above: reader.temperature()?,
+                    heating_below: reader.temperature()?,
+                    delay: reader.duration()?,
+                };
+                self.backlight_config = backlight_config;
+                self.exercise_config = exercise_config;
+                self.optimum_start_config = optimum_start_config;
+                self.open_window_config = open_window_config;
+                self.cooling_config = cooling_config;
+                self.switchover_config = switchover_config;
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Of all the records, to detect the changes
+    fn config_checksum(&self) -> u16 {
+        let mut crc = CRC_INIT;
+        for key in RECORDS.iter() {
+            let mut buffer = [0u8; RECORD_BUFFER_SIZE];
+            let mut writer = Writer::new(&mut buffer);
+            let _ = self.encode_record(*key, &mut writer);
+            crc = crc16_update(crc, &[*key]);
+            crc = crc16_update(crc, writer.data());
+        }
+        crc
+    }
+
+    /// The defaults are kept for the missing or invalid records
+    fn load_config<F: Flash>(&mut self, store: &Store<F>) {
+        for key in RECORDS.iter() {
+            let mut buffer = [0u8; RECORD_BUFFER_SIZE];
+            if let Some((version, len)) = store.read(*key, &mut buffer) {
+                if version >= MIN_SCHEMA_VERSION && version <= SCHEMA_VERSION {
+                    let _ = self.decode_record(*key, version, &mut Reader::new(&buffer[..len]));
+                }
+            }
+        }
+        self.config_saver = Deferred::new(self.config_checksum());
+    }
+
+    /// Only the changed records are written
+    fn save_config<F: Flash>(
+        &self,
+        store: &mut Store<F>,
+    ) -> Result<(), config_store::Error<F::Error>> {
+        for key in RECORDS.iter() {
+            let mut buffer = [0u8; RECORD_BUFFER_SIZE];
+            let mut writer = Writer::new(&mut buffer);
+            self.encode_record(*key, &mut writer)
+                .map_err(|_| config_store::Error::TooLarge)?;
+            store.write(*key, SCHEMA_VERSION, writer.data())?;
+        }
+        Ok(())
+    }
+
+    /// The log of the screed program is restored after its progress
+    fn load_screed_log<F: Flash>(&mut self, store: &Store<F>) {
+        let mut buffer = [0u8; screed::LOG_RECORD_SIZE];
+        if let Some((_, len)) = store.read(RECORD_SCREED_LOG, &mut buffer) {
+            let _ = self.screed.decode_log(&mut Reader::new(&buffer[..len]));
+        }
+    }
+
+    fn save_screed_log<F: Flash>(
+        &self,
+        store: &mut Store<F>,
+    ) -> Result<(), config_store::Error<F::Error>> {
+        let mut buffer = [0u8; screed::LOG_RECORD_SIZE];
+        let mut writer = Writer::new(&mut buffer);
+        self.screed
+            .encode_log(&mut writer)
+            .map_err(|_| config_store::Error::TooLarge)?;
+        store.write(RECORD_SCREED_LOG, SCHEMA_VERSION, writer.data())
+    }
+
+    /// Applies the sections of the config image, nothing is changed if any of them is invalid.
+    /// The changes are stored by the config saver.
+    fn import_config(&mut self, data: &[u8]) -> Result<(), config_format::Error> {
+        let image = Image::parse(data)?;
+        let version = image.version();
+        let invalid = config_format::Error::Invalid;
+        let heating = image
+            .section(Section::Heating)
+            .map(|mut reader| {
+                floor_heating::Config::decode(&mut reader, version).ok_or(invalid(Section::Heating))
+            })
+            .transpose()?;
+        let program = image
+            .section(Section::Program)
+            .map(|mut reader| Schedule::decode(&mut reader).ok_or(invalid(Section::Program)))
+            .transpose()?;
+        let key_map = image
+            .section(Section::Keys)
+            .map(|mut reader| KeyMap::decode(&mut reader).ok_or(invalid(Section::Keys)))
+            .transpose()?;
+        let sensor_registry = image
+            .section(Section::Sensors)
+            .map(|mut reader| Registry::decode(&mut reader).ok_or(invalid(Section::Sensors)))
+            .transpose()?;
+
+        if let Some(mut config) = heating {
+            config.target_air_temperature = self.floor_heating_config.target_air_temperature;
+            self.floor_heating_config = config;
+        }
+        if let Some(program) = program {
+            self.program = program;
+            self.update_programmed_target(true);
+        }
+        if let Some(key_map) = key_map {
+            self.key_map = key_map;
+        }
+        if let Some(sensor_registry) = sensor_registry {
+            self.sensor_registry = sensor_registry;
+        }
+        Ok(())
+    }
+
+    /// Continues from the retained state of the previous run (if valid), the time is set by the rtc
+    fn restore_retained(
+        &mut self,
+        retained: Option<Retained<Duration<u32, Seconds>>>,
+        reason: ResetReason,
+        time: Time<u32, Seconds>,
+    ) {
+        self.time = time;
+        self.state_since = time;
+        let mut retained = match retained {
+            Some(retained) => {
+                self.time_offset = retained.time_offset;
+                if let Some(state) = retained.state {
+                    self.floor_heating.state = state;
+                }
+                for (device, idle) in self.devices.iter_mut().zip(retained.exercise_idle.iter()) {
+                    device.set_idle(*idle);
+                }
+                retained
+            }
+            None => Retained::new(),
+        };
+        retained.boot(reason);
+        self.retained = retained;
+        self.reset_reason = reason;
+        self.update_weektime();
+    }
+
+    /// The words to retain over the resets
+    fn save_retained(&mut self) -> [u32; RETAINED_WORDS] {
+        self.retained.time_offset = self.time_offset;
+        self.retained.state = Some(self.floor_heating.state);
+        self.retained.uptime = self.uptime;
+        for (i, device) in self.devices.iter().enumerate() {
+            self.retained.exercise_idle[i] = device.idle();
+        }
+        self.retained.save()
+    }
+
     //update by real time clock
     fn update_time(&mut self, time: Time<u32, Seconds>) {
         if self.time != time {
             let delta_time = time - self.time;
             self.time = time;
+            self.uptime = self.uptime + delta_time;
+
+            self.can_outdoor_age = self.can_outdoor_age + delta_time;
+            if self.can_outdoor_age > OUTDOOR_TEMPERATURE_TIMEOUT.s() {
+                self.can_outdoor_temperature = None;
+            }
+            self.can_humidity_age = self.can_humidity_age + delta_time;
+            if self.can_humidity_age > HUMIDITY_TIMEOUT.s() {
+                self.can_humidity = None;
+            }
+            self.demand_link
+                .update(&self.demand_link_config, delta_time);
 
-            self.floor_heating_state = self.floor_heating_state.update(
-                &self.floor_heating_config,
-                self.temperatures[0],
-                self.temperatures[1],
-                self.temperatures[2],
-                self.temperatures[3],
+            let previous_state = discriminant(&self.floor_heating.state);
+            let readings = self.readings();
+            self.open_window.update(
+                &self.open_window_config,
+                readings.get(Role::Air),
                 delta_time,
             );
 
-            if self.backlight_timeout > Duration::<u32, Seconds>::default() {
-                if self.backlight_timeout < delta_time {
-                    self.backlight_timeout = Duration::<u32, Seconds>::default();
-                } else {
-                    self.backlight_timeout = self.backlight_timeout - delta_time;
-                }
+            //no heating in the cooling season, but the freeze protection remains
+            self.season.update(
+                &self.switchover_config,
+                readings.get(Role::Outdoor),
+                delta_time,
+            );
+            let cooling = self.season.season() == Season::Cooling;
+            if cooling && !self.open_window.open() {
+                self.cooler.update(
+                    &self.cooling_config,
+                    &readings,
+                    self.can_humidity,
+                    delta_time,
+                );
+            } else {
+                self.cooler.reset();
             }
+            self.floor_heating.suspended = self.open_window.open() || cooling;
 
-            self.update_weektime();
-        }
-    }
+            //the screed program drives the forward temperature instead of the room
+            self.screed.update(
+                readings.get(Role::Forward).map(|t| t.to_sixteenths()),
+                readings.get(Role::Floor).map(|t| t.to_sixteenths()),
+                delta_time,
+            );
+            self.floor_heating.forward_override = self.screed.forward_override();
+            self.floor_heating
+                .update(&self.floor_heating_config, &readings, delta_time);
+            if discriminant(&self.floor_heating.state) != previous_state {
+                self.state_since = time;
+            }
+
+            self.optimum_start.update(
+                &self.optimum_start_config,
+                self.floor_heating_config
+                    .target_air_temperature
+                    .map(|t| t.to_sixteenths()),
+                readings.get(Role::Air).map(|t| t.to_sixteenths()),
+                delta_time,
+            );
+
+            //the idling valve and pump are exercised in standby only
+            let standby = match self.floor_heating.state {
+                floor_heating::State::Standby(_) => true,
+                _ => false,
+            };
+            self.exerciser.update(
+                &self.exercise_config,
+                &mut self.devices,
+                &self.weektime,
+                standby,
+                delta_time,
+            );
+            let valve_open = self.valve_open();
+            let pump_running = self.pump_running();
+            self.devices[VALVE_DEVICE].record(valve_open, delta_time);
+            self.devices[PUMP_DEVICE].record(pump_running, delta_time);
+            self.statistics.update(
+                &self.floor_heating.state,
+                valve_open,
+                pump_running,
+                self.floor_heating.heat_request(),
+                self.weektime.weekday,
+                delta_time,
+            );
 
-    fn search_current_program_index(&self) -> usize {
-        let mut idx = self.program.len() - 1;
-        let now = Time::<u32, Seconds>::from(self.weektime);
-        for i in 0..self.program.len() {
-            if self.program[i].start_time < now {
-                idx = i;
+            let entry_timeout = if let Some(ref mut entry) = self.entry {
+                entry.update(10.s(), delta_time)
             } else {
-                break;
+                false
             };
+            if entry_timeout {
+                self.entry = None;
+            }
+
+            if self.backlight.update(&self.backlight_config, delta_time) {
+                //exit from menu when the screen turned off
+                self.active_menu = None;
+            }
+
+            let weekday = self.weektime.weekday;
+            self.update_weektime();
+            if weekday != self.weektime.weekday {
+                //midnight passed: the party ends, the away days are counted down
+                let mode = self.mode.midnight(self.weektime.weekday);
+                if mode != self.mode {
+                    self.mode = mode;
+                    self.update_programmed_target(true);
+                }
+            }
         }
-        idx
+    }
+
+    /// The target of the switch point by the mode
+    fn programmed_target(&self, slot: Slot) -> Option<Temperature> {
+        self.program
+            .point(slot)
+            .and_then(|point| self.mode.target(point.target))
     }
 
     fn update_programmed_target(&mut self, force_refresh: bool) {
-        if let ProgramModes::Fix(temp) = self.mode {
+        if let Mode::Fix(temp) = self.mode {
             self.floor_heating_config.target_air_temperature = Some(temp);
             return;
         }
 
-        if let ProgramModes::Party(weekday) = self.mode {
-            //if we are in party mode,
-            if self.weektime.weekday != weekday {
-                //if midnight passed, return to normal mode
-                self.mode = ProgramModes::Normal;
-            } else {
-                //stay in party mode -> the user temperature override remains active
+        let mut force_refresh = force_refresh;
+        if let Some(remaining) = self.mode.away_remaining(&self.weektime) {
+            if remaining > 0 {
+                //the setback is kept until the pre-heat before the return
+                self.floor_heating_config.target_air_temperature =
+                    match self.away_preheat_target(remaining) {
+                        Some(target) => Some(target),
+                        None => self.away_config.setback,
+                    };
                 return;
             }
+            //returned, continue as programmed
+            self.mode = Mode::Normal;
+            force_refresh = true;
+        }
+
+        if let Mode::Party(_) = self.mode {
+            //the user temperature override remains active until midnight
+            return;
         }
 
-        let idx = self.search_current_program_index();
-
-        // //midnight passed:
-        // if WeekTime::from(self.program[self.current_program_index].start_time).weekday
-        //     != self.weektime.weekday
-        // {
-        //     match self.mode {
-        //         ProgramModes::Away((days, hour)) => {
-        //             self.current_program_index = idx;
-        //             self.mode = ProgramModes::Away((days - 1, hour)); //decrease the remaining time
-        //             self.floor_heating_config.target_air_temperature = if days > 0 {
-        //                 //keep defreeze state
-        //                 None
-        //             } else {
-        //                 //on the last day keep normal - 4 degree
-        //                 Some(
-        //                     self.program[idx].target_air_temperature
-        //                         - Temperature::from_celsius(4, 0),
-        //                 )
-        //             };
-        //             return;
-        //         }
-        //         _ => {}
-        //     }
-        // } else {
-        //     if let ProgramModes::Away((days, hour)) = self.mode {
-        //         if days < 1 && self.weektime.hour >= hour {
-        //             //in away mode on thelast day, at the given hour return to normal mode
-        //             self.mode = ProgramModes::Normal;
-        //         } else {
-        //             return;
-        //         }
-        //     }
-        // }
+        let (current, (next, until_next)) = match (
+            self.program.current(&self.weektime),
+            self.program.next(&self.weektime),
+        ) {
+            (Some(current), Some(next)) => (current, next),
+            _ => return, //empty program
+        };
+        let current_target = self.programmed_target(current);
+        let next_target = self.programmed_target(next);
+
+        //the next program is started early, if the room can not heat up in time otherwise
+        self.early_start = (self.early_start && self.current_slot == Some(next) && !force_refresh)
+            || self.preheat_due(current_target, next_target, until_next);
+        let (slot, target) = if self.early_start {
+            (next, next_target)
+        } else {
+            (current, current_target)
+        };
 
         //the user override live until program change:
-        if self.current_program_index != idx || force_refresh {
-            self.current_program_index = idx;
+        if self.current_slot != Some(slot) || force_refresh {
+            self.current_slot = Some(slot);
+            self.floor_heating_config.target_air_temperature = target;
 
-            let offset = if let ProgramModes::Economy(offset) = self.mode {
-                offset
-            } else {
-                Temperature::from_celsius(0, 0)
+            //measure the heat-up for the learning
+            let readings = self.readings();
+            if let (Some(air), Some(target)) = (readings.get(Role::Air), target) {
+                self.optimum_start.start(
+                    air.to_sixteenths(),
+                    target.to_sixteenths(),
+                    readings.get(Role::Outdoor).map(|t| t.to_sixteenths()),
+                );
+            }
+        }
+    }
+
+    /// The programmed target at the return, if the pre-heat should be started
+    fn away_preheat_target(&self, remaining: u32) -> Option<Temperature> {
+        let return_time = self.mode.away_return(&self.weektime)?;
+        let target = self.program.setpoint(&return_time)?;
+
+        let readings = self.readings();
+        let air = readings.get(Role::Air)?;
+        let preheat_time = self.optimum_start.preheat_time(
+            &self.optimum_start_config,
+            air.to_sixteenths(),
+            target.to_sixteenths(),
+            readings.get(Role::Outdoor).map(|t| t.to_sixteenths()),
+        );
+        if self.optimum_start_config.enabled && remaining <= preheat_time {
+            Some(target)
+        } else {
+            None
+        }
+    }
+
+    /// Applies the changed return date to the running away mode
+    fn update_away(&mut self) {
+        if let Mode::Away(_) = self.mode {
+            self.mode = Mode::Away((self.away_config.days, self.away_config.hour));
+            self.update_programmed_target(true);
+        }
+    }
+
+    /// Starts the away mode by CAN: the days (0xFF = stop), the return hour in the first 2 bytes,
+    /// the setback in 1/16 degree (i16, little endian, 0x8000 = freeze protection only) in the next 2 bytes
+    fn can_away_received(&mut self, data: u64) {
+        let days = data as u8;
+        if days == 0xFF {
+            if let Mode::Away(_) = self.mode {
+                self.mode = Mode::Normal;
+            }
+        } else {
+            let setback = (data >> 16) as u16;
+            self.away_config = AwayConfig {
+                days: days as u32,
+                hour: ((data >> 8) as u8) % 24,
+                setback: if setback == 0x8000 {
+                    None
+                } else {
+                    Some(Temperature::from_sixteenths(setback as i16 as i32))
+                },
             };
+            self.mode = Mode::Away((self.away_config.days, self.away_config.hour));
+        }
+        self.update_programmed_target(true);
+    }
+
+    fn preheat_due(
+        &self,
+        current: Option<Temperature>,
+        next: Option<Temperature>,
+        until_next: u32,
+    ) -> bool {
+        let target = match (current, next) {
+            (Some(current), Some(next)) if next.to_sixteenths() > current.to_sixteenths() => {
+                next.to_sixteenths()
+            }
+            _ => return false,
+        };
+        if !self.optimum_start_config.enabled {
+            return false;
+        }
+
+        let readings = self.readings();
+        let air = match readings.get(Role::Air) {
+            Some(air) => air.to_sixteenths(),
+            None => return false,
+        };
+        let preheat_time = self.optimum_start.preheat_time(
+            &self.optimum_start_config,
+            air,
+            target,
+            readings.get(Role::Outdoor).map(|t| t.to_sixteenths()),
+        );
+        until_next <= preheat_time
+    }
+
+    //returns true if the command was consumed by the numeric entry
+    fn numeric_entry(&mut self, command: &IrCommands, numeric_entry: &NumericEntry<Self>) -> bool {
+        if let Some(digit) = command.digit() {
+            let mut entry = self
+                .entry
+                .take()
+                .unwrap_or(Entry::new(numeric_entry.format));
+            entry.push(digit);
+            self.entry = Some(entry);
+            return true;
+        }
 
-            self.floor_heating_config.target_air_temperature =
-                Some(self.program[idx].target_air_temperature + offset);
+        if let Some(mut entry) = self.entry.take() {
+            match command {
+                IrCommands::Ok => {
+                    if !numeric_entry.apply(self, &entry) {
+                        //invalid value, start again
+                        self.entry = Some(Entry::new(entry.format));
+                    }
+                    true
+                }
+                IrCommands::Backspace => {
+                    if entry.backspace() {
+                        self.entry = Some(entry);
+                    }
+                    true
+                }
+                _ => false, //the entry is canceled by any other key
+            }
+        } else {
+            false
         }
     }
 
@@ -930,9 +3299,20 @@ impl<'a, 'b> Model<'a, 'b> {
         command: IrCommands,
         root_menu: &'a Menu<'a, Model<'a, '_>, IrCommands>,
     ) {
-        self.backlight_timeout = 20.s();
+        if !self.backlight.activity() {
+            //the first key press just wakes up the screen
+            return;
+        }
+
+        if let Some(active_menu) = self.active_menu {
+            if let Content::MenuItem(ref item) = active_menu.rows[self.selected_row].content {
+                if let Some(ref numeric_entry) = item.entry {
+                    if self.numeric_entry(&command, numeric_entry) {
+                        return;
+                    }
+                }
+            }
 
-        if let Some(active_menu) = self.active_menu {
             let n = active_menu.rows.len();
             match command {
                 IrCommands::Home => self.active_menu = None,
@@ -966,51 +3346,53 @@ impl<'a, 'b> Model<'a, 'b> {
                 }
             }
         } else {
+            //digits typed on the home screen set the target temperature
+            let target_entry = NumericEntry {
+                format: Format::Decimal,
+                min: 50,
+                max: 300,
+                set: enter_target_temp,
+            };
+            if self.numeric_entry(&command, &target_entry) {
+                return;
+            }
+
             match command {
                 IrCommands::Menu => {
                     self.active_menu = Some(root_menu);
                     self.selected_row = 0;
                 }
                 IrCommands::Right => {
-                    self.floor_heating_config.target_air_temperature = if let Some(target_temp) =
-                        self.floor_heating_config.target_air_temperature
-                    {
-                        Some(target_temp + Temperature::from_celsius(0, 1))
-                    } else {
-                        Some(Temperature::from_celsius(20, 0))
-                    };
+                    self.page = self.page.next();
                 }
                 IrCommands::Left => {
-                    self.floor_heating_config.target_air_temperature = if let Some(target_temp) =
-                        self.floor_heating_config.target_air_temperature
-                    {
-                        Some(target_temp - Temperature::from_celsius(0, 1))
-                    } else {
-                        Some(Temperature::from_celsius(20, 0))
-                    };
+                    self.page = self.page.prev();
                 }
                 IrCommands::Backspace => {
                     self.floor_heating_config.target_air_temperature = None;
                 }
+                IrCommands::Ok => {
+                    //acknowledge the latched error
+                    self.floor_heating.reset_error();
+                }
                 IrCommands::Red => {
-                    self.mode = ProgramModes::Party(self.weektime.weekday);
+                    self.mode = Mode::Party(self.weektime.weekday);
                     self.update_programmed_target(true);
                 }
                 IrCommands::Green => {
-                    self.mode =
-                        ProgramModes::Economy(if let ProgramModes::Economy(offset) = self.mode {
-                            offset
-                        } else {
-                            Temperature::from_celsius(-2, 0)
-                        });
+                    self.mode = Mode::Economy(if let Mode::Economy(offset) = self.mode {
+                        offset
+                    } else {
+                        Temperature::from_celsius(-2, 0)
+                    });
                     self.update_programmed_target(true);
                 }
                 IrCommands::Yellow => {
-                    self.mode = ProgramModes::Normal;
+                    self.mode = Mode::Normal;
                     self.update_programmed_target(true);
                 }
                 IrCommands::Blue => {
-                    self.mode = ProgramModes::Fix(if let ProgramModes::Fix(target) = self.mode {
+                    self.mode = Mode::Fix(if let Mode::Fix(target) = self.mode {
                         target
                     } else {
                         if let Some(current_temp) = self.floor_heating_config.target_air_temperature
@@ -1021,150 +3403,609 @@ impl<'a, 'b> Model<'a, 'b> {
                         }
                     });
                     self.update_programmed_target(true);
-
-                    // self.mode = if let ProgramModes::Away((days, hour)) = self.mode {
-                    //     ProgramModes::Away((days, (hour + 1) % 24))
-                    // } else {
-                    //     ProgramModes::Away((1, self.weektime.hour.into()))
-                    // };
-                    // force_refresh = true;
                 }
                 IrCommands::Up => match self.mode {
-                    // ProgramModes::Away((days, hour)) => {
-                    //     self.mode = ProgramModes::Away((days + 1, hour))
-                    // }
-                    ProgramModes::Economy(offset) => {
-                        self.mode = ProgramModes::Economy(offset + Temperature::from_celsius(0, 1));
+                    Mode::Away((days, hour)) => {
+                        self.away_config.days = days + 1;
+                        self.mode = Mode::Away((days + 1, hour));
+                        self.update_programmed_target(true);
+                    }
+                    Mode::Economy(offset) => {
+                        self.mode = Mode::Economy(offset + Temperature::from_celsius(0, 1));
                         self.update_programmed_target(true);
                     }
-                    ProgramModes::Fix(target) => {
-                        self.mode = ProgramModes::Fix(target + Temperature::from_celsius(0, 1));
+                    Mode::Fix(target) => {
+                        self.mode = Mode::Fix(target + Temperature::from_celsius(0, 1));
+                    }
+                    _ => {
+                        self.floor_heating_config.target_air_temperature =
+                            if let Some(target_temp) =
+                                self.floor_heating_config.target_air_temperature
+                            {
+                                Some(target_temp + Temperature::from_celsius(0, 1))
+                            } else {
+                                Some(Temperature::from_celsius(20, 0))
+                            };
                     }
-                    _ => {}
                 },
                 IrCommands::Down => match self.mode {
-                    // ProgramModes::Away((days, hour)) => {
-                    //     self.mode = ProgramModes::Away((if days > 0 { days - 1 } else { 0 }, hour))
-                    // }
-                    ProgramModes::Economy(offset) => {
-                        self.mode = ProgramModes::Economy(offset - Temperature::from_celsius(0, 1));
+                    Mode::Away((days, hour)) => {
+                        let days = if days > 0 { days - 1 } else { 0 };
+                        self.away_config.days = days;
+                        self.mode = Mode::Away((days, hour));
+                        self.update_programmed_target(true);
+                    }
+                    Mode::Economy(offset) => {
+                        self.mode = Mode::Economy(offset - Temperature::from_celsius(0, 1));
                         self.update_programmed_target(true);
                     }
-                    ProgramModes::Fix(target) => {
-                        self.mode = ProgramModes::Fix(target - Temperature::from_celsius(0, 1));
+                    Mode::Fix(target) => {
+                        self.mode = Mode::Fix(target - Temperature::from_celsius(0, 1));
+                    }
+                    _ => {
+                        self.floor_heating_config.target_air_temperature =
+                            if let Some(target_temp) =
+                                self.floor_heating_config.target_air_temperature
+                            {
+                                Some(target_temp - Temperature::from_celsius(0, 1))
+                            } else {
+                                Some(Temperature::from_celsius(20, 0))
+                            };
                     }
-                    _ => {}
                 },
                 _ => {}
             }
         };
     }
 
+    //update by can messages
+    fn can_frame_received(&mut self) {
+        self.can_received = self.can_received.wrapping_add(1);
+        self.can_last_received = Some(self.time);
+    }
+
     //update by temp sensors
-    fn update_temperature(&mut self, index: usize, temperature: Option<Temperature>) {
-        self.temperatures[index] = temperature;
+    /// Stores the reading of the indexed sensor (None on read error) if it is plausible
+    fn update_temperature(
+        &mut self,
+        index: usize,
+        reading: Option<Temperature>,
+        delta_time: Duration<u32, Seconds>,
+    ) {
+        let role = self.sensor_registry.role_of(&self.roms[index]);
+        self.temperatures[index] =
+            self.checks[index].update(&self.plausibility_config, role, reading, delta_time);
     }
 
-    fn refresh_display<D: lcd_hal::Display, B: OutputPin>(
-        &self,
-        display: &mut D,
-        backlight: &mut B,
-    ) -> Result<(), D::Error> {
-        if self.backlight_timeout == Duration::default() {
-            let _ = backlight.set_high(); //turn off
-        } else {
-            let _ = backlight.set_low(); //turn on
+    fn valve_open(&self) -> bool {
+        self.floor_heating.valve_open()
+            || self.cooler.valve_open()
+            || self.exerciser.valve_active(VALVE_DEVICE, PUMP_DEVICE)
+    }
+
+    fn pump_running(&self) -> bool {
+        self.floor_heating.pump_running()
+            || self.cooler.pump_running()
+            || self.exerciser.active(PUMP_DEVICE)
+    }
+
+    fn can_humidity_received(&mut self, data: u64) {
+        let (humidity, location) = decode_humidity(data);
+        if location == ROOM_LOCATION {
+            self.can_humidity = Some(humidity);
+            self.can_humidity_age = 0.s();
         }
+    }
 
-        if let Some(active_menu) = self.active_menu {
-            //TODO render menu
-            display.clear()?;
-            // TODO let (cols, rows) = display.get_char_resolution();
-            let rows = 8;
-            let start_index = if self.selected_row >= rows {
-                self.selected_row - rows + 1
+    fn can_supply_received(&mut self, data: u64) {
+        self.demand_link.supply_received(data);
+    }
+
+    /// The demand of this zone for the heat source coordinator,
+    /// the forward temperature by the heating curve or the max without it
+    fn heat_demand(&self) -> heat_demand::Demand {
+        let heat = self.floor_heating.heat_request();
+        heat_demand::Demand {
+            heat: heat,
+            cool: self.cooler.cool_request(),
+            forward_temperature: if heat {
+                Some(
+                    self.floor_heating.forward_target.unwrap_or(
+                        self.floor_heating_config
+                            .max_forward_temperature
+                            .to_sixteenths(),
+                    ),
+                )
             } else {
-                0
-            };
+                None
+            },
+        }
+    }
+
+    /// The heat demand to send if it changed or it should be refreshed
+    fn demand_payload(&mut self) -> Option<[u8; 8]> {
+        let demand = self.heat_demand();
+        self.demand_link
+            .publish(&self.demand_link_config, demand)
+            .map(|demand| demand.encode())
+    }
 
-            let (cols, _) = display.get_char_resolution();
-            let (colsx, _) = display.get_pixel_resolution();
-            let colc = colsx / cols;
+    /// The heat request signal is the fallback while the coordinator is missing
+    fn hardwired_request(&self) -> bool {
+        !self.demand_link.coordinator_present()
+    }
 
-            for row in 0..rows {
-                let index = row + start_index;
-                if index >= active_menu.rows.len() {
-                    break;
-                }
+    /// 0 = automatic, 1 = heating, 2 = cooling in the 1st byte
+    fn can_season_received(&mut self, data: u64) {
+        self.season.switchover = match data as u8 {
+            1 => Switchover::Forced(Season::Heating),
+            2 => Switchover::Forced(Season::Cooling),
+            _ => Switchover::Auto,
+        };
+    }
 
-                display.set_position(0, row as u8)?;
+    fn can_open_received(&mut self, data: u64) {
+        let (open, location) = decode_open(data);
+        if location == ROOM_LOCATION {
+            self.open_window.set_contact(open);
+        }
+    }
 
-                display.print_char(if self.selected_row == index {
-                    '>' as u8
-                } else {
-                    ' ' as u8
-                })?;
-                display.print(active_menu.rows[index].text)?;
-
-                if let Content::MenuItem(ref item) = active_menu.rows[index].content {
-                    display.print_char(':' as u8)?;
-                    let content = (item.view)(self);
-                    display.set_position(colsx - colc * content.len() as u8, row as u8)?;
-                    display.print(content)?;
-                }
+    fn can_temperature_received(&mut self, data: u64) {
+        let (value, location) = decode_temperature(data);
+        if location == LOCATION_OUTDOOR {
+            self.can_outdoor_temperature = Some(Temperature::from_sixteenths(value));
+            self.can_outdoor_age = 0.s();
+        }
+    }
+
+    /// The temperatures by the roles of the sensors,
+    /// the local outdoor sensor is preferred over the CAN message
+    fn readings(&self) -> Readings<Temperature> {
+        let mut readings = Readings::new();
+        for i in 0..self.rom_count {
+            if let Some(role) = self.sensor_registry.role_of(&self.roms[i]) {
+                readings.set(role, self.temperatures[i]);
             }
+        }
+        if readings.get(Role::Outdoor).is_none() {
+            readings.set(Role::Outdoor, self.can_outdoor_temperature);
+        }
+        readings
+    }
+
+    fn view_overview<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        let status = match self.screed.running() {
+            Some(program) => screens::Status::Screed {
+                day: self.screed.day(),
+                days: program.days(),
+            },
+            None => screens::Status::Program(self.mode),
+        };
+
+        let entry = self.entry.as_ref().map(|entry| unsafe { entry.fmt() });
+        let target = if let Some(digits) = entry {
+            screens::Target::Entry(digits)
+        } else if self.open_window.open() {
+            screens::Target::OpenWindow
+        } else if let Some(target) = self.screed.target() {
+            screens::Target::Screed(Temperature::from_sixteenths(target))
         } else {
-            //display status
-            display.clear()?;
+            let next_change = match self.mode {
+                Mode::Normal | Mode::Economy(_) => self
+                    .program
+                    .next_change(&self.weektime)
+                    .map(|(start, _)| start),
+                _ => None,
+            };
+            screens::Target::Program {
+                target: self.floor_heating_config.target_air_temperature,
+                next_change: next_change,
+            }
+        };
 
-            display.set_position(0, 0)?;
-            print_time(display, self.weektime)?;
+        let heating = &self.floor_heating;
+        let readings = self.readings();
+        let home = screens::Home {
+            time: self.weektime,
+            status: status,
+            icons: screens::Icons {
+                valve: heating.valve_open(),
+                pump: heating.pump_running(),
+                heat_request: heating.heat_request(),
+                defrost: heating.state.defrost(),
+            },
+            target: target,
+            air: readings.get(Role::Air),
+            forward: readings.get(Role::Forward),
+            back: readings.get(Role::Return),
+            floor: readings.get(Role::Floor),
+        };
+        screens::render_home(&home, display)
+    }
 
-            display.set_position(0, 1)?;
-            match self.mode {
-                ProgramModes::Normal => {
-                    display.print(b"Normal")?;
-                }
-                ProgramModes::Economy(offset) => {
-                    display.print(b"Eco ")?;
-                    display.print(unsafe { fmt_temp(offset) })?;
-                }
-                ProgramModes::Party(_day) => {
-                    display.print(b"Party")?;
-                }
-                ProgramModes::Fix(temp) => {
-                    display.print(b"Fix ")?;
-                    display.print(unsafe { fmt_temp(temp) })?;
-                } // ProgramModes::Away((days, hour)) => {
-                  //     display.print(b"Tavol ")?;
-                  //     print_nnn(display, days);
-                  //     display.print(b"d ")?;
-                  //     print_nn(display, hour);
-                  //     display.print(b":00")?;
-                  // }
+    fn view_sensors<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        static LABELS: [&[u8]; 5] = [b"Elo ", b"Vis ", b"Pad ", b"Lev ", b"Kul "];
+
+        //one row per found sensor: role, temperature, the lower serial bytes of the ROM
+        for i in 0..self.rom_count {
+            let label = match self.sensor_registry.role_of(&self.roms[i]) {
+                Some(role) => LABELS[role.index()],
+                None => b"??? ",
             };
+            print_temp(display, i as u8, label, &self.temperatures[i])?;
+            display.print_char(' ' as u8)?;
+            print_hex(display, &self.roms[i][1..3])?;
+            display.print_char(self.checks[i].health().sign())?;
+        }
 
-            let _ = print_temp(
+        let found = &self.roms[..self.rom_count];
+        display.set_position(0, 5)?;
+        display.print(b"Ism:")?;
+        print_u32(display, self.sensor_registry.unknown_count(found) as u32)?;
+        display.print(b" Hiany:")?;
+        print_u32(display, self.sensor_registry.missing_count(found) as u32)?;
+
+        display.set_position(0, 6)?;
+        display.print(b"Kulso (CAN):")?;
+        print_temp(display, 7, b"        ", &self.can_outdoor_temperature)?;
+        Ok(())
+    }
+
+    fn view_cooling<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        display.set_position(0, 0)?;
+        display.print(match self.cooler.state {
+            cooling::State::Idle => b"Hutes: keszenlet",
+            cooling::State::Cooling => b"Hutes",
+            cooling::State::DewPointStop(_) => b"Hutes: harmatpont",
+        })?;
+
+        display.set_position(0, 1)?;
+        display.print(b"Para:   ")?;
+        match self.can_humidity {
+            Some(humidity) => {
+                print_u32(display, humidity as u32)?;
+                display.print_char('%' as u8)?;
+            }
+            None => display.print(b"-")?,
+        }
+
+        print_temp(
+            display,
+            2,
+            b"Cel:    ",
+            &Some(self.cooling_config.target_air_temperature),
+        )?;
+        print_temp(
+            display,
+            3,
+            b"Harmat: ",
+            &self.cooler.dew_point.map(Temperature::from_sixteenths),
+        )?;
+
+        print_icon(display, 0, 4, &ICON_VALVE, true)?;
+        display.set_position(12, 4)?;
+        display.print(if self.cooler.valve_open() {
+            b"Szelep nyitva"
+        } else {
+            b"Szelep zarva"
+        })?;
+
+        print_icon(display, 0, 5, &ICON_PUMP, true)?;
+        display.set_position(12, 5)?;
+        display.print(if self.cooler.pump_running() {
+            b"Szivattyu be"
+        } else {
+            b"Szivattyu ki"
+        })?;
+        Ok(())
+    }
+
+    fn view_heating<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        if self.season.season() == Season::Cooling {
+            return self.view_cooling(display);
+        }
+        let state = &self.floor_heating.state;
+
+        display.set_position(0, 0)?;
+        display.print(status_text(state))?;
+
+        display.set_position(0, 1)?;
+        display.print(b"Ota: ")?;
+        print_hms(display, &(self.time - self.state_since))?;
+
+        print_temp(
+            display,
+            2,
+            b"Cel:    ",
+            &self.floor_heating_config.target_air_temperature,
+        )?;
+
+        if self.floor_heating_config.control == floor_heating::Control::Pid {
+            display.set_position(0, 3)?;
+            display.print(b"PID:    ")?;
+            print_u32(display, (self.floor_heating.pid.duty() / 10) as u32)?;
+            display.print_char('%' as u8)?;
+        }
+
+        print_icon(display, 0, 4, &ICON_VALVE, true)?;
+        display.set_position(12, 4)?;
+        display.print(if self.floor_heating.valve_open() {
+            b"Szelep nyitva"
+        } else {
+            b"Szelep zarva"
+        })?;
+
+        print_icon(display, 0, 5, &ICON_PUMP, true)?;
+        display.set_position(12, 5)?;
+        display.print(if state.pump_running() {
+            b"Szivattyu be"
+        } else {
+            b"Szivattyu ki"
+        })?;
+
+        print_icon(display, 0, 6, &ICON_HEAT_REQUEST, true)?;
+        display.set_position(12, 6)?;
+        display.print(if self.floor_heating.heat_request() {
+            b"Hokeres be"
+        } else {
+            b"Hokeres ki"
+        })?;
+        //the boiler by the coordinator
+        display.set_position(78, 6)?;
+        display.print(match self.demand_link.supply() {
+            Some(supply) if supply.boiler_on => b"K:be",
+            Some(_) => b"K:ki",
+            None => b"K:--",
+        })?;
+
+        if self.floor_heating.forward_target.is_some() {
+            print_temp(
                 display,
-                3,
-                b"Cel:    ",
-                &self.floor_heating_config.target_air_temperature,
+                7,
+                b"Gorbe:  ",
+                &self
+                    .floor_heating
+                    .forward_target
+                    .map(Temperature::from_sixteenths),
             )?;
+        }
+        Ok(())
+    }
+
+    fn view_errors<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        screens::render_errors(
+            self.floor_heating.state.error(),
+            &self.floor_heating.history,
+            display,
+        )
+    }
+
+    /// State, error code and the health of the sensors by role for the CAN status message
+    fn status_payload(&self) -> [u8; 8] {
+        let mut payload = [0u8; 8];
+        payload[0] = self.floor_heating.state.code();
+        payload[1] = match self.floor_heating.state.error() {
+            Some(reason) => reason.code(),
+            None => 0,
+        };
+        for i in 0..self.rom_count {
+            if let Some(role) = self.sensor_registry.role_of(&self.roms[i]) {
+                payload[2 + role.index()] = self.checks[i].health().code();
+            }
+        }
+        payload[7] = match self.open_window.source() {
+            Some(open_window::Source::Contact) => 1,
+            Some(open_window::Source::Temperature) => 2,
+            None => 0,
+        };
+        payload
+    }
+
+    fn view_statistics<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        display.set_position(0, 0)?;
+        display.print(b"Uzemido:")?;
+        display.set_position(66, 0)?;
+        display.print(b"Ind: ")?;
+        print_u32(display, self.retained.boot_count)?;
+        display.set_position(0, 1)?;
+        print_hms(display, &self.uptime)?;
+        display.set_position(66, 1)?;
+        display.print(b"Wdg: ")?;
+        print_u32(
+            display,
+            self.retained.resets[ResetReason::IndependentWatchdog.index()] as u32
+                + self.retained.resets[ResetReason::WindowWatchdog.index()] as u32,
+        )?;
+
+        display.set_position(0, 2)?;
+        display.print(b"Szenzorok: ")?;
+        print_u32(display, self.rom_count as u32)?;
+        display.set_position(84, 2)?;
+        display.print(reset_reason_text(self.reset_reason))?;
+
+        //idle hours of the valve and the pump
+        display.set_position(0, 3)?;
+        display.print(b"Allas: ")?;
+        print_u32(display, u32::from(self.devices[VALVE_DEVICE].idle()) / 3600)?;
+        display.print(b"h ")?;
+        print_u32(display, u32::from(self.devices[PUMP_DEVICE].idle()) / 3600)?;
+        display.print_char('h' as u8)?;
+
+        display.set_position(0, 4)?;
+        display.print(b"CAN fogadott:")?;
+        display.set_position(0, 5)?;
+        print_u32(display, self.can_received)?;
+
+        display.set_position(0, 6)?;
+        display.print(b"CAN utolso:")?;
+        display.set_position(0, 7)?;
+        if let Some(last) = self.can_last_received {
+            print_hms(display, &(self.time - last))?;
+        } else {
+            display.print(b"-----")?;
+        }
+        Ok(())
+    }
+
+    /// Heat request %, minutes of the states, pump runtime and valve cycles of today,
+    /// heat request % of this week and of the last 7 days
+    fn view_runtime<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        let today = self.statistics.today();
+
+        display.set_position(0, 0)?;
+        display.print(b"Ma: ")?;
+        print_u32(display, today.active_percent())?;
+        display.print(b"% Het: ")?;
+        print_u32(display, self.statistics.week().active_percent())?;
+        display.print_char('%' as u8)?;
 
-            static LABELS: [&[u8]; MAX_THERMOMETER_COUNT] =
-                [b"Elore:  ", b"Vissza: ", b"Padlo:  ", b"Levego: "];
+        static LABELS: [&[u8]; STATE_COUNT] =
+            [b"Elo:", b"Fut:", b"Uto:", b"Ksz:", b"Fgy:", b"Hib:"];
+        for i in 0..STATE_COUNT {
+            display.set_position((i % 2) as u8 * 60, 1 + (i / 2) as u8)?;
+            display.print(LABELS[i])?;
+            print_u32(display, today.state_seconds[i] / 60)?;
+            display.print_char('p' as u8)?;
+        }
+
+        display.set_position(0, 4)?;
+        display.print(b"Sziv: ")?;
+        print_u32(display, today.pump_seconds / 60)?;
+        display.print(b"p Nyit: ")?;
+        print_u32(display, today.valve_cycles)?;
 
-            for i in 0..4 as u8 {
-                let _ = print_temp(
-                    display,
-                    4 + i,
-                    LABELS[i as usize],
-                    &self.temperatures[i as usize],
+        //the days before today, the newest first
+        display.set_position(0, 5)?;
+        display.print(b"Elozo 7 nap:")?;
+        for days_ago in 1..=HISTORY_DAYS {
+            if let Some(day) = self.statistics.day(days_ago) {
+                display.set_position(
+                    ((days_ago - 1) % 4) as u8 * 30,
+                    6 + ((days_ago - 1) / 4) as u8,
                 )?;
+                print_u32(display, day.active_percent())?;
+                display.print_char('%' as u8)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The statistics requested over CAN: the bucket (0 = today, 1..7 = days ago,
+    /// 8 = this week, 9 = last week) and the page (0 = summary, 1 and 2 = state minutes) in the first 2 bytes
+    fn statistics_payload(&self, request: u64) -> [u8; 8] {
+        let bucket = request as u8;
+        let page = (request >> 8) as u8;
+        let counters = match bucket {
+            0 => Some(self.statistics.today()),
+            8 => Some(self.statistics.week()),
+            9 => Some(self.statistics.last_week()),
+            days_ago => self.statistics.day(days_ago as usize),
+        };
+
+        let mut payload = [0u8; 8];
+        payload[0] = bucket;
+        payload[1] = page;
+        let counters = match counters {
+            Some(counters) => counters,
+            None => return payload,
+        };
+        let minutes = |seconds: u32| {
+            let minutes = seconds / 60;
+            if minutes > 0xFFFF {
+                0xFFFF
+            } else {
+                minutes
+            }
+        };
+        let values = match page {
+            0 => [
+                minutes(counters.heat_request_seconds),
+                minutes(counters.pump_seconds),
+                (counters.valve_cycles & 0xFF) | (counters.active_percent() << 8),
+            ],
+            1 | 2 => {
+                let first = (page as usize - 1) * 3;
+                [
+                    minutes(counters.state_seconds[first]),
+                    minutes(counters.state_seconds[first + 1]),
+                    minutes(counters.state_seconds[first + 2]),
+                ]
+            }
+            _ => return payload,
+        };
+        for (i, value) in values.iter().enumerate() {
+            payload[2 + 2 * i] = *value as u8;
+            payload[3 + 2 * i] = (*value >> 8) as u8;
+        }
+        payload
+    }
+
+    /// The program, the day, the phase and the forward target of the screed program,
+    /// the target and the achieved averages (forward, floor) of the last days
+    fn view_screed<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        display.set_position(0, 0)?;
+        display.print(b"Esztrich: ")?;
+        display.print(screed_program_text(self.screed.program()))?;
+
+        let program = match self.screed.program() {
+            Some(program) => program,
+            None => return Ok(()),
+        };
+        display.set_position(0, 1)?;
+        display.print(b"Nap: ")?;
+        print_u32(display, self.screed.day() as u32 + 1)?;
+        display.print_char('/' as u8)?;
+        print_u32(display, program.days() as u32)?;
+
+        display.set_position(0, 2)?;
+        match self.screed.phase() {
+            Some(phase) => display.print(phase_text(phase))?,
+            None => display.print(b"Befejezve")?,
+        }
+        if let Some(target) = self.screed.target() {
+            let _ = print_temp(
+                display,
+                3,
+                b"Elore:  ",
+                &Some(Temperature::from_sixteenths(target)),
+            )?;
+        }
+
+        //the days before the current one, the newest last
+        display.set_position(0, 4)?;
+        display.print(b"Nap  Cel Elore Padlo")?;
+        let day = self.screed.day();
+        for (row, past) in (day.saturating_sub(3)..day).enumerate() {
+            if let Some(log) = self.screed.log(past) {
+                display.set_position(0, 5 + row as u8)?;
+                print_nn(display, (past + 1) as u8)?;
+                for value in [Some(log.target), log.forward_average, log.floor_average].iter() {
+                    match value {
+                        Some(value) => display
+                            .print(unsafe { fmt_temp(Temperature::from_sixteenths(*value)) })?,
+                        None => display.print(b"  --  ")?,
+                    }
+                }
             }
+        }
+        Ok(())
+    }
 
-            //display.set_position(90, 7)?;
-            //display.print(b" TTTTTTTTTTTTTTTT")?;
+    fn refresh_display<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        if let Some(active_menu) = self.active_menu {
+            active_menu.render(self, self.selected_row, self.entry.as_ref(), display)?;
+        } else {
+            display.clear()?;
+            match self.page {
+                Page::Overview => self.view_overview(display)?,
+                Page::Sensors => self.view_sensors(display)?,
+                Page::Heating => self.view_heating(display)?,
+                Page::Errors => self.view_errors(display)?,
+                Page::Statistics => self.view_statistics(display)?,
+                Page::Runtime => self.view_runtime(display)?,
+                Page::Screed => self.view_screed(display)?,
+            }
         }
 
         Ok(())
@@ -1194,12 +4035,10 @@ fn main() -> ! {
         .freeze(&mut flash.acr);
     watchdog.feed();
 
-    // real time clock
-    let rtc = {
-        let mut pwr = device.PWR;
-        let mut backup_domain = rcc.bkp.constrain(device.BKP, &mut rcc.apb1, &mut pwr);
-        rtc::Rtc::rtc(device.RTC, &mut backup_domain)
-    };
+    // real time clock, the backup registers keep the screed progress
+    let mut pwr = device.PWR;
+    let mut backup_domain = rcc.bkp.constrain(device.BKP, &mut rcc.apb1, &mut pwr);
+    let rtc = rtc::Rtc::rtc(device.RTC, &mut backup_domain);
     watchdog.feed();
 
     let mut afio = device.AFIO.constrain(&mut rcc.apb2);
@@ -1292,14 +4131,43 @@ fn main() -> ! {
     let mut model = Model::new();
     can.configure(&model.can_config);
 
+    //the stored settings
+    let mut config_store = Store::new(Stm32Flash::new(CONFIG_FLASH_ADDRESS, CONFIG_PAGES)).unwrap();
+    model.load_config(&config_store);
+
+    //the retained state is newer than the stored settings
+    let reset_flags = unsafe { &(*stm32f1xx_hal::pac::RCC::ptr()).csr };
+    let reset_reason = ResetReason::from_csr(reset_flags.read().bits());
+    reset_flags.modify(|_, w| w.rmvf().set_bit());
+    model.restore_retained(
+        Retained::restore(unsafe { &RETAINED.as_ptr().read_volatile() }),
+        reset_reason,
+        Time::<u32, Seconds>::from_sec(rtc.current_time()),
+    );
+
+    //continue the screed program after a reset
+    let mut screed_progress = [0u16; PROGRESS_WORDS];
+    for (i, word) in screed_progress.iter_mut().enumerate() {
+        *word = backup_domain.read_data_register_low(SCREED_REGISTER + i);
+    }
+    model.screed.restore(&screed_progress);
+    model.load_screed_log(&config_store);
+    let mut screed_day = (model.screed.program(), model.screed.day());
+
     watchdog.feed();
     nb::block!(can.to_normal()).unwrap(); //just to be sure
 
     watchdog.feed();
     let can_reconfigure_id: Id = Id::new_standard(13);
+    let can_reconfigured_id: Id = Id::new_standard(15);
     let can_ask_status_id: Id = Id::new_standard(14);
-    let _can_heat_request_id: Id = Id::new_standard(15);
     let _can_temperature_report_id: Id = Id::new_standard(16);
+    let can_status_id: Id = Id::new_standard(17);
+    let can_ask_statistics_id: Id = Id::new_standard(18);
+    let can_statistics_id: Id = Id::new_standard(19);
+    let can_season_id: Id = Id::new_standard(20);
+    let can_away_id: Id = Id::new_standard(21);
+    let can_heat_demand_id: Id = heat_demand_id(ROOM_LOCATION);
 
     let filterbank0_config = FilterBankConfiguration {
         mode: FilterMode::List,
@@ -1312,9 +4180,75 @@ fn main() -> ! {
     };
     can.configure_filter_bank(0, &filterbank0_config);
 
+    let filterbank1_config = FilterBankConfiguration {
+        mode: FilterMode::List,
+        info: FilterInfo::Whole(FilterData {
+            id: ID_MOVEMENT,
+            mask_or_id2: ID_TEMPERATURE,
+        }),
+        fifo_assignment: 0,
+        active: true,
+    };
+    can.configure_filter_bank(1, &filterbank1_config);
+
+    let filterbank2_config = FilterBankConfiguration {
+        mode: FilterMode::List,
+        info: FilterInfo::Whole(FilterData {
+            id: ID_OPEN,
+            mask_or_id2: ID_OPEN,
+        }),
+        fifo_assignment: 0,
+        active: true,
+    };
+    can.configure_filter_bank(2, &filterbank2_config);
+
+    let filterbank3_config = FilterBankConfiguration {
+        mode: FilterMode::List,
+        info: FilterInfo::Whole(FilterData {
+            id: can_ask_statistics_id.clone(),
+            mask_or_id2: can_ask_statistics_id.clone(),
+        }),
+        fifo_assignment: 0,
+        active: true,
+    };
+    can.configure_filter_bank(3, &filterbank3_config);
+
+    let filterbank4_config = FilterBankConfiguration {
+        mode: FilterMode::List,
+        info: FilterInfo::Whole(FilterData {
+            id: ID_HUMIDITY,
+            mask_or_id2: can_season_id.clone(),
+        }),
+        fifo_assignment: 0,
+        active: true,
+    };
+    can.configure_filter_bank(4, &filterbank4_config);
+
+    let filterbank5_config = FilterBankConfiguration {
+        mode: FilterMode::List,
+        info: FilterInfo::Whole(FilterData {
+            id: can_away_id.clone(),
+            mask_or_id2: can_away_id.clone(),
+        }),
+        fifo_assignment: 0,
+        active: true,
+    };
+    can.configure_filter_bank(5, &filterbank5_config);
+
+    let filterbank6_config = FilterBankConfiguration {
+        mode: FilterMode::List,
+        info: FilterInfo::Whole(FilterData {
+            id: ID_SUPPLY,
+            mask_or_id2: ID_SUPPLY,
+        }),
+        fifo_assignment: 0,
+        active: true,
+    };
+    can.configure_filter_bank(6, &filterbank6_config);
+
     let (tx, rx) = can.split();
 
-    let (mut _tx0, mut _tx1, mut _tx2) = tx.split();
+    let (mut tx0, mut tx1, mut _tx2) = tx.split();
     let (mut rx0, mut _rx1) = rx.split();
 
     watchdog.feed();
@@ -1356,8 +4290,17 @@ fn main() -> ! {
     //not mutable anymore
     let roms = roms;
     let count = count;
+    model.roms = roms;
+    model.rom_count = count;
+
+    //the sensors are assigned in the order of discovery at the first boot,
+    //later the unknown and missing ones are shown on the sensors page for the assignment in the menu
+    if model.sensor_registry.is_empty() {
+        model.sensor_registry.assign_new(&roms[..count]);
+    }
 
     let mut last_time = tick.now();
+    let mut config_import = Assembler::new();
 
     //let mut hstdout = hio::hstdout().unwrap();
     loop {
@@ -1367,6 +4310,8 @@ fn main() -> ! {
 
         //receive and process can messages
         if let Ok((filter_match_index, _time, frame)) = rx0.read() {
+            model.can_frame_received();
+
             // writeln!(
             //     hstdout,
             //     "rx0: {} {} {} {} {}",
@@ -1378,12 +4323,52 @@ fn main() -> ! {
             // ).unwrap();
 
             match filter_match_index {
-                0 => assert!(*frame.id() == can_reconfigure_id), //TODO decode new config
-                1 => assert!(*frame.id() == can_ask_status_id),  //TODO send status on can
-                _ => {}                                          //panic!("unexpected"),
+                0 => {
+                    assert!(*frame.id() == can_reconfigure_id);
+                    let chunk = frame.data().data_as_u64().to_le_bytes();
+                    if let Some(image) = config_import.receive(&chunk) {
+                        let result = match model.import_config(image) {
+                            Ok(()) => 0,
+                            Err(error) => error.code(),
+                        };
+                        let _ = tx0.request_transmit(&Frame::new(
+                            can_reconfigured_id,
+                            Payload::new(&[result]),
+                        ));
+                    }
+                }
+                1 => {
+                    assert!(*frame.id() == can_ask_status_id);
+                    let _ = tx0.request_transmit(&Frame::new(
+                        can_status_id,
+                        Payload::new(&model.status_payload()),
+                    ));
+                }
+                2 => {
+                    model.backlight.movement(&model.backlight_config);
+                }
+                3 => model.can_temperature_received(frame.data().data_as_u64()),
+                4 | 5 => model.can_open_received(frame.data().data_as_u64()),
+                6 | 7 => {
+                    let _ = tx0.request_transmit(&Frame::new(
+                        can_statistics_id,
+                        Payload::new(&model.statistics_payload(frame.data().data_as_u64())),
+                    ));
+                }
+                8 => model.can_humidity_received(frame.data().data_as_u64()),
+                9 => model.can_season_received(frame.data().data_as_u64()),
+                10 | 11 => model.can_away_received(frame.data().data_as_u64()),
+                12 | 13 => model.can_supply_received(frame.data().data_as_u64()),
+                _ => {} //panic!("unexpected"),
             }
         };
 
+        if model.backlight.is_lit() {
+            backlight.set_low().unwrap();
+        } else {
+            backlight.set_high().unwrap();
+        }
+
         // if let Ok((filter_match_index, time, frame)) = rx1.read() {
         //     ...
         // };
@@ -1396,10 +4381,10 @@ fn main() -> ! {
         match ir_cmd {
             Ok(ir::NecContent::Repeat) => {}
             Ok(ir::NecContent::Data(data)) => {
-                let command = translate(data);
+                let command = model.key_map.translate(data);
                 //write!(hstdout, "{:x}={:?} ", data, command).unwrap();
                 model.ir_remote_command(command, &MENU);
-                model.refresh_display(&mut display, &mut backlight).unwrap();
+                model.refresh_display(&mut display).unwrap();
             }
             _ => {}
         }
@@ -1428,6 +4413,7 @@ fn main() -> ! {
                     Ok(temperature) => Some(temperature),
                     Err(_code) => None,
                 },
+                delta_sec.s(),
             );
             let _ = one_wire.start_temperature_measurement(&roms[i]);
         }
@@ -1436,29 +4422,55 @@ fn main() -> ! {
         model.update_time(Time::<u32, Seconds>::from_sec(rtc.current_time()));
         model.update_programmed_target(false);
 
+        //the screed progress is saved when it changes (by the minute)
+        let progress = model.screed.save();
+        if progress != screed_progress {
+            for (i, word) in progress.iter().enumerate() {
+                backup_domain.write_data_register_low(SCREED_REGISTER + i, *word);
+            }
+            screed_progress = progress;
+        }
+        //the log of the screed program is written at the day changes (and at the start)
+        let day = (model.screed.program(), model.screed.day());
+        if day != screed_day {
+            watchdog.feed();
+            let _ = model.save_screed_log(&mut config_store); //a failed write is retried at the next day
+            screed_day = day;
+        }
+
+        unsafe { RETAINED.as_mut_ptr().write_volatile(model.save_retained()) };
+
+        //the settings are written once they are unchanged for a while (e.g. after the menu edits)
+        let checksum = model.config_checksum();
+        if model
+            .config_saver
+            .update(checksum, CONFIG_SAVE_DELAY.s(), delta_sec.s())
+        {
+            watchdog.feed(); //the page rotation takes a few erase and program cycles
+            let _ = model.save_config(&mut config_store); //a failed write is retried at the next change
+        }
+
         // drive outputs, send messages:
+        if let Some(payload) = model.demand_payload() {
+            let _ = tx1.request_transmit(&Frame::new(can_heat_demand_id, Payload::new(&payload)));
+        }
 
         // let txresult0 = tx0.request_transmit(&Frame::new(
         //     can_temperature_report_id,
         //     Payload::new(temp_sensors[0]),
         // ));
-        // let txresult1 = tx1.request_transmit(&Frame::new(
-        //     can_heat_request_id, Payload::new(true)
-        // ));
         // let txresult2 = tx2.request_transmit(&Frame::new(
         //     can_reconfigure_id,
         //     Payload::new(floor_heating_config.target_air_temperature),
         // ));
 
-        let _status_text = match model.floor_heating_state {
+        match model.floor_heating.state {
             floor_heating::State::PrepareHeating((defreeze, _)) => {
                 valve.open().unwrap();
                 pump.start().unwrap();
                 heat_request.set_low().unwrap();
-                //CAN: no heat request yet
                 if defreeze {
                     rgb.color(Colors::Purple).unwrap();
-                    "...Olvasztas"
                 } else {
                     rgb.color(if (u32::from(model.time) & 1) != 0 {
                         Colors::Yellow
@@ -1466,70 +4478,98 @@ fn main() -> ! {
                         Colors::Red
                     })
                     .unwrap();
-                    "...Futes"
                 }
             }
             floor_heating::State::Heating(defreeze) => {
-                valve.open().unwrap();
+                //the heating curve may throttle the heat request and the valve
+                if model.floor_heating.valve_open() {
+                    valve.open().unwrap();
+                } else {
+                    valve.close().unwrap();
+                }
                 pump.start().unwrap();
-                heat_request.set_high().unwrap();
-                //CAN: heat request!
+                if model.floor_heating.heat_request() && model.hardwired_request() {
+                    heat_request.set_high().unwrap();
+                } else {
+                    heat_request.set_low().unwrap();
+                }
                 if defreeze {
                     rgb.color(Colors::Purple).unwrap();
-                    "Olvasztas"
                 } else {
                     rgb.color(Colors::Red).unwrap();
-                    "Futes"
                 }
             }
             floor_heating::State::AfterCirculation(_) => {
                 valve.close().unwrap();
                 pump.start().unwrap();
                 heat_request.set_low().unwrap();
-                //CAN: stop heat request
                 rgb.color(if (u32::from(model.time) & 1) != 0 {
                     Colors::Yellow
                 } else {
                     Colors::Green
                 })
                 .unwrap();
-                "Utokeringetes"
             }
             floor_heating::State::Standby(_) => {
-                valve.close().unwrap();
-                pump.stop().unwrap();
-                heat_request.set_low().unwrap();
-                //CAN: no heat request
-                rgb.color(Colors::Green).unwrap();
-                "Keszenlet"
+                //the valve and the pump may be exercised
+                if model.valve_open() {
+                    valve.open().unwrap();
+                } else {
+                    valve.close().unwrap();
+                }
+                if model.pump_running() {
+                    pump.start().unwrap();
+                } else {
+                    pump.stop().unwrap();
+                }
+                //the heat source is requested for the chilled water too
+                if model.cooler.cool_request() && model.hardwired_request() {
+                    heat_request.set_high().unwrap();
+                } else {
+                    heat_request.set_low().unwrap();
+                }
+                if model.cooler.cool_request() {
+                    rgb.color(Colors::White).unwrap();
+                } else {
+                    rgb.color(Colors::Green).unwrap();
+                }
             }
             floor_heating::State::FreezeProtectionCheckCirculation(_) => {
                 valve.close().unwrap();
                 pump.start().unwrap();
                 heat_request.set_low().unwrap();
-                //CAN: no heat request
                 rgb.color(Colors::Blue).unwrap();
-                "Fagyvizsgalat"
             }
-            floor_heating::State::Error => {
-                //CAN: sensor missing error
-                rgb.color(Colors::Cyan).unwrap();
-                "Szenzorhiba"
+            floor_heating::State::Error((reason, _, _)) => {
+                //the freeze protection is kept in the error state
+                if model.floor_heating.valve_open() {
+                    valve.open().unwrap();
+                } else {
+                    valve.close().unwrap();
+                }
+                if model.pump_running() {
+                    pump.start().unwrap();
+                } else {
+                    pump.stop().unwrap();
+                }
+                if model.floor_heating.heat_request() && model.hardwired_request() {
+                    heat_request.set_high().unwrap();
+                } else {
+                    heat_request.set_low().unwrap();
+                }
+                //the reason code is blinked, then a pause
+                let code = reason.code() as u32;
+                let phase = u32::from(model.time) % (2 * code + 3);
+                rgb.color(if phase < 2 * code && phase % 2 == 0 {
+                    Colors::Cyan
+                } else {
+                    Colors::Black
+                })
+                .unwrap();
             }
-        };
-
-        //TODO count the seconds while the heating is active
-        //display the daily active %
-
-        model.refresh_display(&mut display, &mut backlight).unwrap();
-
-        if backlight.is_high().unwrap() {
-            //exit from menu when backlight timed out
-            model.active_menu = None;
         }
 
-        //display.set_position(0, 2);
-        //display.print(_status_text);
+        model.refresh_display(&mut display).unwrap();
     }
 }
 
@@ -0,0 +1,668 @@
+//! Multi-zone floor heating controller for a manifold with one shared pump
+//!
+//! DS18B20 1-wire temperature sensors connected to B4 GPIO, in ROM search order:
+//!   forward, return (shared by the zones), then the air sensor of each zone
+//! JTAG is removed from B3, B4 to make it work
+//!
+//! Solid state relays drive the zone valves on B6, B8, B9, B10
+//! Solid state relay on B7 drives the shared pump
+//! Heat request signal (open collector NPN transistor) on B11
+//!
+//! RGB led on PB13, PB14, PB15 as push pull output
+//!
+//! Hx1230 lcd display conected to SPI1 and some gpio port:
+//!   PA5 = Display SPI clock
+//!   PA7 = Display SPI data
+//!   PA2 = Display Chip Select^
+//!   PA1 = Display Reset^
+//!   B12 = Display Backlight^ (with a PNP transistor) - use open drain output!
+//!
+//! PA11, PA12 = CAN RX, TX, the outdoor temperature is received in ID_TEMPERATURE messages
+//!
+//! The shared heat demand is published to the heat source coordinator over CAN,
+//! the heat request signal is driven only while the coordinator is missing.
+//!
+//! Every zone has its own daily program and floor heating state machine,
+//! the pump and the heat request are shared.
+//! The idling valves and the pump are exercised weekly while none of the zones is heating,
+//! their idle times are retained over the resets.
+//!
+//#![deny(unsafe_code)]
+//#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use cortex_m;
+use cortex_m_rt;
+use embedded_hal;
+use lcd_hal;
+use onewire;
+use panic_halt as _;
+use room_pill;
+use stm32f1xx_hal;
+
+use core::mem::MaybeUninit;
+use cortex_m_rt::{entry, exception, ExceptionFrame};
+use embedded_hal::{digital::v2::OutputPin, watchdog::WatchdogEnable};
+use lcd_hal::{hx1230, hx1230::Hx1230, Display};
+use onewire::{ds18x20::*, temperature::Temperature, *};
+use room_pill::{
+    display::*,
+    exercise, floor_heating, heat_demand, heating_curve,
+    messenger::{decode_temperature, heat_demand_id, ID_SUPPLY, ID_TEMPERATURE, LOCATION_OUTDOOR},
+    pid::PidConfig,
+    pump::*,
+    retained::{ResetReason, Retained, RETAINED_WORDS},
+    rgb::*,
+    sensors::Role,
+    temperature::FixedPoint,
+    timing::{Duration, Seconds, SysTicks, Ticker, Time, TimeExt, WeekTime},
+    valve::*,
+    zones::{SharedPump, Zone},
+};
+use stm32f1xx_hal::{
+    afio::AfioExt, can::*, delay::Delay, flash::FlashExt, gpio::GpioExt, prelude::*, rcc::RccExt,
+    rtc, watchdog::IndependentWatchdog,
+};
+
+const ZONE_COUNT: usize = 4;
+const MAX_THERMOMETER_COUNT: usize = 2 + ZONE_COUNT;
+const PROGRAMS_PER_DAY: usize = 4;
+const PUMP_DEVICE: usize = ZONE_COUNT; //index for the exercise after the zone valves
+const OUTDOOR_TEMPERATURE_TIMEOUT: u32 = 30 * 60; //the CAN outdoor temperature is dropped after this many seconds
+const HEAT_DEMAND_ZONE: u8 = 2; //of the manifold at the coordinator
+
+//not initialized at the boot, so it keeps the state of the previous run over the resets
+#[link_section = ".uninit.RETAINED"]
+static mut RETAINED: MaybeUninit<[u32; RETAINED_WORDS]> = MaybeUninit::uninit();
+
+#[derive(Clone, Copy)]
+struct ProgramEntry {
+    start_hour: u8,
+    target_air_temperature: Temperature,
+}
+
+/// The same daily program for the zones by default, it can be changed per zone
+fn default_program() -> [ProgramEntry; PROGRAMS_PER_DAY] {
+    [
+        ProgramEntry {
+            start_hour: 6,
+            target_air_temperature: Temperature::from_celsius(21, 0),
+        },
+        ProgramEntry {
+            start_hour: 9,
+            target_air_temperature: Temperature::from_celsius(19, 0),
+        },
+        ProgramEntry {
+            start_hour: 16,
+            target_air_temperature: Temperature::from_celsius(21, 0),
+        },
+        ProgramEntry {
+            start_hour: 22,
+            target_air_temperature: Temperature::from_celsius(18, 0),
+        },
+    ]
+}
+
+fn zone_config() -> floor_heating::Config<Temperature, Duration<u32, Seconds>> {
+    floor_heating::Config {
+        control: floor_heating::Control::Histeresis,
+        mode: floor_heating::Mode::Air, //the zones have air sensors only
+        floor: floor_heating::FloorConfig {
+            target_temperature: Temperature::from_celsius(24, 0),
+            min_temperature: Temperature::from_celsius(22, 0),
+            histeresis: Temperature::from_celsius(0, 8),
+        },
+        pid: PidConfig {
+            kp: 500,
+            ki: 200,
+            kd: 0,
+            cycle_period: Duration::<u32, Seconds>::from_hms(0, 30, 0),
+            min_pulse: Duration::<u32, Seconds>::from_hms(0, 5, 0),
+        },
+        heating_curve: heating_curve::Config {
+            enabled: false,
+            slope: 5,
+            offset: Temperature::from_celsius(2, 0),
+            room_factor: 30,
+            min_forward_temperature: Temperature::from_celsius(22, 0),
+            histeresis: Temperature::from_celsius(2, 0),
+            modulation: heating_curve::Modulation::HeatRequest,
+        },
+        max_forward_temperature: Temperature::from_celsius(40, 0),
+        max_floor_temperature: Temperature::from_celsius(29, 0),
+        target_air_temperature: None,
+        temperature_histeresis: Temperature::from_celsius(0, 2),
+        freeze_protection: floor_heating::FreezeProtectionConfig {
+            min_temperature: Temperature::from_celsius(5, 0),
+            safe_temperature: Temperature::from_celsius(8, 0),
+            check_interval: Duration::<u32, Seconds>::from_hms(4, 0, 0),
+            check_duration: Duration::<u32, Seconds>::from_hms(0, 4, 0),
+        },
+        pre_circulation_duration: Duration::<u32, Seconds>::from_hms(0, 4, 0),
+        after_circulation_duration: Duration::<u32, Seconds>::from_hms(0, 4, 0),
+        error: floor_heating::ErrorConfig {
+            policies: [
+                floor_heating::Policy::Retry,
+                floor_heating::Policy::Retry,
+                floor_heating::Policy::Latching,
+                floor_heating::Policy::Latching,
+            ],
+            min_hold: Duration::<u32, Seconds>::from_hms(0, 5, 0),
+            overheat_margin: Temperature::from_celsius(5, 0),
+            circulation_difference: Temperature::from_celsius(20, 0),
+            circulation_timeout: Duration::<u32, Seconds>::from_hms(0, 30, 0),
+        },
+    }
+}
+
+struct Model {
+    //config:
+    after_circulation_duration: Duration<u32, Seconds>, //of the shared pump
+    time_offset: Duration<u32, Seconds>,                //used for rtc to weektime calibration
+    programs: [[ProgramEntry; PROGRAMS_PER_DAY]; ZONE_COUNT],
+    exercise_config: exercise::Config<Duration<u32, Seconds>>,
+    demand_link_config: heat_demand::LinkConfig<Duration<u32, Seconds>>,
+
+    //state:
+    zones: [Zone<Temperature, Duration<u32, Seconds>>; ZONE_COUNT],
+    pump: SharedPump<Duration<u32, Seconds>>,
+    exerciser: exercise::Exerciser<Duration<u32, Seconds>>,
+    devices: [exercise::Device<Duration<u32, Seconds>>; ZONE_COUNT + 1], //the zone valves, the pump
+    temperatures: [Option<Temperature>; MAX_THERMOMETER_COUNT],
+    outdoor_temperature: Option<Temperature>,
+    outdoor_age: Duration<u32, Seconds>, //since the last outdoor temperature message
+    demand_link: heat_demand::DemandLink<Duration<u32, Seconds>>,
+    retained: Retained<Duration<u32, Seconds>>,
+    time: Time<u32, Seconds>, //rtc based, ever increasing, in seconds
+    weektime: WeekTime,
+}
+
+impl Model {
+    fn new() -> Self {
+        Model {
+            after_circulation_duration: Duration::<u32, Seconds>::from_hms(0, 4, 0),
+            time_offset: 0u32.s(),
+            programs: [default_program(); ZONE_COUNT],
+            exercise_config: exercise::Config {
+                enabled: true,
+                weekday: 2, //wednesday
+                hour: 11,
+                min_idle: Duration::<u32, Seconds>::from_hms(72, 0, 0),
+                duration: Duration::<u32, Seconds>::from_hms(0, 5, 0),
+            },
+            demand_link_config: heat_demand::LinkConfig {
+                period: 30.s(),
+                coordinator_timeout: Duration::<u32, Seconds>::from_hms(0, 2, 0),
+            },
+
+            zones: [
+                Zone::new(zone_config()),
+                Zone::new(zone_config()),
+                Zone::new(zone_config()),
+                Zone::new(zone_config()),
+            ],
+            pump: SharedPump::new(),
+            exerciser: exercise::Exerciser::new(),
+            devices: [
+                exercise::Device::new(),
+                exercise::Device::new(),
+                exercise::Device::new(),
+                exercise::Device::new(),
+                exercise::Device::new(),
+            ],
+            temperatures: [None; MAX_THERMOMETER_COUNT],
+            outdoor_temperature: None,
+            outdoor_age: 0.s(),
+            demand_link: heat_demand::DemandLink::new(),
+            retained: Retained::new(),
+            time: Time::<u32, Seconds>::from_sec(0),
+            weektime: WeekTime::default(),
+        }
+    }
+
+    /// Target of the program entry started last (the last entry of the day is active before the first one)
+    fn programmed_target(&self, zone: usize) -> Temperature {
+        let program = &self.programs[zone];
+        let mut target = program[PROGRAMS_PER_DAY - 1].target_air_temperature;
+        for entry in program.iter() {
+            if entry.start_hour <= self.weektime.hour {
+                target = entry.target_air_temperature;
+            }
+        }
+        target
+    }
+
+    /// Continues with the idle times of the previous run (if valid)
+    fn restore_retained(
+        &mut self,
+        retained: Option<Retained<Duration<u32, Seconds>>>,
+        reason: ResetReason,
+    ) {
+        let mut retained = retained.unwrap_or_else(Retained::new);
+        for (device, idle) in self.devices.iter_mut().zip(retained.exercise_idle.iter()) {
+            device.set_idle(*idle);
+        }
+        retained.boot(reason);
+        self.retained = retained;
+    }
+
+    /// The words to retain over the resets
+    fn save_retained(&mut self) -> [u32; RETAINED_WORDS] {
+        for (i, device) in self.devices.iter().enumerate() {
+            self.retained.exercise_idle[i] = device.idle();
+        }
+        self.retained.save()
+    }
+
+    fn can_temperature_received(&mut self, data: u64) {
+        let (value, location) = decode_temperature(data);
+        if location == LOCATION_OUTDOOR {
+            self.outdoor_temperature = Some(Temperature::from_sixteenths(value));
+            self.outdoor_age = 0.s();
+        }
+    }
+
+    //update by real time clock
+    fn update_time(&mut self, time: Time<u32, Seconds>) {
+        if self.time != time {
+            let delta_time = time - self.time;
+            self.time = time;
+            self.weektime =
+                WeekTime::from(Time::<u32, Seconds>::from(self.time + self.time_offset));
+
+            self.outdoor_age = self.outdoor_age + delta_time;
+            if self.outdoor_age > OUTDOOR_TEMPERATURE_TIMEOUT.s() {
+                self.outdoor_temperature = None;
+            }
+            self.demand_link
+                .update(&self.demand_link_config, delta_time);
+
+            for i in 0..ZONE_COUNT {
+                let target = self.programmed_target(i);
+                let zone = &mut self.zones[i];
+                zone.config.target_air_temperature = Some(target);
+                zone.readings.set(Role::Forward, self.temperatures[0]);
+                zone.readings.set(Role::Return, self.temperatures[1]);
+                zone.readings.set(Role::Air, self.temperatures[2 + i]);
+            }
+
+            self.pump.update(
+                &mut self.zones,
+                self.after_circulation_duration,
+                self.outdoor_temperature,
+                delta_time,
+            );
+
+            let idle = !self.pump.pump_running(&self.zones);
+            self.exerciser.update(
+                &self.exercise_config,
+                &mut self.devices,
+                &self.weektime,
+                idle,
+                delta_time,
+            );
+            for i in 0..ZONE_COUNT {
+                let valve_open = self.valve_open(i);
+                self.devices[i].record(valve_open, delta_time);
+            }
+            let pump_running = self.pump_running();
+            self.devices[PUMP_DEVICE].record(pump_running, delta_time);
+        }
+    }
+
+    /// The shared demand with the highest forward temperature of the heating zones
+    fn heat_demand(&self) -> heat_demand::Demand {
+        let forward_temperature = self
+            .zones
+            .iter()
+            .filter(|zone| zone.controller.heat_request())
+            .map(|zone| {
+                zone.controller
+                    .forward_target
+                    .unwrap_or(zone.config.max_forward_temperature.to_sixteenths())
+            })
+            .max();
+        heat_demand::Demand {
+            heat: self.pump.heat_request(),
+            cool: false,
+            forward_temperature: forward_temperature,
+        }
+    }
+
+    /// The heat demand to send if it changed or it should be refreshed
+    fn demand_payload(&mut self) -> Option<[u8; 8]> {
+        let demand = self.heat_demand();
+        self.demand_link
+            .publish(&self.demand_link_config, demand)
+            .map(|demand| demand.encode())
+    }
+
+    fn valve_open(&self, zone: usize) -> bool {
+        self.zones[zone].valve_open() || self.exerciser.valve_active(zone, PUMP_DEVICE)
+    }
+
+    fn pump_running(&self) -> bool {
+        self.pump.pump_running(&self.zones) || self.exerciser.active(PUMP_DEVICE)
+    }
+
+    fn refresh_display<D: Display>(&self, display: &mut D) -> Result<(), D::Error> {
+        display.set_position(0, 0)?;
+        print_time(display, self.weektime)?;
+
+        print_temp(display, 1, b"Elore:  ", &self.temperatures[0])?;
+        print_temp(display, 2, b"Vissza: ", &self.temperatures[1])?;
+
+        //one row per zone: number, air temperature, valve
+        for i in 0..ZONE_COUNT {
+            let zone = &self.zones[i];
+            display.set_position(0, 3 + i as u8)?;
+            display.print_char('1' as u8 + i as u8)?;
+            display.print(b": ")?;
+            if let Some(air) = zone.readings.get(Role::Air) {
+                display.print(unsafe { fmt_temp(air) })?;
+            } else {
+                display.print(b" -----")?;
+            }
+            display.print(if self.valve_open(i) {
+                b" nyit"
+            } else {
+                b" zar "
+            })?;
+        }
+
+        display.set_position(0, 7)?;
+        display.print(if self.pump_running() {
+            b"Sziv be "
+        } else {
+            b"Sziv ki "
+        })?;
+        display.print(if self.pump.heat_request() {
+            b"Hok be"
+        } else {
+            b"Hok ki"
+        })?;
+        Ok(())
+    }
+}
+
+fn drive_valve<V: Valve>(valve: &mut V, open: bool) {
+    let _ = if open { valve.open() } else { valve.close() };
+}
+
+#[entry]
+fn main() -> ! {
+    let device = stm32f1xx_hal::pac::Peripherals::take().unwrap();
+    let mut rcc = device.RCC.constrain();
+    let mut watchdog = IndependentWatchdog::new(device.IWDG);
+    watchdog.start(stm32f1xx_hal::time::U32Ext::ms(2_000u32));
+
+    let mut flash = device.FLASH.constrain();
+
+    let clocks = rcc
+        .cfgr
+        .use_hse(8.mhz())
+        .sysclk(72.mhz())
+        .hclk(72.mhz())
+        .pclk1(36.mhz())
+        .pclk2(72.mhz())
+        .freeze(&mut flash.acr);
+    watchdog.feed();
+
+    // real time clock
+    let rtc = {
+        let mut pwr = device.PWR;
+        let mut backup_domain = rcc.bkp.constrain(device.BKP, &mut rcc.apb1, &mut pwr);
+        rtc::Rtc::rtc(device.RTC, &mut backup_domain)
+    };
+    watchdog.feed();
+
+    let mut afio = device.AFIO.constrain(&mut rcc.apb2);
+
+    //configure pins:
+    let mut gpioa = device.GPIOA.split(&mut rcc.apb2);
+    let mut gpiob = device.GPIOB.split(&mut rcc.apb2);
+    let mut gpioc = device.GPIOC.split(&mut rcc.apb2);
+
+    // Disables the JTAG to free up pb3, pb4 and pa15 for normal use
+    let (_pa15, _pb3_itm_swo, pb4) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
+
+    // RGB led:
+    let mut rgb = RgbLed::new(
+        gpiob.pb13.into_open_drain_output(&mut gpiob.crh),
+        gpiob.pb14.into_open_drain_output(&mut gpiob.crh),
+        gpiob.pb15.into_open_drain_output(&mut gpiob.crh),
+    );
+
+    let mut heat_request = gpiob.pb11.into_push_pull_output(&mut gpiob.crh);
+
+    // on board led^:
+    let mut led = gpioc.pc13.into_push_pull_output(&mut gpioc.crh);
+
+    // zone valve SSRs^
+    let mut valve0 = ValveSSR::new(gpiob.pb6.into_open_drain_output(&mut gpiob.crl));
+    let mut valve1 = ValveSSR::new(gpiob.pb8.into_open_drain_output(&mut gpiob.crh));
+    let mut valve2 = ValveSSR::new(gpiob.pb9.into_open_drain_output(&mut gpiob.crh));
+    let mut valve3 = ValveSSR::new(gpiob.pb10.into_open_drain_output(&mut gpiob.crh));
+
+    // shared pump starter SSR^
+    let mut pump = PumpSSR::new(gpiob.pb7.into_open_drain_output(&mut gpiob.crl));
+
+    // setup SPI for the lcd display:
+    let sck = gpioa.pa5.into_push_pull_output(&mut gpioa.crl);
+    let mosi = gpioa.pa7.into_push_pull_output(&mut gpioa.crl);
+
+    let mut backlight = gpiob.pb12.into_open_drain_output(&mut gpiob.crh);
+    backlight.set_low().unwrap();
+
+    let cs = gpioa.pa2.into_push_pull_output(&mut gpioa.crl);
+    let mut rst = gpioa.pa1.into_push_pull_output(&mut gpioa.crl);
+
+    let core = cortex_m::Peripherals::take().unwrap();
+    let mut delay = Delay::new(core.SYST, clocks);
+    let mut display = hx1230::gpio::Hx1230Gpio::new(sck, mosi, cs, &mut rst, &mut delay).unwrap();
+    display.init().unwrap();
+    display.set_contrast(7).unwrap();
+    display.clear().unwrap();
+
+    watchdog.feed();
+
+    // setup the one wire thermometers:
+    let mut one_wire = {
+        let onewire_io = pb4.into_open_drain_output(&mut gpiob.crl);
+        OneWirePort::new(onewire_io, delay).unwrap()
+    };
+
+    let tick = Ticker::new(core.DWT, core.DCB, clocks);
+
+    let canrx = gpioa.pa11.into_floating_input(&mut gpioa.crh);
+    let cantx = gpioa.pa12.into_alternate_push_pull(&mut gpioa.crh);
+    let mut can = Can::can1(
+        device.CAN1,
+        (cantx, canrx),
+        &mut afio.mapr,
+        &mut rcc.apb1,
+        device.USB,
+    );
+
+    let mut model = Model::new();
+    let reset_flags = unsafe { &(*stm32f1xx_hal::pac::RCC::ptr()).csr };
+    let reset_reason = ResetReason::from_csr(reset_flags.read().bits());
+    reset_flags.modify(|_, w| w.rmvf().set_bit());
+    model.restore_retained(
+        Retained::restore(unsafe { &RETAINED.as_ptr().read_volatile() }),
+        reset_reason,
+    );
+
+    can.configure(&Configuration {
+        time_triggered_communication_mode: false,
+        automatic_bus_off_management: true,
+        automatic_wake_up_mode: true,
+        no_automatic_retransmission: false,
+        receive_fifo_locked_mode: false,
+        transmit_fifo_priority: false,
+        silent_mode: false,
+        loopback_mode: false,
+        synchronisation_jump_width: 1,
+        bit_segment_1: 3,
+        bit_segment_2: 2,
+        time_quantum_length: 6,
+    });
+
+    can.configure_filter_bank(
+        0,
+        &FilterBankConfiguration {
+            mode: FilterMode::List,
+            info: FilterInfo::Whole(FilterData {
+                id: ID_TEMPERATURE,
+                mask_or_id2: ID_TEMPERATURE,
+            }),
+            fifo_assignment: 0,
+            active: true,
+        },
+    );
+
+    can.configure_filter_bank(
+        1,
+        &FilterBankConfiguration {
+            mode: FilterMode::List,
+            info: FilterInfo::Whole(FilterData {
+                id: ID_SUPPLY,
+                mask_or_id2: ID_SUPPLY,
+            }),
+            fifo_assignment: 0,
+            active: true,
+        },
+    );
+
+    let (tx, rx) = can.split();
+    let (mut tx0, mut _tx1, mut _tx2) = tx.split();
+    let (mut rx0, mut _rx1) = rx.split();
+
+    watchdog.feed();
+
+    //store the addresses of temp sensors, start measurement on each:
+    let mut roms = [[0u8; 8]; MAX_THERMOMETER_COUNT];
+    let mut count = 0;
+
+    let mut it = RomIterator::new(0);
+
+    loop {
+        watchdog.feed();
+
+        match one_wire.iterate_next(true, &mut it) {
+            Ok(None) => {
+                break;
+            }
+
+            Ok(Some(rom)) => {
+                if let Some(_device_type) = detect_18x20_devices(rom[0]) {
+                    roms[count] = *rom;
+                    count = count + 1;
+                    let _ = one_wire.start_temperature_measurement(&rom);
+                    if count >= MAX_THERMOMETER_COUNT {
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            Err(_e) => {
+                rgb.color(Colors::White).unwrap();
+                break;
+            }
+        }
+    }
+
+    let roms = roms;
+    let count = count;
+
+    let mut last_time = tick.now();
+
+    loop {
+        watchdog.feed();
+
+        let now = tick.now();
+
+        //receive the outdoor temperature and the supply status
+        if let Ok((filter_match_index, _time, frame)) = rx0.read() {
+            match filter_match_index {
+                0 | 1 => model.can_temperature_received(frame.data().data_as_u64()),
+                2 | 3 => model
+                    .demand_link
+                    .supply_received(frame.data().data_as_u64()),
+                _ => {}
+            }
+        }
+
+        let delta = now - last_time;
+
+        // temperature conversion time of the sensors is a lower limit
+        if u32::from(delta) < tick.frequency {
+            continue;
+        }
+
+        led.toggle().unwrap();
+
+        let delta_sec = u32::from(delta) / tick.frequency;
+        last_time = last_time + Duration::<u32, SysTicks>::from(delta_sec * tick.frequency);
+
+        //read sensors and restart temperature measurement
+        for i in 0..count {
+            model.temperatures[i] = match one_wire.read_temperature_measurement_result(&roms[i]) {
+                Ok(temperature) => Some(temperature),
+                Err(_code) => None,
+            };
+            let _ = one_wire.start_temperature_measurement(&roms[i]);
+        }
+
+        model.update_time(Time::<u32, Seconds>::from_sec(rtc.current_time()));
+        unsafe { RETAINED.as_mut_ptr().write_volatile(model.save_retained()) };
+
+        // drive outputs, send messages:
+        if let Some(payload) = model.demand_payload() {
+            let _ = tx0.request_transmit(&Frame::new(
+                heat_demand_id(HEAT_DEMAND_ZONE),
+                Payload::new(&payload),
+            ));
+        }
+
+        drive_valve(&mut valve0, model.valve_open(0));
+        drive_valve(&mut valve1, model.valve_open(1));
+        drive_valve(&mut valve2, model.valve_open(2));
+        drive_valve(&mut valve3, model.valve_open(3));
+
+        if model.pump_running() {
+            pump.start().unwrap();
+        } else {
+            pump.stop().unwrap();
+        }
+
+        //the heat request signal is the fallback while the coordinator is missing
+        if model.pump.heat_request() && !model.demand_link.coordinator_present() {
+            heat_request.set_high().unwrap();
+        } else {
+            heat_request.set_low().unwrap();
+        }
+        if model.pump.heat_request() {
+            rgb.color(Colors::Red).unwrap();
+        } else {
+            rgb.color(if model.pump.after_circulation() {
+                Colors::Yellow
+            } else {
+                Colors::Green
+            })
+            .unwrap();
+        }
+
+        model.refresh_display(&mut display).unwrap();
+    }
+}
+
+#[exception]
+fn HardFault(_ef: &ExceptionFrame) -> ! {
+    loop {}
+}
+
+#[exception]
+fn DefaultHandler(_irqn: i16) {}
@@ -83,7 +83,7 @@ impl Model {
         self.temperatures[index] = temperature;
     }
 
-    fn refresh_display<D: lcd_hal::Display, RGB: Rgb>(
+    fn refresh_display<D: Display, RGB: Rgb>(
         &self,
         display: &mut D,
         rgb: &mut RGB,
@@ -94,11 +94,9 @@ impl Model {
         display.print(b"Cel  ")?;
         display.print(unsafe { fmt_temp(self.target_temperature) })?;
 
-        if let Some(temp) = self.temperatures[0 as usize] {
-            display.set_position(0, 2)?;
-            display.print(b"Temp ")?;
-            display.print(unsafe { fmt_temp(temp) })?;
+        print_big_temp(display, 3, &self.temperatures[0 as usize], 3)?;
 
+        if let Some(temp) = self.temperatures[0 as usize] {
             let _ = rgb.color(if temp > self.target_temperature {
                 Colors::Red
             } else if temp < self.target_temperature {
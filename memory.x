@@ -1,6 +1,7 @@
 /* Linker script for the STM32F103CBT6 */
+/* The last 4K (4 pages from 0x0801F000) is reserved for the config store */
 MEMORY
 {
-  FLASH : ORIGIN = 0x08000000, LENGTH = 128K
+  FLASH : ORIGIN = 0x08000000, LENGTH = 124K
   RAM : ORIGIN = 0x20000000, LENGTH = 20K
 }
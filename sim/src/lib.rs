@@ -0,0 +1,510 @@
+//! Lumped thermal model of a floor heated room for the host side testing of the floor heating control.
+//! The water of the loop, the screed and the room air are single nodes connected to each other
+//! and to the outdoor by thermal conductances. The controller is run by a fake clock with the given
+//! weather for days of simulated time, the trace is written as CSV and the limits are checked in every step.
+//! The model temperatures are in degree Celsius, the heat capacities in J/K, the conductances in W/K.
+//! The scenarios are in the tests, the parent crate builds for the MCU by default, so the host target should be given:
+//!
+//!   cargo test --release --target x86_64-unknown-linux-gnu
+//!
+
+use core::fmt::Write;
+use core::ops::{Add, Sub};
+use room_pill::floor_heating::{Config, Controller, Mode, State};
+use room_pill::sensors::{Readings, Role, ROLES, ROLE_COUNT};
+use room_pill::temperature::FixedPoint;
+use room_pill::timing::{Duration, Seconds, Time};
+
+const SECONDS_PER_DAY: u32 = 24 * 3600;
+const MAX_MODEL_STEP: u32 = 10; //seconds, for the stability of the integration
+
+pub const TRACE_HEADER: &str = "time,outdoor,air,floor,water,forward,return,state,valve,pump,heat";
+
+fn sixteenths(celsius: f32) -> i32 {
+    if celsius < 0.0 {
+        (celsius * 16.0 - 0.5) as i32
+    } else {
+        (celsius * 16.0 + 0.5) as i32
+    }
+}
+
+fn celsius<Temperature: FixedPoint>(temperature: &Temperature) -> f32 {
+    temperature.to_sixteenths() as f32 / 16.0
+}
+
+pub struct Parameters {
+    pub water_capacity: f32,           //of the loop
+    pub screed_capacity: f32,          //with the floor covering
+    pub air_capacity: f32,             //with the furniture and the inner walls
+    pub supply_conductance: f32,       //flow from the heat source * specific heat of the water
+    pub water_screed_conductance: f32, //with circulation
+    pub standstill_conductance: f32,   //water to screed without circulation
+    pub screed_air_conductance: f32,
+    pub air_outdoor_conductance: f32,    //losses of the room
+    pub screed_outdoor_conductance: f32, //through the insulation under the screed
+    pub supply_temperature: f32,         //of the heat source while the heat is requested
+}
+
+/// The outdoor temperature by the clock (seconds from monday 00:00)
+#[derive(Clone, Copy)]
+pub enum Weather<'a> {
+    Constant(f32),
+    Daily {
+        min: f32,
+        max: f32,
+        coldest_hour: u8, //the max is 12 hours later, linear between them
+    },
+    Profile(&'a [(u32, f32)]), //hour from the start of the clock, temperature; linear between the points
+}
+
+impl<'a> Weather<'a> {
+    pub fn outdoor(&self, time: u32) -> f32 {
+        match self {
+            Weather::Constant(temperature) => *temperature,
+            Weather::Daily {
+                min,
+                max,
+                coldest_hour,
+            } => {
+                let coldest = (*coldest_hour as u32 * 3600) % SECONDS_PER_DAY;
+                let since_coldest =
+                    (time % SECONDS_PER_DAY + SECONDS_PER_DAY - coldest) % SECONDS_PER_DAY;
+                let half_day = SECONDS_PER_DAY / 2;
+                let distance = if since_coldest < half_day {
+                    since_coldest
+                } else {
+                    SECONDS_PER_DAY - since_coldest
+                };
+                min + (max - min) * distance as f32 / half_day as f32
+            }
+            Weather::Profile(points) => {
+                let hour = time as f32 / 3600.0;
+                let mut previous: Option<(f32, f32)> = None;
+                for (point_hour, temperature) in points.iter() {
+                    let point_hour = *point_hour as f32;
+                    if hour < point_hour {
+                        return match previous {
+                            Some((previous_hour, previous_temperature)) => {
+                                previous_temperature
+                                    + (temperature - previous_temperature) * (hour - previous_hour)
+                                        / (point_hour - previous_hour)
+                            }
+                            None => *temperature,
+                        };
+                    }
+                    previous = Some((point_hour, *temperature));
+                }
+                previous.map(|(_, temperature)| temperature).unwrap_or(0.0)
+            }
+        }
+    }
+}
+
+/// The temperatures of the nodes
+pub struct Plant {
+    pub water: f32,
+    pub screed: f32,
+    pub air: f32,
+    pub outdoor: f32,
+    feeding: bool, //the heat source feeds the loop
+}
+
+impl Plant {
+    pub fn new(initial: f32, outdoor: f32) -> Self {
+        Plant {
+            water: initial,
+            screed: initial,
+            air: initial,
+            outdoor: outdoor,
+            feeding: false,
+        }
+    }
+
+    /// The supply is measured while the heat source feeds the loop, the loop water otherwise
+    pub fn forward(&self, parameters: &Parameters) -> f32 {
+        if self.feeding {
+            parameters.supply_temperature
+        } else {
+            self.water
+        }
+    }
+
+    pub fn return_temperature(&self) -> f32 {
+        self.water
+    }
+
+    /// The readings of the connected sensors (by role index)
+    pub fn readings<Temperature: Copy + FixedPoint>(
+        &self,
+        parameters: &Parameters,
+        connected: &[bool; ROLE_COUNT],
+    ) -> Readings<Temperature> {
+        let mut readings = Readings::new();
+        for role in ROLES.iter() {
+            let value = match role {
+                Role::Forward => self.forward(parameters),
+                Role::Return => self.return_temperature(),
+                Role::Floor => self.screed,
+                Role::Air => self.air,
+                Role::Outdoor => self.outdoor,
+            };
+            if connected[role.index()] {
+                readings.set(*role, Some(Temperature::from_sixteenths(sixteenths(value))));
+            }
+        }
+        readings
+    }
+
+    /// Integrates the heat flows with the given outputs
+    pub fn step(
+        &mut self,
+        parameters: &Parameters,
+        valve_open: bool,
+        pump_running: bool,
+        heat_request: bool,
+        seconds: u32,
+    ) {
+        self.feeding = valve_open && pump_running && heat_request;
+        let water_screed = if pump_running {
+            parameters.water_screed_conductance
+        } else {
+            parameters.standstill_conductance
+        };
+
+        let mut left = seconds;
+        while left > 0 {
+            let dt = if left > MAX_MODEL_STEP {
+                MAX_MODEL_STEP
+            } else {
+                left
+            };
+            left -= dt;
+
+            let supply = if self.feeding {
+                parameters.supply_conductance * (parameters.supply_temperature - self.water)
+            } else {
+                0.0
+            };
+            let to_screed = water_screed * (self.water - self.screed);
+            let to_air = parameters.screed_air_conductance * (self.screed - self.air);
+            let screed_loss = parameters.screed_outdoor_conductance * (self.screed - self.outdoor);
+            let air_loss = parameters.air_outdoor_conductance * (self.air - self.outdoor);
+
+            let dt = dt as f32;
+            self.water += (supply - to_screed) / parameters.water_capacity * dt;
+            self.screed += (to_screed - to_air - screed_loss) / parameters.screed_capacity * dt;
+            self.air += (to_air - air_loss) / parameters.air_capacity * dt;
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Violation {
+    AirOvershoot, //above the target + histeresis + the allowed overshoot (in the Air mode)
+    ForwardOverheat, //heat requested at or above the max forward temperature
+    FloorOverheat, //heat requested at or above the max floor temperature
+    HeatWithoutFlow, //heat requested with closed valve or stopped pump
+    FreezeProtectionLate, //below the freeze limit longer than the check interval and duration
+    PreCirculationShort, //the heating started before the pre circulation duration
+    AfterCirculationShort, //the circulation stopped before the after circulation duration
+}
+
+pub const VIOLATION_COUNT: usize = 7;
+pub const VIOLATIONS: [Violation; VIOLATION_COUNT] = [
+    Violation::AirOvershoot,
+    Violation::ForwardOverheat,
+    Violation::FloorOverheat,
+    Violation::HeatWithoutFlow,
+    Violation::FreezeProtectionLate,
+    Violation::PreCirculationShort,
+    Violation::AfterCirculationShort,
+];
+
+impl Violation {
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Violation::AirOvershoot => "air overshoot",
+            Violation::ForwardOverheat => "forward overheat",
+            Violation::FloorOverheat => "floor overheat",
+            Violation::HeatWithoutFlow => "heat without flow",
+            Violation::FreezeProtectionLate => "freeze protection late",
+            Violation::PreCirculationShort => "pre circulation short",
+            Violation::AfterCirculationShort => "after circulation short",
+        }
+    }
+}
+
+pub struct Report {
+    pub violations: [u32; VIOLATION_COUNT], //the number of the violating steps by index
+    pub first: Option<(Violation, u32)>,    //the first violation and its clock time
+    pub heating_cycles: u32,                //heating started by demand
+    pub freeze_triggers: u32,               //defrost started (in the error state too)
+    pub errors: u32,                        //the error state entered
+    pub error_seconds: u32,                 //spent in the error state
+    pub heat_request_seconds: u32,
+    pub min_air: f32,
+    pub max_air: f32,
+    pub max_floor: f32,
+    pub max_forward: f32,
+}
+
+impl Report {
+    fn new(initial: f32) -> Self {
+        Report {
+            violations: [0; VIOLATION_COUNT],
+            first: None,
+            heating_cycles: 0,
+            freeze_triggers: 0,
+            errors: 0,
+            error_seconds: 0,
+            heat_request_seconds: 0,
+            min_air: initial,
+            max_air: initial,
+            max_floor: initial,
+            max_forward: initial,
+        }
+    }
+
+    pub fn count(&self, violation: Violation) -> u32 {
+        self.violations[violation.index()]
+    }
+
+    pub fn passed(&self) -> bool {
+        self.first.is_none()
+    }
+
+    fn record(&mut self, violation: Violation, time: u32) {
+        self.violations[violation.index()] += 1;
+        if self.first.is_none() {
+            self.first = Some((violation, time));
+        }
+    }
+}
+
+pub struct Simulation<'a, Temperature> {
+    pub parameters: Parameters,
+    pub config: Config<Temperature, Duration<u32, Seconds>>,
+    pub weather: Weather<'a>,
+    pub connected: [bool; ROLE_COUNT], //the sensors by role index
+    pub air_overshoot: f32,            //allowed by the inertia of the screed
+    pub plant: Plant,
+    pub controller: Controller<Duration<u32, Seconds>>,
+    pub time: Time<u32, Seconds>, //the fake clock, seconds from monday 00:00
+    pub report: Report,
+    state_time: u32,   //spent in the current state before the last update
+    below_freeze: u32, //since the return (or air) is below the freeze limit without defrost
+    defrosting: bool,  //at the last update
+}
+
+impl<'a, Temperature> Simulation<'a, Temperature>
+where
+    Temperature: Copy
+        + PartialOrd
+        + FixedPoint
+        + Add<Temperature, Output = Temperature>
+        + Sub<Temperature, Output = Temperature>,
+{
+    /// All the nodes start at the initial temperature, the controller in standby
+    pub fn new(
+        parameters: Parameters,
+        config: Config<Temperature, Duration<u32, Seconds>>,
+        weather: Weather<'a>,
+        start: Time<u32, Seconds>,
+        initial: f32,
+    ) -> Self {
+        Simulation {
+            parameters: parameters,
+            config: config,
+            weather: weather,
+            connected: [true; ROLE_COUNT],
+            air_overshoot: 1.0,
+            plant: Plant::new(initial, weather.outdoor(u32::from(start))),
+            controller: Controller::new(State::Standby(Duration::default())),
+            time: start,
+            report: Report::new(initial),
+            state_time: 0,
+            below_freeze: 0,
+            defrosting: false,
+        }
+    }
+
+    /// Updates the controller by the readings, checks the limits, then advances the model and the clock
+    pub fn step(&mut self, seconds: u32) {
+        let now = u32::from(self.time);
+        self.plant.outdoor = self.weather.outdoor(now);
+        let readings = self.plant.readings(&self.parameters, &self.connected);
+
+        let previous = self.controller.state.code();
+        let delta = Duration::<u32, Seconds>::from(seconds);
+        self.controller.update(&self.config, &readings, delta);
+
+        self.check(&readings, previous, seconds, now);
+
+        self.plant.step(
+            &self.parameters,
+            self.controller.valve_open(),
+            self.controller.pump_running(),
+            self.controller.heat_request(),
+            seconds,
+        );
+        self.time = self.time + delta;
+    }
+
+    /// Runs the simulation for the duration, a trace row is written after every trace period
+    pub fn run<W: Write>(
+        &mut self,
+        duration: u32,
+        step: u32,
+        trace_period: u32,
+        trace: &mut W,
+    ) -> core::fmt::Result {
+        writeln!(trace, "{}", TRACE_HEADER)?;
+        let mut elapsed = 0;
+        let mut since_trace = 0;
+        while elapsed < duration {
+            self.step(step);
+            elapsed += step;
+            since_trace += step;
+            if since_trace >= trace_period {
+                since_trace = 0;
+                self.write_row(trace)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_row<W: Write>(&self, trace: &mut W) -> core::fmt::Result {
+        writeln!(
+            trace,
+            "{},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{},{},{},{}",
+            u32::from(self.time),
+            self.plant.outdoor,
+            self.plant.air,
+            self.plant.screed,
+            self.plant.water,
+            self.plant.forward(&self.parameters),
+            self.plant.return_temperature(),
+            self.controller.state.code(),
+            self.controller.valve_open() as u8,
+            self.controller.pump_running() as u8,
+            self.controller.heat_request() as u8,
+        )
+    }
+
+    fn check(&mut self, readings: &Readings<Temperature>, previous: u8, seconds: u32, now: u32) {
+        //the limits of the forward override (e.g. the screed program) are checked while it is set
+        let overridden;
+        let config = match self.controller.forward_override {
+            Some(forward_override) => {
+                overridden = forward_override.apply(&self.config);
+                &overridden
+            }
+            None => &self.config,
+        };
+        let heat_request = self.controller.heat_request();
+
+        let room_controlled =
+            !self.controller.suspended && self.controller.forward_override.is_none();
+        if let (Mode::Air, Some(target), true) =
+            (config.mode, config.target_air_temperature, room_controlled)
+        {
+            let limit =
+                celsius(&target) + celsius(&config.temperature_histeresis) + self.air_overshoot;
+            if self.plant.air > limit {
+                self.report.record(Violation::AirOvershoot, now);
+            }
+        }
+
+        if heat_request {
+            self.report.heat_request_seconds += seconds;
+            if let Some(forward) = readings.get(Role::Forward) {
+                if forward >= config.max_forward_temperature {
+                    self.report.record(Violation::ForwardOverheat, now);
+                }
+            }
+            if let Some(floor) = readings.get(Role::Floor) {
+                if floor >= config.max_floor_temperature {
+                    self.report.record(Violation::FloorOverheat, now);
+                }
+            }
+            if !(self.controller.valve_open() && self.controller.pump_running()) {
+                self.report.record(Violation::HeatWithoutFlow, now);
+            }
+        }
+
+        //the freeze protection should start heating within the check interval and duration
+        let protected = self.controller.state.defrost() || heat_request;
+        match readings.get(Role::Return).or(readings.get(Role::Air)) {
+            Some(temperature)
+                if temperature < config.freeze_protection.min_temperature && !protected =>
+            {
+                let allowed = u32::from(config.freeze_protection.check_interval)
+                    + u32::from(config.freeze_protection.check_duration)
+                    + 2 * seconds;
+                if self.below_freeze <= allowed && self.below_freeze + seconds > allowed {
+                    self.report.record(Violation::FreezeProtectionLate, now);
+                }
+                self.below_freeze += seconds;
+            }
+            _ => self.below_freeze = 0,
+        }
+
+        //the durations of the states are measured by the fake clock (see State::code)
+        let current = self.controller.state.code();
+        let spent = self.state_time + seconds;
+        if current == previous {
+            self.state_time = spent;
+        } else {
+            match (previous, current) {
+                (1, 2) => {
+                    if spent < u32::from(config.pre_circulation_duration) {
+                        self.report.record(Violation::PreCirculationShort, now);
+                    }
+                    if !self.controller.state.defrost() {
+                        self.report.heating_cycles += 1;
+                    }
+                }
+                (3, 4) => {
+                    if spent < u32::from(config.after_circulation_duration) {
+                        self.report.record(Violation::AfterCirculationShort, now);
+                    }
+                }
+                _ => {}
+            }
+            if current == 6 {
+                self.report.errors += 1;
+            }
+            self.state_time = 0;
+        }
+        if current == 6 {
+            self.report.error_seconds += seconds;
+        }
+
+        //the error state keeps the freeze protection, its defrost is not a separate state
+        let defrosting = self.controller.state.defrost();
+        if defrosting && !self.defrosting {
+            self.report.freeze_triggers += 1;
+        }
+        self.defrosting = defrosting;
+
+        let report = &mut self.report;
+        let air = self.plant.air;
+        if air < report.min_air {
+            report.min_air = air;
+        }
+        if air > report.max_air {
+            report.max_air = air;
+        }
+        if self.plant.screed > report.max_floor {
+            report.max_floor = self.plant.screed;
+        }
+        let forward = self.plant.forward(&self.parameters);
+        if forward > report.max_forward {
+            report.max_forward = forward;
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! The floor heating scenarios, days of simulated time each (see floor_simulation)
+//!
+//! The limits are checked in every step, a scenario fails on any violation or unmet expectation.
+//! The CSV traces are written into the directory given by the SIMULATION_TRACES environment variable.
+//! The parent crate builds for the MCU by default, so the host target should be given:
+//!
+//!   cargo test --release --target x86_64-unknown-linux-gnu
+//!
+use floor_simulation::{Parameters, Report, Simulation, Weather, TRACE_HEADER, VIOLATIONS};
+use onewire::temperature::Temperature;
+use room_pill::{
+    floor_heating::{Config, Control, ErrorReason, Mode, Policy, State},
+    screed::{self, Program},
+    sensors::Role,
+    temperature::FixedPoint,
+    timing::{Duration, Seconds, Time},
+};
+use std::{env, fmt::Write, fs, path::PathBuf};
+
+const STEP: u32 = 10; //seconds
+const TRACE_PERIOD: u32 = 5 * 60;
+const HOUR: u32 = 3600;
+const DAY: u32 = 24 * HOUR;
+
+//a cold spell: hour from the start, outdoor temperature
+const COLD_SPELL: [(u32, f32); 5] = [(0, 5.0), (24, -5.0), (48, -20.0), (96, -18.0), (144, 0.0)];
+
+const WINTER: Weather<'static> = Weather::Daily {
+    min: -8.0,
+    max: 2.0,
+    coldest_hour: 5,
+};
+
+/// A 20 m2 room with 5 cm screed, about 1.2 kW loss at -10 degree outdoor
+fn room() -> Parameters {
+    Parameters {
+        water_capacity: 63e3,      //15 liter
+        screed_capacity: 2.2e6,    //1 m3 screed and the tiles
+        air_capacity: 3e5,         //the air and the furniture
+        supply_conductance: 210.0, //3 liter/min
+        water_screed_conductance: 400.0,
+        standstill_conductance: 60.0,
+        screed_air_conductance: 216.0, //10.8 W/m2K
+        air_outdoor_conductance: 40.0,
+        screed_outdoor_conductance: 5.0,
+        supply_temperature: 38.0,
+    }
+}
+
+/// The defaults of the floor unit with the given control, mode and target
+fn config(
+    control: Control,
+    mode: Mode,
+    target_air_temperature: Option<Temperature>,
+) -> Config<Temperature, Duration<u32, Seconds>> {
+    Config {
+        control: control,
+        mode: mode,
+        target_air_temperature: target_air_temperature,
+        ..Config::with_defaults()
+    }
+}
+
+fn target() -> Option<Temperature> {
+    Some(Temperature::from_celsius(21, 0))
+}
+
+fn monday() -> Time<u32, Seconds> {
+    Time::<u32, Seconds>::from_sec(0)
+}
+
+fn summary(name: &str, report: &Report) -> String {
+    let mut text = String::new();
+    write!(
+        text,
+        "{}: air {:.1}..{:.1}  floor max {:.1}  forward max {:.1}  heating cycles {}  freeze triggers {}  errors {} ({} h)  heat {} h",
+        name,
+        report.min_air,
+        report.max_air,
+        report.max_floor,
+        report.max_forward,
+        report.heating_cycles,
+        report.freeze_triggers,
+        report.errors,
+        report.error_seconds / HOUR,
+        report.heat_request_seconds / HOUR,
+    )
+    .unwrap();
+    for violation in VIOLATIONS.iter() {
+        if report.count(*violation) > 0 {
+            write!(
+                text,
+                "\n    {}: {} steps",
+                violation.name(),
+                report.count(*violation)
+            )
+            .unwrap();
+        }
+    }
+    if let Some((violation, time)) = report.first {
+        write!(
+            text,
+            "\n    first: {} at day {} {:02}:{:02}",
+            violation.name(),
+            time / DAY,
+            time % DAY / HOUR,
+            time % HOUR / 60
+        )
+        .unwrap();
+    }
+    text
+}
+
+/// Steps the simulation for the duration, the check is called after every step
+fn run<F>(simulation: &mut Simulation<Temperature>, duration: u32, trace: &mut String, mut check: F)
+where
+    F: FnMut(&Simulation<Temperature>),
+{
+    let mut elapsed = 0;
+    while elapsed < duration {
+        simulation.step(STEP);
+        check(simulation);
+        elapsed += STEP;
+        if elapsed % TRACE_PERIOD == 0 {
+            simulation.write_row(trace).unwrap();
+        }
+    }
+}
+
+/// Writes the trace if requested, fails on any violation or unmet expectation
+fn finish(
+    name: &str,
+    simulation: &Simulation<Temperature>,
+    trace: &str,
+    expectation: Result<(), &str>,
+) {
+    if let Some(directory) = env::var_os("SIMULATION_TRACES") {
+        let directory = PathBuf::from(directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join(format!("{}.csv", name)), trace).unwrap();
+    }
+    let report = &simulation.report;
+    assert!(report.passed(), "{}", summary(name, report));
+    if let Err(expectation) = expectation {
+        panic!("{}\n    {}", summary(name, report), expectation);
+    }
+}
+
+fn scenario<F>(name: &str, mut simulation: Simulation<Temperature>, days: u32, check: F)
+where
+    F: Fn(&Report) -> Result<(), &'static str>,
+{
+    let mut trace = String::from(TRACE_HEADER) + "\n";
+    run(&mut simulation, days * DAY, &mut trace, |_| {});
+    let expectation = check(&simulation.report);
+    finish(name, &simulation, &trace, expectation);
+}
+
+fn heated(report: &Report) -> Result<(), &'static str> {
+    match (report.heating_cycles, report.freeze_triggers, report.errors) {
+        (0, _, _) => Err("no heating"),
+        (_, 0, 0) => Ok(()),
+        (_, _, 0) => Err("unexpected freeze protection"),
+        _ => Err("unexpected error"),
+    }
+}
+
+#[test]
+fn histeresis() {
+    let config = config(Control::Histeresis, Mode::Air, target());
+    scenario(
+        "histeresis",
+        Simulation::new(room(), config, WINTER, monday(), 18.0),
+        7,
+        heated,
+    );
+}
+
+#[test]
+fn pid() {
+    let config = config(Control::Pid, Mode::Air, target());
+    scenario(
+        "pid",
+        Simulation::new(room(), config, WINTER, monday(), 18.0),
+        7,
+        heated,
+    );
+}
+
+#[test]
+fn floor_mode() {
+    //without air sensor
+    let config = config(Control::Histeresis, Mode::Floor, target());
+    let mut simulation = Simulation::new(room(), config, WINTER, monday(), 18.0);
+    simulation.connected[Role::Air.index()] = false;
+    scenario("floor_mode", simulation, 7, heated);
+}
+
+#[test]
+fn heating_curve() {
+    //the outdoor sensor feeds the heating curve
+    let mut config = config(Control::Histeresis, Mode::Air, target());
+    config.heating_curve.enabled = true;
+    let weather = Weather::Profile(&COLD_SPELL);
+    scenario(
+        "heating_curve",
+        Simulation::new(room(), config, weather, monday(), 18.0),
+        7,
+        heated,
+    );
+}
+
+#[test]
+fn freeze_protection() {
+    //unoccupied house: the heating is off, only the freeze protection may heat
+    let config = config(Control::Histeresis, Mode::Air, None);
+    scenario(
+        "freeze_protection",
+        Simulation::new(room(), config, Weather::Constant(-15.0), monday(), 7.0),
+        4,
+        |report| match (report.freeze_triggers, report.heating_cycles) {
+            (0, _) => Err("freeze protection not triggered"),
+            (_, 0) => Ok(()),
+            _ => Err("heating without target"),
+        },
+    );
+}
+
+#[test]
+fn air_and_return_sensor_loss() {
+    //both sensors of the air control are lost for half a day, then reconnected
+    let config = config(Control::Histeresis, Mode::Air, target());
+    let mut simulation = Simulation::new(room(), config, WINTER, monday(), 18.0);
+    let mut trace = String::from(TRACE_HEADER) + "\n";
+    run(&mut simulation, DAY, &mut trace, |_| {});
+    let heating_cycles = simulation.report.heating_cycles;
+
+    simulation.connected[Role::Air.index()] = false;
+    simulation.connected[Role::Return.index()] = false;
+    let mut heated_blind = false;
+    run(&mut simulation, 12 * HOUR, &mut trace, |simulation| {
+        heated_blind |= simulation.controller.heat_request();
+    });
+    let error = simulation.controller.state.error();
+
+    simulation.connected[Role::Air.index()] = true;
+    simulation.connected[Role::Return.index()] = true;
+    run(&mut simulation, 2 * DAY, &mut trace, |_| {});
+
+    let report = &simulation.report;
+    let expectation = if heated_blind {
+        Err("heating without air and return sensor")
+    } else if error.is_some() && error != Some(ErrorReason::MissingAirSensor) {
+        Err("unexpected error reason")
+    } else if simulation.controller.state.error().is_some() {
+        Err("the retry policy did not clear the error")
+    } else if report.heating_cycles <= heating_cycles {
+        Err("no heating after the reconnect")
+    } else {
+        Ok(())
+    };
+    finish("sensor_loss", &simulation, &trace, expectation);
+}
+
+#[test]
+fn forward_and_floor_sensor_loss() {
+    //the air control goes on without the limit sensors
+    let config = config(Control::Histeresis, Mode::Air, target());
+    let mut simulation = Simulation::new(room(), config, WINTER, monday(), 18.0);
+    simulation.connected[Role::Forward.index()] = false;
+    simulation.connected[Role::Floor.index()] = false;
+    scenario("limit_sensor_loss", simulation, 3, heated);
+}
+
+/// The heat source feeds over the overheat limit (max forward + margin)
+fn overheating_room() -> Parameters {
+    Parameters {
+        supply_temperature: 50.0,
+        ..room()
+    }
+}
+
+/// No heat request and no circulation against the closed valve in the error state (except the defrost)
+fn check_error_outputs(simulation: &Simulation<Temperature>, wrong: &mut bool) {
+    let state = &simulation.controller.state;
+    if state.error().is_some() && !state.defrost() {
+        *wrong |= simulation.controller.heat_request() || simulation.controller.pump_running();
+    }
+}
+
+#[test]
+fn overheat_latching() {
+    let config = config(Control::Histeresis, Mode::Air, target());
+    let mut simulation = Simulation::new(overheating_room(), config, WINTER, monday(), 18.0);
+    let mut trace = String::from(TRACE_HEADER) + "\n";
+    let mut wrong_outputs = false;
+    run(&mut simulation, 2 * DAY, &mut trace, |simulation| {
+        check_error_outputs(simulation, &mut wrong_outputs);
+    });
+
+    let report = &simulation.report;
+    let expectation = if report.errors != 1 {
+        Err("the overheat error should be latched at the first time")
+    } else if simulation.controller.state.error() != Some(ErrorReason::Overheat) {
+        Err("the latched overheat error is cleared")
+    } else if wrong_outputs {
+        Err("heat request or pump in the error state")
+    } else {
+        Ok(())
+    };
+    finish("overheat_latching", &simulation, &trace, expectation);
+}
+
+#[test]
+fn overheat_retry() {
+    let mut config = config(Control::Histeresis, Mode::Air, target());
+    config.error.policies[ErrorReason::Overheat.index()] = Policy::Retry;
+    let mut simulation = Simulation::new(overheating_room(), config, WINTER, monday(), 18.0);
+    let mut trace = String::from(TRACE_HEADER) + "\n";
+    let mut wrong_outputs = false;
+    run(&mut simulation, 2 * DAY, &mut trace, |simulation| {
+        check_error_outputs(simulation, &mut wrong_outputs);
+    });
+
+    let report = &simulation.report;
+    let expectation = if report.errors < 2 {
+        Err("the overheat error is not cleared after the cool down")
+    } else if wrong_outputs {
+        Err("heat request or pump in the error state")
+    } else {
+        Ok(())
+    };
+    finish("overheat_retry", &simulation, &trace, expectation);
+}
+
+#[test]
+fn latched_error_keeps_the_freeze_protection() {
+    //unoccupied cold house with a latched circulation failure (e.g. restored after a reset)
+    let config = config(Control::Histeresis, Mode::Air, target());
+    let mut simulation = Simulation::new(room(), config, Weather::Constant(-15.0), monday(), 7.0);
+    simulation.controller.state =
+        State::Error((ErrorReason::CirculationFailure, Duration::default(), false));
+    let mut trace = String::from(TRACE_HEADER) + "\n";
+    let mut left_error = false;
+    run(&mut simulation, 4 * DAY, &mut trace, |simulation| {
+        left_error |= simulation.controller.state.error().is_none();
+    });
+
+    let report = &simulation.report;
+    let expectation = match (report.freeze_triggers, report.heating_cycles, left_error) {
+        (0, _, _) => Err("freeze protection not triggered"),
+        (_, 0, false) => Ok(()),
+        (_, _, false) => Err("comfort heating in the error state"),
+        (_, _, true) => Err("the latched error is cleared"),
+    };
+    finish("latch_freeze", &simulation, &trace, expectation);
+}
+
+#[test]
+fn sensor_error_keeps_the_freeze_protection() {
+    //unoccupied cold house, the return and air sensors are lost, then the return is reconnected
+    let config = config(Control::Histeresis, Mode::Air, None);
+    let mut simulation = Simulation::new(room(), config, Weather::Constant(-15.0), monday(), 4.0);
+    simulation.connected[Role::Air.index()] = false;
+    simulation.connected[Role::Return.index()] = false;
+    let mut trace = String::from(TRACE_HEADER) + "\n";
+    run(&mut simulation, 6 * HOUR, &mut trace, |_| {});
+    let error = simulation.controller.state.error();
+
+    //the air sensor stays broken, the error is kept while defrosting
+    simulation.connected[Role::Return.index()] = true;
+    let mut defrosted_in_error = false;
+    let mut wrong_outputs = false;
+    run(&mut simulation, DAY, &mut trace, |simulation| {
+        let controller = &simulation.controller;
+        if controller.state.error().is_some() && controller.state.defrost() {
+            defrosted_in_error = true;
+            wrong_outputs |= !(controller.valve_open()
+                && controller.heat_request()
+                && controller.pump_running());
+        }
+    });
+
+    let expectation = if error != Some(ErrorReason::MissingReturnSensor) {
+        Err("the lost sensors did not raise the error")
+    } else if !defrosted_in_error {
+        Err("no freeze protection in the error state")
+    } else if wrong_outputs {
+        Err("valve closed or no heat request while defrosting in the error state")
+    } else {
+        Ok(())
+    };
+    finish("sensor_error_freeze", &simulation, &trace, expectation);
+}
+
+#[test]
+fn screed_functional_heating() {
+    //the hold phase of the program is above the max forward temperature of the finished floor
+    let config = config(Control::Histeresis, Mode::Air, target());
+    let parameters = Parameters {
+        supply_temperature: 48.0,
+        ..room()
+    };
+    let mut simulation = Simulation::new(parameters, config, WINTER, monday(), 15.0);
+    let mut runner = screed::Runner::new();
+    runner.start(Program::FunctionalHeating);
+    let mut trace = String::from(TRACE_HEADER) + "\n";
+    let mut wrong_modulation = false;
+    while runner.running().is_some() {
+        //the target changes by the day
+        simulation.controller.forward_override = runner.forward_override();
+        run(&mut simulation, TRACE_PERIOD, &mut trace, |simulation| {
+            //the valve is closed when the heat request is withdrawn
+            let controller = &simulation.controller;
+            wrong_modulation |= controller.state.heat_request()
+                && !controller.heat_request()
+                && controller.valve_open();
+
+            let readings = simulation
+                .plant
+                .readings::<Temperature>(&simulation.parameters, &simulation.connected);
+            runner.update(
+                readings.get(Role::Forward).map(|t| t.to_sixteenths()),
+                readings.get(Role::Floor).map(|t| t.to_sixteenths()),
+                Duration::<u32, Seconds>::from(STEP),
+            );
+        });
+    }
+
+    //fed over the limit of the finished floor in the hold phase
+    let days = Program::FunctionalHeating.days();
+    let reached = (3..days).all(|day| match runner.log(day) {
+        Some(log) => log.forward_max.map_or(false, |max| max >= 45 * 16),
+        None => false,
+    });
+    let expectation = if simulation.report.errors > 0 {
+        Err("unexpected error")
+    } else if runner.day() != days || !reached {
+        Err("the forward temperature of the hold phase is not reached")
+    } else if wrong_modulation {
+        Err("the heat request is withdrawn with open valve")
+    } else {
+        Ok(())
+    };
+    finish("screed", &simulation, &trace, expectation);
+}
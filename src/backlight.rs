@@ -0,0 +1,139 @@
+//! Display backlight and screen power management driven by the user activity
+//! (IR remote keys, local buttons, movement messages)
+//!
+//! The backlight output is switched on and off only: a software pwm would flicker while the
+//! main loop blocks (e.g. during the 1-wire reads) and the backlight pin has no timer channel.
+
+use core::ops::Add;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Level {
+    On,
+    Off,
+}
+
+pub struct Config<Duration> {
+    pub off_timeout: Duration, //inactivity before turning off
+    pub wake_on_movement: bool,
+}
+
+impl<Duration: Copy + PartialOrd + Default> Config<Duration> {
+    /// A zero timeout would turn the screen off before the first key press is shown
+    pub fn is_valid(&self) -> bool {
+        self.off_timeout > Duration::default()
+    }
+}
+
+pub struct Backlight<Duration> {
+    idle: Duration, //since the last activity
+    level: Level,
+}
+
+impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> Backlight<Duration> {
+    pub fn new() -> Self {
+        Backlight {
+            idle: Duration::default(),
+            level: Level::On,
+        }
+    }
+
+    /// Call on user interaction (key press).
+    /// Returns false if the screen was off, then the interaction should be used for wake up only.
+    pub fn activity(&mut self) -> bool {
+        let was_off = self.level == Level::Off;
+        self.idle = Duration::default();
+        self.level = Level::On;
+        !was_off
+    }
+
+    /// Call on movement detection, it keeps the screen on only if it was configured so
+    pub fn movement(&mut self, config: &Config<Duration>) {
+        if config.wake_on_movement {
+            self.activity();
+        }
+    }
+
+    /// This should be called regurarily.
+    /// Returns true when the screen has just turned off, the UI should return to the home page.
+    pub fn update(&mut self, config: &Config<Duration>, delta_time: Duration) -> bool {
+        if self.level == Level::Off {
+            return false;
+        }
+
+        self.idle = self.idle + delta_time;
+
+        if self.idle > config.off_timeout {
+            self.level = Level::Off;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// State of the backlight output
+    pub fn is_lit(&self) -> bool {
+        self.level == Level::On
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(wake_on_movement: bool) -> Config<u32> {
+        Config {
+            off_timeout: 60,
+            wake_on_movement: wake_on_movement,
+        }
+    }
+
+    #[test]
+    fn zero_timeout_is_invalid() {
+        assert!(config(true).is_valid());
+        let config = Config {
+            off_timeout: 0u32,
+            wake_on_movement: true,
+        };
+        assert!(!config.is_valid());
+    }
+
+    #[test]
+    fn turns_off_after_the_timeout() {
+        let config = config(false);
+        let mut backlight = Backlight::new();
+        assert!(!backlight.update(&config, 60));
+        assert!(backlight.level() == Level::On && backlight.is_lit());
+        assert!(backlight.update(&config, 1)); //reported once
+        assert!(backlight.level() == Level::Off && !backlight.is_lit());
+        assert!(!backlight.update(&config, 1));
+    }
+
+    #[test]
+    fn wake_on_key() {
+        let config = config(false);
+        let mut backlight = Backlight::new();
+        backlight.update(&config, 30);
+        assert!(backlight.activity()); //lit: the key is used
+        assert!(!backlight.update(&config, 59)); //the idle time restarted
+
+        backlight.update(&config, 10);
+        assert!(backlight.level() == Level::Off);
+        assert!(!backlight.activity()); //off: the key wakes up only
+        assert!(backlight.level() == Level::On);
+        assert!(backlight.activity());
+    }
+
+    #[test]
+    fn wake_on_movement() {
+        let mut backlight = Backlight::new();
+        backlight.update(&config(false), 61);
+        backlight.movement(&config(false));
+        assert!(backlight.level() == Level::Off);
+        backlight.movement(&config(true));
+        assert!(backlight.level() == Level::On);
+    }
+}
@@ -0,0 +1,194 @@
+//! Compact binary encoding of the config records: little endian integers, the temperatures
+//! as i16 in 1/16 degree units, the durations as u32 seconds. CRC-16 (CCITT) for the integrity checks.
+
+use crate::temperature::FixedPoint;
+
+pub const CRC_INIT: u16 = 0xFFFF;
+
+/// CRC-16/CCITT (polynomial 0x1021) continued from the given crc
+pub fn crc16_update(crc: u16, data: &[u8]) -> u16 {
+    let mut crc = crc;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+pub fn crc16(data: &[u8]) -> u16 {
+    crc16_update(CRC_INIT, data)
+}
+
+/// The buffer is too small for the encoded data
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Overflow;
+
+pub struct Writer<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Writer {
+            buffer: buffer,
+            len: 0,
+        }
+    }
+
+    /// The length of the encoded data
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    pub fn bytes(&mut self, data: &[u8]) -> Result<(), Overflow> {
+        let end = self.len + data.len();
+        if end > self.buffer.len() {
+            return Err(Overflow);
+        }
+        self.buffer[self.len..end].copy_from_slice(data);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Overwrites the already written bytes at the position (e.g. a length placeholder)
+    pub fn set_u16(&mut self, position: usize, value: u16) {
+        self.buffer[position..position + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u8(&mut self, value: u8) -> Result<(), Overflow> {
+        self.bytes(&[value])
+    }
+
+    pub fn bool(&mut self, value: bool) -> Result<(), Overflow> {
+        self.u8(value as u8)
+    }
+
+    pub fn u16(&mut self, value: u16) -> Result<(), Overflow> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn i16(&mut self, value: i16) -> Result<(), Overflow> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn u32(&mut self, value: u32) -> Result<(), Overflow> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn i32(&mut self, value: i32) -> Result<(), Overflow> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn temperature<Temperature: FixedPoint>(
+        &mut self,
+        value: &Temperature,
+    ) -> Result<(), Overflow> {
+        self.i16(value.to_sixteenths() as i16)
+    }
+
+    /// None is encoded as i16::MIN
+    pub fn optional_temperature<Temperature: FixedPoint>(
+        &mut self,
+        value: &Option<Temperature>,
+    ) -> Result<(), Overflow> {
+        match value {
+            Some(value) => self.temperature(value),
+            None => self.i16(i16::MIN),
+        }
+    }
+
+    pub fn duration<Duration: Copy + Into<u32>>(
+        &mut self,
+        value: Duration,
+    ) -> Result<(), Overflow> {
+        self.u32(value.into())
+    }
+}
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader {
+            data: data,
+            position: 0,
+        }
+    }
+
+    /// Number of the bytes not read yet
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.position + len;
+        if end > self.data.len() {
+            return None;
+        }
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Some(bytes)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    /// Only 0 and 1 are accepted
+    pub fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn i16(&mut self) -> Option<i16> {
+        self.u16().map(|value| value as i16)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn i32(&mut self) -> Option<i32> {
+        self.u32().map(|value| value as i32)
+    }
+
+    pub fn temperature<Temperature: FixedPoint>(&mut self) -> Option<Temperature> {
+        self.i16()
+            .map(|value| Temperature::from_sixteenths(value as i32))
+    }
+
+    /// The outer None means the end of the data
+    pub fn optional_temperature<Temperature: FixedPoint>(&mut self) -> Option<Option<Temperature>> {
+        match self.i16()? {
+            i16::MIN => Some(None),
+            value => Some(Some(Temperature::from_sixteenths(value as i32))),
+        }
+    }
+
+    pub fn duration<Duration: From<u32>>(&mut self) -> Option<Duration> {
+        self.u32().map(Duration::from)
+    }
+}
@@ -0,0 +1,352 @@
+//! Versioned binary config image for the import and export (e.g. prepared on a PC, pushed over CAN)
+//!
+//! Magic "RPCF", the schema version (u8), then the sections: tag (u8), length (u16), payload.
+//! A crc16 of all the preceding bytes closes the image. The integers are little endian (see codec).
+//! The sections are optional and the unknown ones are skipped, so an image may update a part of the config.
+//!
+//! Schema versions (the records of the config store follow them too):
+//!   1: the first layout
+//!   2: the pid gains and the heating curve factors are i16 instead of i32
+//!   3: the backlight dim timeout is dropped from the options record
+
+use crate::codec::{crc16, Overflow, Reader, Writer};
+
+pub const SCHEMA_VERSION: u8 = 3;
+pub const MIN_SCHEMA_VERSION: u8 = 1; //the older images are rejected
+pub const MAGIC: [u8; 4] = *b"RPCF";
+pub const MAX_IMAGE_SIZE: usize = 768;
+const HEADER_SIZE: usize = 5;
+const CRC_SIZE: usize = 2;
+const CHUNK_DATA: usize = 6; //bytes of the image per frame
+const END_OFFSET: u16 = 0xFFFF;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Section {
+    Heating = 1, //floor_heating::Config
+    Program = 2, //schedule::Schedule
+    Keys = 3,    //ir_remote::KeyMap
+    Sensors = 4, //sensors::Registry
+}
+
+pub const SECTIONS: [Section; 4] = [
+    Section::Heating,
+    Section::Program,
+    Section::Keys,
+    Section::Sensors,
+];
+
+impl Section {
+    pub fn tag(self) -> u8 {
+        self as u8
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Error {
+    Overflow,         //the image does not fit in the buffer
+    Magic,            //not a config image
+    Version(u8),      //unsupported schema version
+    Crc,              //corrupted
+    Truncated,        //a section runs over the end
+    Invalid(Section), //the section can not be decoded
+}
+
+impl Error {
+    /// Code for the reply of the import (0 = accepted)
+    pub fn code(self) -> u8 {
+        match self {
+            Error::Overflow => 1,
+            Error::Magic => 2,
+            Error::Version(_) => 3,
+            Error::Crc => 4,
+            Error::Truncated => 5,
+            Error::Invalid(_) => 6,
+        }
+    }
+}
+
+impl From<Overflow> for Error {
+    fn from(_: Overflow) -> Self {
+        Error::Overflow
+    }
+}
+
+/// Builds the image of the current schema version
+pub struct ImageWriter<'a> {
+    writer: Writer<'a>,
+}
+
+impl<'a> ImageWriter<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Result<Self, Error> {
+        let mut writer = Writer::new(buffer);
+        writer.bytes(&MAGIC)?;
+        writer.u8(SCHEMA_VERSION)?;
+        Ok(ImageWriter { writer: writer })
+    }
+
+    /// Appends the section encoded by the given function
+    pub fn section<E: FnOnce(&mut Writer) -> Result<(), Overflow>>(
+        &mut self,
+        section: Section,
+        encode: E,
+    ) -> Result<(), Error> {
+        self.writer.u8(section.tag())?;
+        let length_position = self.writer.len();
+        self.writer.u16(0)?;
+        encode(&mut self.writer)?;
+        let length = self.writer.len() - length_position - 2;
+        self.writer.set_u16(length_position, length as u16);
+        Ok(())
+    }
+
+    /// Closes the image by the crc, returns its length
+    pub fn finish(mut self) -> Result<usize, Error> {
+        let crc = crc16(self.writer.data());
+        self.writer.u16(crc)?;
+        Ok(self.writer.len())
+    }
+}
+
+/// A checked image
+pub struct Image<'a> {
+    version: u8,
+    sections: &'a [u8],
+}
+
+impl<'a> Image<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_SIZE + CRC_SIZE || data[..4] != MAGIC {
+            return Err(Error::Magic);
+        }
+        let version = data[4];
+        if version < MIN_SCHEMA_VERSION || version > SCHEMA_VERSION {
+            return Err(Error::Version(version));
+        }
+        let end = data.len() - CRC_SIZE;
+        if crc16(&data[..end]) != u16::from_le_bytes([data[end], data[end + 1]]) {
+            return Err(Error::Crc);
+        }
+        let image = Image {
+            version: version,
+            sections: &data[HEADER_SIZE..end],
+        };
+        //the section lengths are checked once here
+        let mut reader = Reader::new(image.sections);
+        while reader.remaining() > 0 {
+            reader.u8().ok_or(Error::Truncated)?;
+            let length = reader.u16().ok_or(Error::Truncated)?;
+            reader.bytes(length as usize).ok_or(Error::Truncated)?;
+        }
+        Ok(image)
+    }
+
+    /// The schema version of the image, the decoders migrate the earlier ones
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The reader of the last section with the tag, None if the image does not contain it
+    pub fn section(&self, section: Section) -> Option<Reader<'a>> {
+        let mut reader = Reader::new(self.sections);
+        let mut found = None;
+        while reader.remaining() > 0 {
+            let tag = reader.u8()?;
+            let length = reader.u16()?;
+            let payload = reader.bytes(length as usize)?;
+            if tag == section.tag() {
+                found = Some(Reader::new(payload));
+            }
+        }
+        found
+    }
+}
+
+/// The frames of the image for the transfer: the offset (u16) and 6 bytes of the image each,
+/// then an end frame with 0xFFFF offset and the length of the image
+pub struct Frames<'a> {
+    image: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+pub fn frames(image: &[u8]) -> Frames<'_> {
+    Frames {
+        image: image,
+        offset: 0,
+        done: false,
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = [u8; 8];
+
+    fn next(&mut self) -> Option<[u8; 8]> {
+        let mut frame = [0u8; 8];
+        if self.offset < self.image.len() {
+            let end = self.image.len().min(self.offset + CHUNK_DATA);
+            frame[..2].copy_from_slice(&(self.offset as u16).to_le_bytes());
+            frame[2..2 + end - self.offset].copy_from_slice(&self.image[self.offset..end]);
+            self.offset = end;
+            Some(frame)
+        } else if !self.done {
+            self.done = true;
+            frame[..2].copy_from_slice(&END_OFFSET.to_le_bytes());
+            frame[2..4].copy_from_slice(&(self.image.len() as u16).to_le_bytes());
+            Some(frame)
+        } else {
+            None
+        }
+    }
+}
+
+/// Collects the image from the frames, they are expected in order.
+/// A missing frame invalidates the transfer, it restarts by the frame of 0 offset.
+pub struct Assembler {
+    buffer: [u8; MAX_IMAGE_SIZE],
+    received: usize,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Assembler {
+            buffer: [0; MAX_IMAGE_SIZE],
+            received: 0,
+        }
+    }
+
+    /// Returns the image after its end frame, if all of it was received
+    pub fn receive(&mut self, frame: &[u8; 8]) -> Option<&[u8]> {
+        let offset = u16::from_le_bytes([frame[0], frame[1]]);
+        if offset == END_OFFSET {
+            let length = u16::from_le_bytes([frame[2], frame[3]]) as usize;
+            let received = self.received;
+            self.received = 0;
+            return if length <= received {
+                Some(&self.buffer[..length])
+            } else {
+                None
+            };
+        }
+        if offset == 0 {
+            self.received = 0;
+        }
+        let offset = offset as usize;
+        if offset == self.received && offset + CHUNK_DATA <= MAX_IMAGE_SIZE {
+            self.buffer[offset..offset + CHUNK_DATA].copy_from_slice(&frame[2..]);
+            self.received += CHUNK_DATA;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(buffer: &mut [u8]) -> usize {
+        let mut writer = ImageWriter::new(buffer).unwrap();
+        writer
+            .section(Section::Heating, |writer| writer.u16(0x1234))
+            .unwrap();
+        writer
+            .section(Section::Keys, |writer| writer.bytes(b"keys!"))
+            .unwrap();
+        writer
+            .section(Section::Heating, |writer| writer.u16(0x5678))
+            .unwrap();
+        writer.finish().unwrap()
+    }
+
+    /// Recomputes the crc after a change of the image
+    fn seal(image: &mut [u8]) {
+        let end = image.len() - CRC_SIZE;
+        let crc = crc16(&image[..end]);
+        image[end..].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buffer = [0u8; MAX_IMAGE_SIZE];
+        let length = image(&mut buffer);
+        let image = Image::parse(&buffer[..length]).unwrap();
+        assert!(image.version() == SCHEMA_VERSION);
+        //the last section of a tag wins
+        assert!(image.section(Section::Heating).unwrap().u16() == Some(0x5678));
+        assert!(image.section(Section::Keys).unwrap().bytes(5) == Some(&b"keys!"[..]));
+        assert!(image.section(Section::Program).is_none());
+    }
+
+    #[test]
+    fn overflow() {
+        let mut buffer = [0u8; 12];
+        let mut writer = ImageWriter::new(&mut buffer).unwrap();
+        let result = writer.section(Section::Keys, |writer| writer.bytes(b"too long"));
+        assert!(result.err() == Some(Error::Overflow));
+    }
+
+    #[test]
+    fn rejects_corrupted_images() {
+        let mut buffer = [0u8; MAX_IMAGE_SIZE];
+        let length = image(&mut buffer);
+        let image = &buffer[..length];
+
+        let mut corrupted = image.to_vec();
+        corrupted[HEADER_SIZE + 3] ^= 0x01;
+        assert!(Image::parse(&corrupted).err() == Some(Error::Crc));
+
+        let mut corrupted = image.to_vec();
+        corrupted[0] = b'X';
+        seal(&mut corrupted);
+        assert!(Image::parse(&corrupted).err() == Some(Error::Magic));
+        assert!(Image::parse(&image[..HEADER_SIZE]).err() == Some(Error::Magic));
+
+        for version in [MIN_SCHEMA_VERSION - 1, SCHEMA_VERSION + 1].iter() {
+            let mut corrupted = image.to_vec();
+            corrupted[4] = *version;
+            seal(&mut corrupted);
+            assert!(Image::parse(&corrupted).err() == Some(Error::Version(*version)));
+        }
+
+        //the first section runs over the end
+        let mut corrupted = image.to_vec();
+        corrupted[HEADER_SIZE + 1] = 0xFF;
+        seal(&mut corrupted);
+        assert!(Image::parse(&corrupted).err() == Some(Error::Truncated));
+    }
+
+    #[test]
+    fn transfer_with_a_partial_last_frame() {
+        let mut buffer = [0u8; MAX_IMAGE_SIZE];
+        let length = image(&mut buffer);
+        assert!(length % CHUNK_DATA == 1); //the last frame carries a single byte
+        let mut assembler = Assembler::new();
+        let mut received = None;
+        for frame in frames(&buffer[..length]) {
+            if let Some(image) = assembler.receive(&frame) {
+                received = Some(image.to_vec());
+            }
+        }
+        assert!(received.as_deref() == Some(&buffer[..length]));
+    }
+
+    #[test]
+    fn transfer_with_a_frame_out_of_order() {
+        let mut buffer = [0u8; MAX_IMAGE_SIZE];
+        let length = image(&mut buffer);
+        let mut sent: Vec<[u8; 8]> = frames(&buffer[..length]).collect();
+        sent.swap(1, 2);
+        let mut assembler = Assembler::new();
+        for frame in sent.iter() {
+            assert!(assembler.receive(frame).is_none());
+        }
+
+        //the transfer restarts by the first frame
+        let mut received = None;
+        for frame in frames(&buffer[..length]) {
+            if let Some(image) = assembler.receive(&frame) {
+                received = Some(image.to_vec());
+            }
+        }
+        assert!(received.as_deref() == Some(&buffer[..length]));
+    }
+}
@@ -0,0 +1,633 @@
+//! Persistent config store in a few reserved flash pages
+//!
+//! The records (key, version, length, crc and the payload) are appended to the active page, the latest
+//! valid record of a key wins. A full page is rotated to the next page: it is erased, the latest records
+//! are copied with the new one, then the page header with the next sequence number commits it.
+//! So the pages are erased in turn (wear leveling), and an interrupted write or rotation leaves
+//! the previous records in effect. The records with bad crc are ignored.
+//! At least two pages are needed, the active page is never erased.
+//!
+//! Page header: magic (u32), sequence (u32)
+//! Record: key (u8), version (u8), length (u16), crc (u16), payload padded to half words with 0xFF
+
+use crate::codec::{crc16_update, CRC_INIT};
+use core::ops::Add;
+
+/// Of the medium density STM32F103
+pub const PAGE_SIZE: usize = 1024;
+pub const MAX_RECORD_SIZE: usize = 512;
+const PAGE_MAGIC: u32 = 0x5354_4346;
+const ERASED_SEQUENCE: u32 = 0xFFFF_FFFF;
+const PAGE_HEADER_SIZE: usize = 8;
+const RECORD_HEADER_SIZE: usize = 6;
+const ERASED_KEY: u8 = 0xFF; //reserved
+const CHUNK_SIZE: usize = 32;
+
+/// Flash pages of PAGE_SIZE, programmed by half words
+pub trait Flash {
+    type Error;
+
+    fn page_count(&self) -> usize;
+    fn read(&self, page: usize, offset: usize, buffer: &mut [u8]);
+    fn erase(&mut self, page: usize) -> Result<(), Self::Error>;
+    /// The offset is even, the last odd byte is padded with 0xFF
+    fn program(&mut self, page: usize, offset: usize, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FlashError {
+    Programming, //not erased
+    WriteProtected,
+}
+
+//the FLASH registers, the hal supports only the wait states
+const FLASH_KEYR: *mut u32 = 0x4002_2004 as *mut u32;
+const FLASH_SR: *mut u32 = 0x4002_200C as *mut u32;
+const FLASH_CR: *mut u32 = 0x4002_2010 as *mut u32;
+const FLASH_AR: *mut u32 = 0x4002_2014 as *mut u32;
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+const SR_BSY: u32 = 1 << 0;
+const SR_PGERR: u32 = 1 << 2;
+const SR_WRPRTERR: u32 = 1 << 4;
+const SR_EOP: u32 = 1 << 5;
+const CR_PG: u32 = 1 << 0;
+const CR_PER: u32 = 1 << 1;
+const CR_STRT: u32 = 1 << 6;
+const CR_LOCK: u32 = 1 << 7;
+
+/// The internal flash of the STM32F1 from the given page aligned address,
+/// these pages should be excluded from the FLASH region of memory.x
+pub struct Stm32Flash {
+    address: usize,
+    pages: usize,
+}
+
+impl Stm32Flash {
+    pub fn new(address: usize, pages: usize) -> Self {
+        Stm32Flash {
+            address: address,
+            pages: pages,
+        }
+    }
+
+    fn page_address(&self, page: usize) -> usize {
+        self.address + page * PAGE_SIZE
+    }
+
+    unsafe fn unlock(&mut self) {
+        if FLASH_CR.read_volatile() & CR_LOCK != 0 {
+            FLASH_KEYR.write_volatile(KEY1);
+            FLASH_KEYR.write_volatile(KEY2);
+        }
+    }
+
+    unsafe fn lock(&mut self) {
+        FLASH_CR.write_volatile(CR_LOCK);
+    }
+
+    /// Waits for the end of the operation, clears the status
+    unsafe fn wait(&mut self) -> Result<(), FlashError> {
+        while FLASH_SR.read_volatile() & SR_BSY != 0 {}
+        let status = FLASH_SR.read_volatile();
+        FLASH_SR.write_volatile(SR_EOP | SR_PGERR | SR_WRPRTERR);
+        if status & SR_WRPRTERR != 0 {
+            Err(FlashError::WriteProtected)
+        } else if status & SR_PGERR != 0 {
+            Err(FlashError::Programming)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Flash for Stm32Flash {
+    type Error = FlashError;
+
+    fn page_count(&self) -> usize {
+        self.pages
+    }
+
+    fn read(&self, page: usize, offset: usize, buffer: &mut [u8]) {
+        let address = self.page_address(page) + offset;
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = unsafe { ((address + i) as *const u8).read_volatile() };
+        }
+    }
+
+    fn erase(&mut self, page: usize) -> Result<(), FlashError> {
+        unsafe {
+            self.unlock();
+            FLASH_CR.write_volatile(CR_PER);
+            FLASH_AR.write_volatile(self.page_address(page) as u32);
+            FLASH_CR.write_volatile(CR_PER | CR_STRT);
+            let result = self.wait();
+            self.lock();
+            result
+        }
+    }
+
+    fn program(&mut self, page: usize, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+        let address = self.page_address(page) + offset;
+        unsafe {
+            self.unlock();
+            FLASH_CR.write_volatile(CR_PG);
+            let mut result = Ok(());
+            for (i, half_word) in data.chunks(2).enumerate() {
+                let value = u16::from_le_bytes([half_word[0], *half_word.get(1).unwrap_or(&0xFF)]);
+                ((address + i * 2) as *mut u16).write_volatile(value);
+                result = self.wait();
+                if result.is_err() {
+                    break;
+                }
+            }
+            self.lock();
+            result
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Error<E> {
+    Flash(E),
+    TooLarge,    //or the key is reserved
+    Full,        //the latest records do not fit in a page
+    TooFewPages, //the rotation needs at least two pages
+}
+
+#[derive(Clone, Copy)]
+struct RecordHeader {
+    key: u8,
+    version: u8,
+    length: usize,
+    crc: u16,
+}
+
+impl RecordHeader {
+    fn encode(&self) -> [u8; RECORD_HEADER_SIZE] {
+        let length = (self.length as u16).to_le_bytes();
+        let crc = self.crc.to_le_bytes();
+        [self.key, self.version, length[0], length[1], crc[0], crc[1]]
+    }
+
+    /// The whole record with the padding
+    fn size(&self) -> usize {
+        RECORD_HEADER_SIZE + (self.length + 1) / 2 * 2
+    }
+}
+
+fn record_crc(key: u8, version: u8, length: usize, payload: &[u8]) -> u16 {
+    let length = (length as u16).to_le_bytes();
+    let crc = crc16_update(CRC_INIT, &[key, version, length[0], length[1]]);
+    crc16_update(crc, payload)
+}
+
+pub struct Store<F> {
+    flash: F,
+    active: Option<(usize, u32)>, //page, sequence
+    free: usize,                  //offset in the active page
+}
+
+impl<F: Flash> Store<F> {
+    /// Finds the active page (the valid one with the highest sequence)
+    pub fn new(flash: F) -> Result<Self, Error<F::Error>> {
+        if flash.page_count() < 2 {
+            return Err(Error::TooFewPages);
+        }
+        let mut active: Option<(usize, u32)> = None;
+        for page in 0..flash.page_count() {
+            let mut header = [0u8; PAGE_HEADER_SIZE];
+            flash.read(page, 0, &mut header);
+            let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+            let sequence = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+            if magic == PAGE_MAGIC
+                && sequence != ERASED_SEQUENCE
+                && active.map_or(true, |(_, latest)| sequence > latest)
+            {
+                active = Some((page, sequence));
+            }
+        }
+        let mut store = Store {
+            flash: flash,
+            active: active,
+            free: PAGE_SIZE,
+        };
+        if let Some((page, _)) = active {
+            store.free = store.scan(page, |_, _, _| {});
+        }
+        Ok(store)
+    }
+
+    /// Calls f with the offset, the header and the validity of the records of the page,
+    /// returns the offset of the free space (PAGE_SIZE if a broken header was found)
+    fn scan<V: FnMut(usize, &RecordHeader, bool)>(&self, page: usize, mut f: V) -> usize {
+        let mut offset = PAGE_HEADER_SIZE;
+        while offset + RECORD_HEADER_SIZE <= PAGE_SIZE {
+            let mut bytes = [0u8; RECORD_HEADER_SIZE];
+            self.flash.read(page, offset, &mut bytes);
+            if bytes.iter().all(|byte| *byte == 0xFF) {
+                return offset;
+            }
+            let header = RecordHeader {
+                key: bytes[0],
+                version: bytes[1],
+                length: u16::from_le_bytes([bytes[2], bytes[3]]) as usize,
+                crc: u16::from_le_bytes([bytes[4], bytes[5]]),
+            };
+            if header.key == ERASED_KEY || offset + header.size() > PAGE_SIZE {
+                //interrupted write, the rest of the page is unusable
+                return PAGE_SIZE;
+            }
+            f(
+                offset,
+                &header,
+                self.payload_crc(page, offset, &header) == header.crc,
+            );
+            offset += header.size();
+        }
+        PAGE_SIZE
+    }
+
+    fn payload_crc(&self, page: usize, offset: usize, header: &RecordHeader) -> u16 {
+        let mut crc = record_crc(header.key, header.version, header.length, &[]);
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let mut position = 0;
+        while position < header.length {
+            let len = (header.length - position).min(CHUNK_SIZE);
+            self.flash.read(
+                page,
+                offset + RECORD_HEADER_SIZE + position,
+                &mut chunk[..len],
+            );
+            crc = crc16_update(crc, &chunk[..len]);
+            position += len;
+        }
+        crc
+    }
+
+    /// The offset and the header of the latest valid record of the key in the active page
+    fn latest(&self, key: u8) -> Option<(usize, RecordHeader)> {
+        let (page, _) = self.active?;
+        let mut latest = None;
+        self.scan(page, |offset, header, valid| {
+            if valid && header.key == key {
+                latest = Some((offset, *header));
+            }
+        });
+        latest
+    }
+
+    /// Copies the payload of the latest record of the key into the buffer,
+    /// returns its version and length. None if missing or larger than the buffer.
+    pub fn read(&self, key: u8, buffer: &mut [u8]) -> Option<(u8, usize)> {
+        let (page, _) = self.active?;
+        let (offset, header) = self.latest(key)?;
+        if header.length > buffer.len() {
+            return None;
+        }
+        self.flash.read(
+            page,
+            offset + RECORD_HEADER_SIZE,
+            &mut buffer[..header.length],
+        );
+        Some((header.version, header.length))
+    }
+
+    /// Appends the record, nothing is written if it is unchanged
+    pub fn write(&mut self, key: u8, version: u8, data: &[u8]) -> Result<(), Error<F::Error>> {
+        if key == ERASED_KEY || data.len() > MAX_RECORD_SIZE {
+            return Err(Error::TooLarge);
+        }
+        let header = RecordHeader {
+            key: key,
+            version: version,
+            length: data.len(),
+            crc: record_crc(key, version, data.len(), data),
+        };
+        if let Some((_, latest)) = self.latest(key) {
+            if latest.version == version && latest.length == data.len() && latest.crc == header.crc
+            {
+                return Ok(());
+            }
+        }
+
+        if let Some((page, _)) = self.active {
+            if self.free + header.size() <= PAGE_SIZE && self.erased(page, self.free, header.size())
+            {
+                match self.append(page, self.free, &header, data) {
+                    Ok(()) => {
+                        self.free += header.size();
+                        return Ok(());
+                    }
+                    Err(_) => self.free = PAGE_SIZE,
+                }
+            }
+        }
+        self.rotate(&header, data)
+    }
+
+    fn erased(&self, page: usize, offset: usize, size: usize) -> bool {
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let mut position = 0;
+        while position < size {
+            let len = (size - position).min(CHUNK_SIZE);
+            self.flash.read(page, offset + position, &mut chunk[..len]);
+            if chunk[..len].iter().any(|byte| *byte != 0xFF) {
+                return false;
+            }
+            position += len;
+        }
+        true
+    }
+
+    fn append(
+        &mut self,
+        page: usize,
+        offset: usize,
+        header: &RecordHeader,
+        data: &[u8],
+    ) -> Result<(), Error<F::Error>> {
+        self.flash
+            .program(page, offset, &header.encode())
+            .map_err(Error::Flash)?;
+        //the odd byte is padded by the flash
+        self.flash
+            .program(page, offset + RECORD_HEADER_SIZE, data)
+            .map_err(Error::Flash)
+    }
+
+    /// Moves the latest records and the new one to the next page
+    fn rotate(&mut self, header: &RecordHeader, data: &[u8]) -> Result<(), Error<F::Error>> {
+        let (next, sequence) = match self.active {
+            Some((page, sequence)) => ((page + 1) % self.flash.page_count(), sequence + 1),
+            None => (0, 0),
+        };
+        self.flash.erase(next).map_err(Error::Flash)?;
+
+        let mut offset = PAGE_HEADER_SIZE;
+        if let Some((page, _)) = self.active {
+            //the offset of the latest valid record by key, 0 if none
+            let mut latest = [0u16; 255];
+            self.scan(page, |offset, record, valid| {
+                if valid {
+                    latest[record.key as usize] = offset as u16;
+                }
+            });
+            for (key, from) in latest.iter().enumerate() {
+                if *from == 0 || key == header.key as usize {
+                    continue;
+                }
+                let mut bytes = [0u8; RECORD_HEADER_SIZE];
+                self.flash.read(page, *from as usize, &mut bytes);
+                let size = RECORD_HEADER_SIZE
+                    + (u16::from_le_bytes([bytes[2], bytes[3]]) as usize + 1) / 2 * 2;
+                if offset + size > PAGE_SIZE {
+                    return Err(Error::Full);
+                }
+                let mut chunk = [0u8; CHUNK_SIZE];
+                let mut position = 0;
+                while position < size {
+                    let len = (size - position).min(CHUNK_SIZE);
+                    self.flash
+                        .read(page, *from as usize + position, &mut chunk[..len]);
+                    self.flash
+                        .program(next, offset + position, &chunk[..len])
+                        .map_err(Error::Flash)?;
+                    position += len;
+                }
+                offset += size;
+            }
+        }
+
+        if offset + header.size() > PAGE_SIZE {
+            return Err(Error::Full);
+        }
+        self.append(next, offset, header, data)?;
+        offset += header.size();
+
+        //the magic is the last, it commits the page
+        self.flash
+            .program(next, 4, &sequence.to_le_bytes())
+            .map_err(Error::Flash)?;
+        self.flash
+            .program(next, 0, &PAGE_MAGIC.to_le_bytes())
+            .map_err(Error::Flash)?;
+        self.active = Some((next, sequence));
+        self.free = offset;
+        Ok(())
+    }
+}
+
+/// Defers the write of the config until it is unchanged for a while, so a series of menu edits
+/// makes a single write. The config is tracked by its checksum.
+pub struct Deferred<Duration> {
+    checksum: u16,
+    since_change: Option<Duration>,
+}
+
+impl<Duration: Copy + PartialOrd + Add<Output = Duration> + From<u32>> Deferred<Duration> {
+    /// With the checksum of the stored config
+    pub fn new(checksum: u16) -> Self {
+        Deferred {
+            checksum: checksum,
+            since_change: None,
+        }
+    }
+
+    /// A write is waiting for the delay
+    pub fn pending(&self) -> bool {
+        self.since_change.is_some()
+    }
+
+    /// This should be called regurarily with the checksum of the current config,
+    /// returns true when the write is due
+    pub fn update(&mut self, checksum: u16, delay: Duration, delta_time: Duration) -> bool {
+        if checksum != self.checksum {
+            self.checksum = checksum;
+            self.since_change = Some(Duration::from(0));
+            return false;
+        }
+        match self.since_change {
+            Some(since_change) if since_change + delta_time >= delay => {
+                self.since_change = None;
+                true
+            }
+            Some(since_change) => {
+                self.since_change = Some(since_change + delta_time);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Erased pages in the RAM, the power is lost after the given number of programmed half words
+    struct SimFlash {
+        pages: Vec<[u8; PAGE_SIZE]>,
+        power: Option<usize>,
+    }
+
+    impl SimFlash {
+        fn new(pages: usize) -> Self {
+            SimFlash {
+                pages: vec![[0xFF; PAGE_SIZE]; pages],
+                power: None,
+            }
+        }
+    }
+
+    impl Flash for &mut SimFlash {
+        type Error = FlashError;
+
+        fn page_count(&self) -> usize {
+            self.pages.len()
+        }
+
+        fn read(&self, page: usize, offset: usize, buffer: &mut [u8]) {
+            buffer.copy_from_slice(&self.pages[page][offset..offset + buffer.len()]);
+        }
+
+        fn erase(&mut self, page: usize) -> Result<(), FlashError> {
+            self.pages[page] = [0xFF; PAGE_SIZE];
+            Ok(())
+        }
+
+        fn program(&mut self, page: usize, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+            for (i, half_word) in data.chunks(2).enumerate() {
+                match self.power {
+                    Some(0) => return Err(FlashError::Programming),
+                    Some(left) => self.power = Some(left - 1),
+                    None => {}
+                }
+                let at = offset + i * 2;
+                if self.pages[page][at..at + 2] != [0xFF, 0xFF] {
+                    return Err(FlashError::Programming);
+                }
+                self.pages[page][at] = half_word[0];
+                self.pages[page][at + 1] = *half_word.get(1).unwrap_or(&0xFF);
+            }
+            Ok(())
+        }
+    }
+
+    fn read<F: Flash>(store: &Store<F>, key: u8) -> Option<(u8, Vec<u8>)> {
+        let mut buffer = [0u8; MAX_RECORD_SIZE];
+        let (version, length) = store.read(key, &mut buffer)?;
+        Some((version, buffer[..length].to_vec()))
+    }
+
+    fn active<F: Flash>(store: &Store<F>) -> usize {
+        store.active.unwrap().0
+    }
+
+    #[test]
+    fn rejects_a_single_page() {
+        let mut flash = SimFlash::new(1);
+        assert!(Store::new(&mut flash).err() == Some(Error::TooFewPages));
+    }
+
+    #[test]
+    fn latest_record_wins() {
+        let mut flash = SimFlash::new(2);
+        let mut store = Store::new(&mut flash).unwrap();
+        assert!(read(&store, 1).is_none());
+        store.write(1, 1, b"first").unwrap();
+        store.write(2, 1, b"other").unwrap();
+        store.write(1, 2, b"second").unwrap();
+        let free = store.free;
+        store.write(1, 2, b"second").unwrap(); //unchanged, not written
+        assert!(store.free == free);
+        assert!(store.write(ERASED_KEY, 1, b"reserved") == Err(Error::TooLarge));
+
+        let store = Store::new(&mut flash).unwrap();
+        assert!(read(&store, 1) == Some((2, b"second".to_vec())));
+        assert!(read(&store, 2) == Some((1, b"other".to_vec())));
+    }
+
+    #[test]
+    fn rotation_keeps_the_latest_records() {
+        let mut flash = SimFlash::new(3);
+        let mut store = Store::new(&mut flash).unwrap();
+        store.write(1, 1, b"kept").unwrap();
+        let mut pages = Vec::new();
+        for i in 0..400u32 {
+            store.write(2, 1, &i.to_le_bytes()).unwrap();
+            if pages.last() != Some(&active(&store)) {
+                pages.push(active(&store));
+            }
+        }
+        //the pages are used in turn
+        assert!(pages.len() > 3);
+        for (i, page) in pages.iter().enumerate() {
+            assert!(*page == i % 3);
+        }
+
+        let store = Store::new(&mut flash).unwrap();
+        assert!(read(&store, 1) == Some((1, b"kept".to_vec())));
+        assert!(read(&store, 2) == Some((1, 399u32.to_le_bytes().to_vec())));
+    }
+
+    #[test]
+    fn rotation_of_too_many_records() {
+        let mut flash = SimFlash::new(2);
+        let mut store = Store::new(&mut flash).unwrap();
+        let payload = [0x55u8; 300];
+        store.write(1, 1, &payload).unwrap();
+        store.write(2, 1, &payload).unwrap();
+        store.write(3, 1, &payload).unwrap();
+        assert!(store.write(4, 1, &payload) == Err(Error::Full));
+        //the failed rotation is not committed
+        let store = Store::new(&mut flash).unwrap();
+        assert!(active(&store) == 0);
+        assert!(read(&store, 3).is_some() && read(&store, 4).is_none());
+    }
+
+    #[test]
+    fn torn_append_keeps_the_previous_record() {
+        let data = b"after the reset";
+        //the power is lost after each half word of the record
+        for cut in 0..(RECORD_HEADER_SIZE + data.len()) / 2 {
+            let mut flash = SimFlash::new(2);
+            let mut store = Store::new(&mut flash).unwrap();
+            store.write(1, 1, b"before").unwrap();
+
+            flash.power = Some(cut);
+            let mut store = Store::new(&mut flash).unwrap();
+            assert!(store.write(1, 1, data).is_err());
+
+            flash.power = None;
+            let mut store = Store::new(&mut flash).unwrap();
+            assert!(read(&store, 1) == Some((1, b"before".to_vec())));
+            store.write(1, 1, b"again").unwrap();
+            let store = Store::new(&mut flash).unwrap();
+            assert!(read(&store, 1) == Some((1, b"again".to_vec())));
+        }
+    }
+
+    #[test]
+    fn torn_rotation_keeps_the_previous_page() {
+        let mut flash = SimFlash::new(2);
+        let mut store = Store::new(&mut flash).unwrap();
+        let payload = [0xAAu8; 400];
+        store.write(1, 1, &payload).unwrap();
+        store.write(2, 1, &payload).unwrap();
+
+        //the next write rotates, the power is lost before the page header is written
+        flash.power = Some(2 * (RECORD_HEADER_SIZE + 400) / 2);
+        let mut store = Store::new(&mut flash).unwrap();
+        assert!(store.write(2, 2, &payload).is_err());
+
+        flash.power = None;
+        let mut store = Store::new(&mut flash).unwrap();
+        assert!(active(&store) == 0);
+        assert!(read(&store, 2) == Some((1, payload.to_vec())));
+        store.write(2, 2, &payload).unwrap();
+        assert!(active(&store) == 1);
+        assert!(read(&store, 1) == Some((1, payload.to_vec())));
+        assert!(read(&store, 2) == Some((2, payload.to_vec())));
+    }
+}
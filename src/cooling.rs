@@ -0,0 +1,255 @@
+//! Cooling by chilled water through the floor loops (reversible heat pump) with dew point protection:
+//! the valve is closed when the forward or floor temperature gets close to the dew point of the room air,
+//! so the water vapour can not condense on the floor. Without humidity the cooling is not allowed.
+//! The switchover between the heating and the cooling season is automatic by the outdoor temperature
+//! or forced (e.g. by a CAN command).
+//! The temperatures are compared in 1/16 degree units.
+
+use crate::sensors::{Readings, Role};
+use crate::temperature::FixedPoint;
+use core::ops::Add;
+
+/// Dew point in 1/16 degree by the Lawrence approximation (Td = T - (100 - RH) / 5),
+/// it is accurate within 1 degree above 50% relative humidity and it is lower (safer) below that
+pub fn dew_point(air: i32, humidity: u8) -> i32 {
+    let humidity = if humidity > 100 { 100 } else { humidity } as i32;
+    air - (100 - humidity) * 16 / 5
+}
+
+pub struct Config<Temperature, Duration> {
+    pub target_air_temperature: Temperature,
+    pub histeresis: Temperature,
+    pub dew_point_margin: Temperature, //the forward and floor are kept above the dew point + margin
+    pub min_stop: Duration,            //after a dew point stop
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum State<Duration> {
+    Idle,
+    Cooling,
+    DewPointStop(Duration), //since the stop
+}
+
+pub struct Cooler<Duration> {
+    pub state: State<Duration>,
+    pub dew_point: Option<i32>,
+}
+
+impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> Cooler<Duration> {
+    pub fn new() -> Self {
+        Cooler {
+            state: State::Idle,
+            dew_point: None,
+        }
+    }
+
+    pub fn valve_open(&self) -> bool {
+        self.state == State::Cooling
+    }
+
+    pub fn pump_running(&self) -> bool {
+        self.state == State::Cooling
+    }
+
+    /// The chilled water should be requested
+    pub fn cool_request(&self) -> bool {
+        self.state == State::Cooling
+    }
+
+    pub fn reset(&mut self) {
+        self.state = State::Idle;
+    }
+
+    /// This should be called regurarily in the cooling season with the relative humidity in %
+    pub fn update<Temperature: Copy + FixedPoint>(
+        &mut self,
+        config: &Config<Temperature, Duration>,
+        readings: &Readings<Temperature>,
+        humidity: Option<u8>,
+        delta_time: Duration,
+    ) {
+        let air = readings.get(Role::Air).map(|t| t.to_sixteenths());
+        self.dew_point = match (air, humidity) {
+            (Some(air), Some(humidity)) => Some(dew_point(air, humidity)),
+            _ => None,
+        };
+
+        //the coldest of the forward and the floor should stay above the limit
+        let coldest = match (
+            readings.get(Role::Forward).map(|t| t.to_sixteenths()),
+            readings.get(Role::Floor).map(|t| t.to_sixteenths()),
+        ) {
+            (Some(forward), Some(floor)) => Some(if forward < floor { forward } else { floor }),
+            (forward, floor) => forward.or(floor),
+        };
+        let safe = match (self.dew_point, coldest) {
+            (Some(dew_point), Some(coldest)) => {
+                coldest > dew_point + config.dew_point_margin.to_sixteenths()
+            }
+            _ => false, //unknown dew point or water temperature
+        };
+
+        let target = config.target_air_temperature.to_sixteenths();
+        let histeresis = config.histeresis.to_sixteenths();
+
+        self.state = match (self.state, air) {
+            (_, None) => State::Idle,
+            (State::Cooling, _) if !safe => State::DewPointStop(Duration::default()),
+            (State::Cooling, Some(air)) if air < target => State::Idle,
+            (State::Cooling, _) => State::Cooling,
+            (State::DewPointStop(since), _) => {
+                let since = since + delta_time;
+                if since >= config.min_stop && safe {
+                    State::Idle
+                } else {
+                    State::DewPointStop(since)
+                }
+            }
+            (State::Idle, Some(air)) if safe && air >= target + histeresis => State::Cooling,
+            (State::Idle, _) => State::Idle,
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Season {
+    Heating,
+    Cooling,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Switchover {
+    Auto,
+    Forced(Season),
+}
+
+pub struct SwitchoverConfig<Temperature, Duration> {
+    pub cooling_above: Temperature, //outdoor
+    pub heating_below: Temperature, //outdoor
+    pub delay: Duration,            //the outdoor temperature should stay over the limit this long
+}
+
+pub struct SeasonSelector<Duration> {
+    pub switchover: Switchover,
+    season: Season,
+    pending: Duration, //since the outdoor temperature is over the limit of the other season
+}
+
+impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>>
+    SeasonSelector<Duration>
+{
+    pub fn new() -> Self {
+        SeasonSelector {
+            switchover: Switchover::Auto,
+            season: Season::Heating,
+            pending: Duration::default(),
+        }
+    }
+
+    pub fn season(&self) -> Season {
+        match self.switchover {
+            Switchover::Forced(season) => season,
+            Switchover::Auto => self.season,
+        }
+    }
+
+    /// This should be called regurarily, without outdoor temperature the season is kept
+    pub fn update<Temperature: FixedPoint>(
+        &mut self,
+        config: &SwitchoverConfig<Temperature, Duration>,
+        outdoor: Option<Temperature>,
+        delta_time: Duration,
+    ) {
+        let outdoor = match outdoor {
+            Some(outdoor) => outdoor.to_sixteenths(),
+            None => {
+                self.pending = Duration::default();
+                return;
+            }
+        };
+        let other = match self.season {
+            Season::Heating if outdoor >= config.cooling_above.to_sixteenths() => Season::Cooling,
+            Season::Cooling if outdoor <= config.heating_below.to_sixteenths() => Season::Heating,
+            _ => {
+                self.pending = Duration::default();
+                return;
+            }
+        };
+
+        self.pending = self.pending + delta_time;
+        if self.pending >= config.delay {
+            self.season = other;
+            self.pending = Duration::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use onewire::temperature::Temperature;
+
+    fn celsius(degrees: i32) -> Temperature {
+        Temperature::from_sixteenths(degrees * 16)
+    }
+
+    fn config() -> Config<Temperature, u32> {
+        Config {
+            target_air_temperature: celsius(24),
+            histeresis: celsius(1),
+            dew_point_margin: celsius(2),
+            min_stop: 600,
+        }
+    }
+
+    fn readings() -> Readings<Temperature> {
+        let mut readings = Readings::new();
+        readings.set(Role::Air, Some(celsius(26)));
+        readings.set(Role::Forward, Some(celsius(21)));
+        readings.set(Role::Floor, Some(celsius(22)));
+        readings
+    }
+
+    #[test]
+    fn dew_point_by_the_humidity() {
+        assert!(dew_point(26 * 16, 60) == 18 * 16);
+        assert!(dew_point(26 * 16, 100) == 26 * 16);
+        assert!(dew_point(26 * 16, 120) == 26 * 16);
+    }
+
+    #[test]
+    fn dew_point_stop() {
+        let config = config();
+        let readings = readings();
+        let mut cooler = Cooler::<u32>::new();
+        cooler.update(&config, &readings, Some(60), 60); //the limit is 18 + 2 degree
+        assert!(cooler.state == State::Cooling && cooler.valve_open() && cooler.cool_request());
+
+        cooler.update(&config, &readings, Some(75), 60); //the limit is 21 + 2 degree
+        assert!(cooler.state == State::DewPointStop(0));
+        assert!(!cooler.valve_open() && !cooler.pump_running() && !cooler.cool_request());
+
+        //held for the min stop time even if the air dries
+        for _ in 0..9 {
+            cooler.update(&config, &readings, Some(60), 60);
+        }
+        assert!(!cooler.valve_open());
+        cooler.update(&config, &readings, Some(60), 60);
+        assert!(cooler.state == State::Idle);
+        cooler.update(&config, &readings, Some(60), 60);
+        assert!(cooler.state == State::Cooling);
+    }
+
+    #[test]
+    fn no_cooling_without_humidity() {
+        let mut cooler = Cooler::<u32>::new();
+        cooler.update(&config(), &readings(), None, 60);
+        assert!(cooler.state == State::Idle && cooler.dew_point.is_none());
+
+        //nor without the water temperatures
+        let mut readings = Readings::new();
+        readings.set(Role::Air, Some(celsius(26)));
+        cooler.update(&config(), &readings, Some(40), 60);
+        assert!(cooler.state == State::Idle);
+    }
+}
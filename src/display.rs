@@ -1,3 +1,4 @@
+use crate::temperature::FixedPoint;
 use crate::timing::{Duration, Seconds, WeekTime};
 use onewire::temperature::Temperature;
 
@@ -109,3 +110,227 @@ pub fn print_time<D: lcd_hal::Display>(display: &mut D, t: WeekTime) -> Result<(
     display.print_char(':' as u8)?;
     print_nn(display, t.min)
 }
+
+//5x7 glyphs, the ones used for the readouts only:
+static BIG_DIGITS: [&[u8]; 10] = [
+    &[0x3E, 0x51, 0x49, 0x45, 0x3E],
+    &[0x00, 0x42, 0x7F, 0x40, 0x00],
+    &[0x42, 0x61, 0x51, 0x49, 0x46],
+    &[0x21, 0x41, 0x45, 0x4B, 0x31],
+    &[0x18, 0x14, 0x12, 0x7F, 0x10],
+    &[0x27, 0x45, 0x45, 0x45, 0x39],
+    &[0x3C, 0x4A, 0x49, 0x49, 0x30],
+    &[0x01, 0x71, 0x09, 0x05, 0x03],
+    &[0x36, 0x49, 0x49, 0x49, 0x36],
+    &[0x06, 0x49, 0x49, 0x29, 0x1E],
+];
+static BIG_MINUS: &[u8] = &[0x08, 0x08, 0x08, 0x08, 0x08];
+static BIG_SPACE: &[u8] = &[0x00, 0x00, 0x00, 0x00, 0x00];
+static BIG_POINT: &[u8] = &[0x60, 0x60];
+static BIG_COLON: &[u8] = &[0x36, 0x36];
+static BIG_DEGREE: &[u8] = &[0x06, 0x09, 0x09, 0x06];
+static BIG_CELSIUS: &[u8] = &[0x3E, 0x41, 0x41, 0x41, 0x22];
+
+fn big_glyph(c: u8) -> &'static [u8] {
+    match c {
+        b'0'..=b'9' => BIG_DIGITS[(c - b'0') as usize],
+        b'-' => BIG_MINUS,
+        b'.' => BIG_POINT,
+        b':' => BIG_COLON,
+        b'*' => BIG_DEGREE, //there is no degree sign in ascii
+        b'C' => BIG_CELSIUS,
+        _ => BIG_SPACE,
+    }
+}
+
+pub const MAX_BIG_SCALE: u8 = 3;
+
+fn big_scale(scale: u8) -> u8 {
+    scale.max(1).min(MAX_BIG_SCALE)
+}
+
+/// Returns the pixel width of the text printed with print_big
+pub fn big_width(text: &[u8], scale: u8) -> u16 {
+    text.iter()
+        .map(|c| (big_glyph(*c).len() as u16 + 1) * big_scale(scale) as u16)
+        .sum::<u16>()
+}
+
+/// Prints the text with the digit glyphs scaled up 1x, 2x or 3x, the text occupies `scale` rows.
+/// Supported characters: '0'..'9', '-', '.', ':', '*' for the degree sign, 'C' and space.
+/// The scale is clamped to 1..=3.
+pub fn print_big<D: lcd_hal::Display>(
+    display: &mut D,
+    x: u8,
+    row: u8,
+    text: &[u8],
+    scale: u8,
+) -> Result<(), D::Error> {
+    let scale = big_scale(scale);
+    let mut columns = [0u8; MAX_BIG_SCALE as usize];
+
+    for page in 0..scale {
+        display.set_position(x, row + page)?;
+        for c in text {
+            for column in big_glyph(*c).iter().chain([0u8].iter()) {
+                //stretch the requested 8 pixel slice of the column:
+                let mut bits = 0u8;
+                for bit in 0..8 {
+                    if (column >> ((page * 8 + bit) / scale)) & 1 != 0 {
+                        bits |= 1 << bit;
+                    }
+                }
+                for i in 0..scale as usize {
+                    columns[i] = bits;
+                }
+                //8 pixel high columns from the current position, LSB is the top pixel
+                display.print_image(&columns[..scale as usize])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Formats the temperature rounded to "-NN.N*C" where the sign is replaced by space if positive
+pub unsafe fn fmt_big_temp(temp: Temperature) -> &'static [u8] {
+    static mut TEXT: [u8; 7] = [0u8; 7];
+    let tenths = temp.to_tenths();
+    let sign = if tenths < 0 { b'-' } else { b' ' };
+    let t = tenths.abs() as u32;
+    let tens = (t / 100 % 10) as u8;
+
+    //move the sign next to the first digit:
+    TEXT[0] = if tens == 0 { b' ' } else { sign };
+    TEXT[1] = if tens == 0 { sign } else { b'0' + tens };
+    TEXT[2] = b'0' + (t / 10 % 10) as u8;
+    TEXT[3] = b'.';
+    TEXT[4] = b'0' + (t % 10) as u8;
+    TEXT[5] = b'*';
+    TEXT[6] = b'C';
+    &TEXT
+}
+
+/// Prints the temperature with big digits horizontally centered, "--.-*C" is printed if missing
+pub fn print_big_temp<D: lcd_hal::Display>(
+    display: &mut D,
+    row: u8,
+    temp: &Option<Temperature>,
+    scale: u8,
+) -> Result<(), D::Error> {
+    let text = if let Some(temp) = temp {
+        let text = unsafe { fmt_big_temp(*temp) };
+        //skip the leading spaces:
+        let mut start = 0;
+        while text[start] == b' ' {
+            start += 1;
+        }
+        &text[start..]
+    } else {
+        b"--.-*C"
+    };
+
+    let (width, _) = display.get_pixel_resolution();
+    let w = big_width(text, scale);
+    let x = (width as u16).saturating_sub(w) / 2;
+    print_big(display, x as u8, row, text, scale)
+}
+
+//8x8 state icons, LSB is the top pixel:
+pub static ICON_VALVE: [u8; 8] = [0x7F, 0x3E, 0x1C, 0x08, 0x1C, 0x3E, 0x7F, 0x00];
+pub static ICON_PUMP: [u8; 8] = [0x1C, 0x22, 0x5D, 0x49, 0x41, 0x22, 0x1C, 0x00];
+pub static ICON_HEAT_REQUEST: [u8; 8] = [0x08, 0x04, 0x02, 0x7F, 0x02, 0x04, 0x08, 0x00];
+pub static ICON_FLAME: [u8; 8] = [0x60, 0x78, 0x7E, 0x7F, 0x7C, 0x70, 0x60, 0x00];
+pub static ICON_FROST: [u8; 8] = [0x49, 0x2A, 0x1C, 0x7F, 0x1C, 0x2A, 0x49, 0x00];
+pub static ICON_NONE: [u8; 8] = [0x00; 8];
+
+/// Draws the icon if the state is active, otherwise clears its place
+pub fn print_icon<D: lcd_hal::Display>(
+    display: &mut D,
+    x: u8,
+    row: u8,
+    icon: &[u8; 8],
+    active: bool,
+) -> Result<(), D::Error> {
+    display.set_position(x, row)?;
+    display.print_image(if active { icon } else { &ICON_NONE })
+}
+
+static HEX: &[u8] = b"0123456789ABCDEF";
+
+/// Prints the bytes as hexadecimal digits (like the 1-wire ROM codes)
+pub fn print_hex<D: lcd_hal::Display>(display: &mut D, data: &[u8]) -> Result<(), D::Error> {
+    for b in data {
+        display.print_char(HEX[(b >> 4) as usize])?;
+        display.print_char(HEX[(b & 0x0F) as usize])?;
+    }
+    Ok(())
+}
+
+/// Formats the bytes in hexadecimal (max 8 bytes)
+pub unsafe fn fmt_hex(data: &[u8]) -> &'static [u8] {
+    static mut TEXT: [u8; 16] = [0u8; 16];
+    let mut len = 0;
+    for b in data.iter().take(TEXT.len() / 2) {
+        TEXT[len] = HEX[(b >> 4) as usize];
+        TEXT[len + 1] = HEX[(b & 0x0F) as usize];
+        len += 2;
+    }
+    &TEXT[..len]
+}
+
+/// Formats the number without leading zeros
+pub unsafe fn fmt_u32(n: u32) -> &'static [u8] {
+    static mut TEXT: [u8; 10] = [0u8; 10];
+    let mut i = TEXT.len();
+    let mut n = n;
+    loop {
+        i -= 1;
+        TEXT[i] = '0' as u8 + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    &TEXT[i..]
+}
+
+/// Formats a value given in 1/10 units with one decimal digit, like "-1.5"
+pub unsafe fn fmt_tenths(n: i32) -> &'static [u8] {
+    static mut TEXT: [u8; 12] = [0u8; 12];
+    let mut i = TEXT.len();
+    let mut value = if n < 0 { -n } else { n } as u32;
+    i -= 1;
+    TEXT[i] = '0' as u8 + (value % 10) as u8;
+    i -= 1;
+    TEXT[i] = '.' as u8;
+    value /= 10;
+    loop {
+        i -= 1;
+        TEXT[i] = '0' as u8 + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        i -= 1;
+        TEXT[i] = '-' as u8;
+    }
+    &TEXT[i..]
+}
+
+/// Prints the number without leading zeros
+pub fn print_u32<D: lcd_hal::Display>(display: &mut D, n: u32) -> Result<(), D::Error> {
+    display.print(unsafe { fmt_u32(n) })
+}
+
+/// Prints the duration in "H:MM:SS" format
+pub fn print_hms<D: lcd_hal::Display>(
+    display: &mut D,
+    duration: &Duration<u32, Seconds>,
+) -> Result<(), D::Error> {
+    let (hour, _, _) = duration.to_hms();
+    print_u32(display, hour)?;
+    display.print_char(':' as u8)?;
+    display.print(unsafe { fmt_duration(duration) })
+}
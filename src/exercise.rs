@@ -0,0 +1,191 @@
+//! Anti-seize exercise of idling valves and pumps (e.g. in summer)
+//! The devices are moved one after the other at the configured weekday and hour,
+//! a device is skipped if it has moved recently in the normal operation.
+//! The valves come first and the pump is the last device: the first valve is kept open
+//! while the pump is exercised, so it does not run against closed valves.
+
+use crate::timing::WeekTime;
+use core::ops::Add;
+
+pub struct Config<Duration> {
+    pub enabled: bool,
+    pub weekday: u8, //of the exercise, 0 = monday
+    pub hour: u8,
+    pub min_idle: Duration, //devices moved within this are skipped
+    pub duration: Duration, //of the exercise per device
+}
+
+/// Tracks the idle time of a valve or pump
+pub struct Device<Duration> {
+    idle: Duration, //since the device was last moved
+}
+
+impl<Duration: Copy + Default + Add<Duration, Output = Duration>> Device<Duration> {
+    pub fn new() -> Self {
+        Device {
+            idle: Duration::default(),
+        }
+    }
+
+    /// Time since the device has last moved
+    pub fn idle(&self) -> Duration {
+        self.idle
+    }
+
+    /// Continues with the idle time saved before a reset
+    pub fn set_idle(&mut self, idle: Duration) {
+        self.idle = idle;
+    }
+
+    /// This should be called regurarily with the actual state of the output
+    pub fn record(&mut self, moving: bool, delta_time: Duration) {
+        self.idle = if moving {
+            Duration::default()
+        } else {
+            self.idle + delta_time
+        };
+    }
+}
+
+pub struct Exerciser<Duration> {
+    current: Option<(usize, Duration)>, //index of the exercised device, elapsed time
+}
+
+impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> Exerciser<Duration> {
+    pub fn new() -> Self {
+        Exerciser { current: None }
+    }
+
+    /// The indexed device should be opened or started now
+    pub fn active(&self, index: usize) -> bool {
+        match self.current {
+            Some((current, _)) => current == index,
+            None => false,
+        }
+    }
+
+    /// The indexed valve should be opened now: it is exercised,
+    /// or it is the first valve that is kept open for the exercised pump
+    pub fn valve_active(&self, index: usize, pump: usize) -> bool {
+        self.active(index) || (index == 0 && self.active(pump))
+    }
+
+    pub fn running(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// This should be called regurarily.
+    /// allowed=false stops the exercise, it should run only while the heating is in standby.
+    pub fn update(
+        &mut self,
+        config: &Config<Duration>,
+        devices: &mut [Device<Duration>],
+        weektime: &WeekTime,
+        allowed: bool,
+        delta_time: Duration,
+    ) {
+        if !allowed || !config.enabled {
+            self.current = None;
+            return;
+        }
+
+        self.current = match self.current {
+            Some((index, elapsed)) => {
+                let elapsed = elapsed + delta_time;
+                if elapsed < config.duration {
+                    Some((index, elapsed))
+                } else {
+                    devices[index].idle = Duration::default();
+                    Self::next(config, devices, index + 1)
+                }
+            }
+            None if weektime.weekday == config.weekday && weektime.hour == config.hour => {
+                Self::next(config, devices, 0)
+            }
+            None => None,
+        };
+    }
+
+    /// The first device from the given index that was idle long enough
+    fn next(
+        config: &Config<Duration>,
+        devices: &[Device<Duration>],
+        from: usize,
+    ) -> Option<(usize, Duration)> {
+        (from..devices.len())
+            .find(|index| devices[*index].idle >= config.min_idle)
+            .map(|index| (index, Duration::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUMP: usize = 2;
+
+    fn config() -> Config<u32> {
+        Config {
+            enabled: true,
+            weekday: 0,
+            hour: 3,
+            min_idle: 3600,
+            duration: 60,
+        }
+    }
+
+    fn devices() -> [Device<u32>; 3] {
+        let mut devices = [Device::new(), Device::new(), Device::new()];
+        for device in devices.iter_mut() {
+            device.set_idle(7 * 24 * 3600);
+        }
+        devices
+    }
+
+    fn at(weekday: u8, hour: u8) -> WeekTime {
+        WeekTime {
+            sec: 0,
+            min: 0,
+            hour: hour,
+            weekday: weekday,
+        }
+    }
+
+    #[test]
+    fn first_valve_is_open_for_the_pump() {
+        let config = config();
+        let mut devices = devices();
+        let mut exerciser = Exerciser::new();
+        exerciser.update(&config, &mut devices, &at(0, 2), true, 60);
+        assert!(!exerciser.running());
+
+        exerciser.update(&config, &mut devices, &at(0, 3), true, 60);
+        assert!(exerciser.valve_active(0, PUMP) && !exerciser.valve_active(1, PUMP));
+        exerciser.update(&config, &mut devices, &at(0, 3), true, 60);
+        assert!(!exerciser.valve_active(0, PUMP) && exerciser.valve_active(1, PUMP));
+        assert!(devices[0].idle() == 0);
+
+        exerciser.update(&config, &mut devices, &at(0, 3), true, 60);
+        assert!(exerciser.active(PUMP));
+        assert!(exerciser.valve_active(0, PUMP) && !exerciser.valve_active(1, PUMP));
+
+        exerciser.update(&config, &mut devices, &at(0, 3), true, 60);
+        assert!(!exerciser.running() && !exerciser.valve_active(0, PUMP));
+        exerciser.update(&config, &mut devices, &at(0, 3), true, 60); //not again in the same hour
+        assert!(!exerciser.running());
+    }
+
+    #[test]
+    fn recently_moved_devices_are_skipped() {
+        let config = config();
+        let mut devices = devices();
+        devices[0].record(true, 60);
+        let mut exerciser = Exerciser::new();
+        exerciser.update(&config, &mut devices, &at(0, 3), true, 60);
+        assert!(exerciser.active(1));
+
+        //stopped when the heating starts
+        exerciser.update(&config, &mut devices, &at(0, 3), false, 60);
+        assert!(!exerciser.running());
+    }
+}
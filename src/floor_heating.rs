@@ -1,15 +1,60 @@
+use crate::codec::{Overflow, Reader, Writer};
+use crate::heating_curve;
+use crate::pid::{Pid, PidConfig};
+use crate::sensors::{Readings, Role};
+use crate::temperature::FixedPoint;
 use core::ops::Add;
+use core::ops::Div;
+use core::ops::Mul;
 use core::ops::Sub;
 
+#[derive(Clone, Copy)]
 pub enum State<Duration> {
     PrepareHeating((bool, Duration)), //bool=defrost, duration required to fully open the valve before starting the heater
     Heating(bool),                    //bool=defrost
     AfterCirculation(Duration),       //circulation_since_closed duration
     Standby(Duration),                //since_last_freeze_test duration
     FreezeProtectionCheckCirculation(Duration), //pre_circulation_duration
-    Error,
+    Error((ErrorReason, Duration, bool)), //since the error, bool=defrost (the freeze protection is kept)
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum ErrorReason {
+    MissingAirSensor,    //neither air nor return temperature for the heating
+    MissingReturnSensor, //neither return nor air temperature for the freeze protection
+    Overheat,            //over the forward or floor limit even with the valve closed
+    CirculationFailure,  //the return temperature does not follow the forward
+}
+
+pub const ERROR_REASON_COUNT: usize = 4;
+
+impl ErrorReason {
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Code for the display and the CAN status (0 = no error)
+    pub fn code(self) -> u8 {
+        self as u8 + 1
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Policy {
+    Latching, //kept until reset by the user
+    Retry,    //cleared after the hold time if the cause has gone
+}
+
+#[derive(Clone)]
+pub struct ErrorConfig<Temperature, Duration> {
+    pub policies: [Policy; ERROR_REASON_COUNT], //by reason index
+    pub min_hold: Duration,                     //the error state is kept at least this long
+    pub overheat_margin: Temperature,           //above the max forward and floor temperatures
+    pub circulation_difference: Temperature,    //max forward - return difference while heating
+    pub circulation_timeout: Duration,          //allowed duration of the larger difference
+}
+
+#[derive(Clone)]
 pub struct FreezeProtectionConfig<Temperature, Duration> {
     pub min_temperature: Temperature,
     pub safe_temperature: Temperature,
@@ -17,7 +62,34 @@ pub struct FreezeProtectionConfig<Temperature, Duration> {
     pub check_duration: Duration,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum Control {
+    Histeresis, //on/off control around the target air temperature
+    Pid,        //time proportional valve drive by a PID loop
+}
+
+/// The temperature regulated, the PID control is used in the Air mode only
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Air,         //the floor temperature is a max limit only
+    Floor,       //e.g. without air sensor, falls back to the air if the floor sensor is missing
+    AirMinFloor, //the floor is kept at least at the min temperature (e.g. bathroom)
+}
+
+#[derive(Clone)]
+pub struct FloorConfig<Temperature> {
+    pub target_temperature: Temperature, //in the Floor mode
+    pub min_temperature: Temperature,    //in the AirMinFloor mode
+    pub histeresis: Temperature,
+}
+
+#[derive(Clone)]
 pub struct Config<Temperature, Duration> {
+    pub control: Control,
+    pub mode: Mode,
+    pub floor: FloorConfig<Temperature>,
+    pub pid: PidConfig<Duration>,
+    pub heating_curve: heating_curve::Config<Temperature>,
     pub max_forward_temperature: Temperature,
     pub max_floor_temperature: Temperature,
     pub target_air_temperature: Option<Temperature>,
@@ -25,6 +97,283 @@ pub struct Config<Temperature, Duration> {
     pub freeze_protection: FreezeProtectionConfig<Temperature, Duration>,
     pub pre_circulation_duration: Duration,
     pub after_circulation_duration: Duration,
+    pub error: ErrorConfig<Temperature, Duration>,
+}
+
+impl Policy {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Policy::Latching),
+            1 => Some(Policy::Retry),
+            _ => None,
+        }
+    }
+}
+
+impl Control {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Control::Histeresis),
+            1 => Some(Control::Pid),
+            _ => None,
+        }
+    }
+}
+
+impl Mode {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Mode::Air),
+            1 => Some(Mode::Floor),
+            2 => Some(Mode::AirMinFloor),
+            _ => None,
+        }
+    }
+}
+
+fn clamp_i16(value: i32) -> i16 {
+    value.max(i16::MIN as i32).min(i16::MAX as i32) as i16
+}
+
+/// The gains and factors were i32 in the schema version 1
+fn read_factor(reader: &mut Reader, version: u8) -> Option<i32> {
+    if version < 2 {
+        reader.i32()
+    } else {
+        reader.i16().map(i32::from)
+    }
+}
+
+impl<Temperature: FixedPoint, Duration: Copy + Into<u32> + From<u32>>
+    Config<Temperature, Duration>
+{
+    /// The defaults of the floor unit, the target air temperature is set by the program
+    pub fn with_defaults() -> Self {
+        let celsius = |degrees: i32| Temperature::from_sixteenths(degrees * 16);
+        let minutes = |minutes: u32| Duration::from(minutes * 60);
+        Config {
+            control: Control::Histeresis,
+            mode: Mode::Air,
+            floor: FloorConfig {
+                target_temperature: celsius(24),
+                min_temperature: celsius(22), //comfort floor in the AirMinFloor mode
+                histeresis: Temperature::from_sixteenths(8),
+            },
+            pid: PidConfig {
+                kp: 500, //full power at 2 degree below the target
+                ki: 200,
+                kd: 0,
+                cycle_period: minutes(30),
+                min_pulse: minutes(5),
+            },
+            heating_curve: heating_curve::Config {
+                enabled: false,
+                slope: 5, //+0.5 degree forward per outdoor degree drop
+                offset: celsius(2),
+                room_factor: 30,
+                min_forward_temperature: celsius(22),
+                histeresis: celsius(2),
+                modulation: heating_curve::Modulation::HeatRequest,
+            },
+            max_forward_temperature: celsius(40),
+            max_floor_temperature: celsius(29),
+            target_air_temperature: None,
+            temperature_histeresis: Temperature::from_sixteenths(2),
+            freeze_protection: FreezeProtectionConfig {
+                min_temperature: celsius(5),
+                safe_temperature: celsius(8),
+                check_interval: minutes(4 * 60),
+                check_duration: minutes(4),
+            },
+            pre_circulation_duration: minutes(4),
+            after_circulation_duration: minutes(4),
+            error: ErrorConfig {
+                policies: [
+                    Policy::Retry,    //missing air sensor
+                    Policy::Retry,    //missing return sensor
+                    Policy::Latching, //overheat
+                    Policy::Latching, //circulation failure
+                ],
+                min_hold: minutes(5),
+                overheat_margin: celsius(5),
+                circulation_difference: celsius(20),
+                circulation_timeout: minutes(30),
+            },
+        }
+    }
+
+    /// The target air temperature is set by the program, it is not encoded.
+    /// The pid gains and the heating curve factors are encoded as i16.
+    pub fn encode(&self, writer: &mut Writer) -> Result<(), Overflow> {
+        writer.u8(self.control as u8)?;
+        writer.u8(self.mode as u8)?;
+        writer.temperature(&self.floor.target_temperature)?;
+        writer.temperature(&self.floor.min_temperature)?;
+        writer.temperature(&self.floor.histeresis)?;
+
+        writer.i16(clamp_i16(self.pid.kp))?;
+        writer.i16(clamp_i16(self.pid.ki))?;
+        writer.i16(clamp_i16(self.pid.kd))?;
+        writer.duration(self.pid.cycle_period)?;
+        writer.duration(self.pid.min_pulse)?;
+
+        let curve = &self.heating_curve;
+        writer.bool(curve.enabled)?;
+        writer.i16(clamp_i16(curve.slope))?;
+        writer.temperature(&curve.offset)?;
+        writer.i16(clamp_i16(curve.room_factor))?;
+        writer.temperature(&curve.min_forward_temperature)?;
+        writer.temperature(&curve.histeresis)?;
+        writer.u8(curve.modulation as u8)?;
+
+        writer.temperature(&self.max_forward_temperature)?;
+        writer.temperature(&self.max_floor_temperature)?;
+        writer.temperature(&self.temperature_histeresis)?;
+
+        let freeze = &self.freeze_protection;
+        writer.temperature(&freeze.min_temperature)?;
+        writer.temperature(&freeze.safe_temperature)?;
+        writer.duration(freeze.check_interval)?;
+        writer.duration(freeze.check_duration)?;
+
+        writer.duration(self.pre_circulation_duration)?;
+        writer.duration(self.after_circulation_duration)?;
+
+        for policy in self.error.policies.iter() {
+            writer.u8(*policy as u8)?;
+        }
+        writer.duration(self.error.min_hold)?;
+        writer.temperature(&self.error.overheat_margin)?;
+        writer.temperature(&self.error.circulation_difference)?;
+        writer.duration(self.error.circulation_timeout)
+    }
+
+    /// None if the data is short or invalid, the earlier versions of the encoding are migrated
+    /// (see config_format::SCHEMA_VERSION)
+    pub fn decode(reader: &mut Reader, version: u8) -> Option<Self> {
+        let control = Control::from_code(reader.u8()?)?;
+        let mode = Mode::from_code(reader.u8()?)?;
+        let floor = FloorConfig {
+            target_temperature: reader.temperature()?,
+            min_temperature: reader.temperature()?,
+            histeresis: reader.temperature()?,
+        };
+        let pid = PidConfig {
+            kp: read_factor(reader, version)?,
+            ki: read_factor(reader, version)?,
+            kd: read_factor(reader, version)?,
+            cycle_period: reader.duration()?,
+            min_pulse: reader.duration()?,
+        };
+        let curve = heating_curve::Config {
+            enabled: reader.bool()?,
+            slope: read_factor(reader, version)?,
+            offset: reader.temperature()?,
+            room_factor: read_factor(reader, version)?,
+            min_forward_temperature: reader.temperature()?,
+            histeresis: reader.temperature()?,
+            modulation: match reader.u8()? {
+                0 => heating_curve::Modulation::HeatRequest,
+                1 => heating_curve::Modulation::Valve,
+                _ => return None,
+            },
+        };
+        let max_forward_temperature = reader.temperature()?;
+        let max_floor_temperature = reader.temperature()?;
+        let temperature_histeresis = reader.temperature()?;
+        let freeze_protection = FreezeProtectionConfig {
+            min_temperature: reader.temperature()?,
+            safe_temperature: reader.temperature()?,
+            check_interval: reader.duration()?,
+            check_duration: reader.duration()?,
+        };
+        let pre_circulation_duration = reader.duration()?;
+        let after_circulation_duration = reader.duration()?;
+        let mut policies = [Policy::Latching; ERROR_REASON_COUNT];
+        for policy in policies.iter_mut() {
+            *policy = Policy::from_code(reader.u8()?)?;
+        }
+        let error = ErrorConfig {
+            policies: policies,
+            min_hold: reader.duration()?,
+            overheat_margin: reader.temperature()?,
+            circulation_difference: reader.temperature()?,
+            circulation_timeout: reader.duration()?,
+        };
+        Some(Config {
+            control: control,
+            mode: mode,
+            floor: floor,
+            pid: pid,
+            heating_curve: curve,
+            max_forward_temperature: max_forward_temperature,
+            max_floor_temperature: max_floor_temperature,
+            target_air_temperature: None,
+            temperature_histeresis: temperature_histeresis,
+            freeze_protection: freeze_protection,
+            pre_circulation_duration: pre_circulation_duration,
+            after_circulation_duration: after_circulation_duration,
+            error: error,
+        })
+    }
+}
+
+impl<Duration> State<Duration> {
+    /// The valve should be opened in this state
+    pub fn valve_open(&self) -> bool {
+        match self {
+            State::PrepareHeating(_) | State::Heating(_) => true,
+            State::Error((_, _, defrost)) => *defrost,
+            _ => false,
+        }
+    }
+
+    /// The circulation pump should run in this state
+    pub fn pump_running(&self) -> bool {
+        match self {
+            State::Error((_, _, defrost)) => *defrost, //not against the closed valve
+            State::Standby(_) => false,
+            _ => true,
+        }
+    }
+
+    /// The heat source should be requested in this state
+    pub fn heat_request(&self) -> bool {
+        match self {
+            State::Heating(_) => true,
+            State::Error((_, _, defrost)) => *defrost,
+            _ => false,
+        }
+    }
+
+    pub fn error(&self) -> Option<ErrorReason> {
+        match self {
+            State::Error((reason, _, _)) => Some(*reason),
+            _ => None,
+        }
+    }
+
+    /// Code for the CAN status
+    pub fn code(&self) -> u8 {
+        match self {
+            State::PrepareHeating(_) => 1,
+            State::Heating(_) => 2,
+            State::AfterCirculation(_) => 3,
+            State::Standby(_) => 4,
+            State::FreezeProtectionCheckCirculation(_) => 5,
+            State::Error(_) => 6,
+        }
+    }
+
+    /// True while heating for freeze protection
+    pub fn defrost(&self) -> bool {
+        match self {
+            State::PrepareHeating((defrost, _))
+            | State::Heating(defrost)
+            | State::Error((_, _, defrost)) => *defrost,
+            _ => false,
+        }
+    }
 }
 
 impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> State<Duration> {
@@ -36,12 +385,34 @@ impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> S
     >(
         &self,
         config: &Config<Temperature, Duration>,
-        forward_temperature: Option<Temperature>,
-        return_temperature: Option<Temperature>,
-        floor_temperature: Option<Temperature>,
-        air_temperature: Option<Temperature>,
+        readings: &Readings<Temperature>,
         delta_time: Duration,
     ) -> State<Duration> {
+        self.update_with_demand(config, readings, None, delta_time)
+    }
+
+    /// Same as update, but the heat demand (if given) overrides the histeresis based decision
+    pub fn update_with_demand<
+        Temperature: Copy
+            + PartialOrd
+            + Add<Temperature, Output = Temperature>
+            + Sub<Temperature, Output = Temperature>,
+    >(
+        &self,
+        config: &Config<Temperature, Duration>,
+        readings: &Readings<Temperature>,
+        demand: Option<bool>,
+        delta_time: Duration,
+    ) -> State<Duration> {
+        let forward_temperature = readings.get(Role::Forward);
+        let return_temperature = readings.get(Role::Return);
+        let floor_temperature = readings.get(Role::Floor);
+        let air_temperature = readings.get(Role::Air);
+
+        if self.error().is_none() && overheated(config, forward_temperature, floor_temperature) {
+            return State::Error((ErrorReason::Overheat, Duration::default(), false));
+        }
+
         match self {
             State::PrepareHeating((defreeze, circulation_since_opened)) => {
                 if *circulation_since_opened > config.pre_circulation_duration {
@@ -71,7 +442,11 @@ impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> S
                         //use as a backup sensor
                         temp
                     } else {
-                        return State::Error;
+                        return State::Error((
+                            ErrorReason::MissingReturnSensor,
+                            Duration::default(),
+                            false,
+                        ));
                     };
 
                     if *return_temp >= config.freeze_protection.safe_temperature {
@@ -80,22 +455,11 @@ impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> S
                         State::Heating(true)
                     }
                 } else {
-                    if let Some(ref target) = config.target_air_temperature {
-                        let current_temp = if let Some(ref temp) = air_temperature {
-                            temp
-                        } else if let Some(ref temp) = return_temperature {
-                            temp //use a backup sensor
-                        } else {
-                            return State::Error;
-                        };
-
-                        if *current_temp > (*target + config.temperature_histeresis) {
-                            State::AfterCirculation(Duration::default())
-                        } else {
-                            State::Heating(false)
-                        }
-                    } else {
-                        State::Standby(Duration::default())
+                    match heat_needed(config, readings, demand, true) {
+                        Ok(Some(true)) => State::Heating(false),
+                        Ok(Some(false)) => State::AfterCirculation(Duration::default()),
+                        Ok(None) => State::Standby(Duration::default()),
+                        Err(reason) => State::Error((reason, Duration::default(), false)),
                     }
                 }
             }
@@ -109,13 +473,9 @@ impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> S
             }
 
             State::Standby(since_last_freeze_test) => {
-                if let Some(target) = config.target_air_temperature {
-                    if let Some(temp) = air_temperature {
-                        if temp <= target - config.temperature_histeresis {
-                            return State::PrepareHeating((false, Duration::default()));
-                        }
-                    };
-                };
+                if let Ok(Some(true)) = heat_needed(config, readings, demand, false) {
+                    return State::PrepareHeating((false, Duration::default()));
+                }
 
                 if *since_last_freeze_test > config.freeze_protection.check_interval {
                     State::FreezeProtectionCheckCirculation(Duration::default())
@@ -131,7 +491,11 @@ impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> S
                     //use this as backup
                     temp
                 } else {
-                    return State::Error;
+                    return State::Error((
+                        ErrorReason::MissingReturnSensor,
+                        Duration::default(),
+                        false,
+                    ));
                 };
 
                 if return_temp < config.freeze_protection.min_temperature {
@@ -145,7 +509,460 @@ impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> S
                 }
             }
 
-            State::Error => State::Standby(Duration::default()),
+            State::Error((reason, since, defrost)) => {
+                let since = *since + delta_time;
+                let overheated = overheated(config, forward_temperature, floor_temperature);
+                let cleared = match reason {
+                    ErrorReason::MissingAirSensor | ErrorReason::MissingReturnSensor => {
+                        air_temperature.is_some() || return_temperature.is_some()
+                    }
+                    ErrorReason::Overheat => !overheated,
+                    ErrorReason::CirculationFailure => true,
+                };
+
+                //the error blocks the comfort heating only, the freeze protection is kept
+                let defrost = match return_temperature.or(air_temperature) {
+                    _ if overheated => false,
+                    Some(temp) if *defrost => temp < config.freeze_protection.safe_temperature,
+                    Some(temp) => temp < config.freeze_protection.min_temperature,
+                    None => false,
+                };
+
+                if since >= config.error.min_hold
+                    && config.error.policies[reason.index()] == Policy::Retry
+                    && cleared
+                    && !defrost
+                {
+                    State::Standby(Duration::default())
+                } else {
+                    State::Error((*reason, since, defrost))
+                }
+            }
+        }
+    }
+}
+
+/// Decides by the control mode if the heating is needed (None without target),
+/// the histeresis is applied depending on the heating is running.
+/// The return temperature is a backup of the air only while heating, a missing sensor does not start the heating.
+fn heat_needed<
+    Temperature: Copy
+        + PartialOrd
+        + Add<Temperature, Output = Temperature>
+        + Sub<Temperature, Output = Temperature>,
+    Duration,
+>(
+    config: &Config<Temperature, Duration>,
+    readings: &Readings<Temperature>,
+    demand: Option<bool>,
+    heating: bool,
+) -> Result<Option<bool>, ErrorReason> {
+    if let Some(demand) = demand {
+        return Ok(Some(demand));
+    }
+    let target = match config.target_air_temperature {
+        Some(target) => target,
+        None => return Ok(None),
+    };
+
+    let needed = |current: Temperature, target: Temperature, histeresis: Temperature| {
+        if heating {
+            !(current > target + histeresis)
+        } else {
+            current <= target - histeresis
+        }
+    };
+    let air_needed = || match (readings.get(Role::Air), readings.get(Role::Return)) {
+        (Some(air), _) => Ok(needed(air, target, config.temperature_histeresis)),
+        (None, Some(backup)) if heating => {
+            Ok(needed(backup, target, config.temperature_histeresis))
+        }
+        (None, _) if heating => Err(ErrorReason::MissingAirSensor),
+        (None, _) => Ok(false),
+    };
+
+    match (config.mode, readings.get(Role::Floor)) {
+        (Mode::Air, _) => air_needed().map(Some),
+        (Mode::Floor, Some(floor)) => Ok(Some(needed(
+            floor,
+            config.floor.target_temperature,
+            config.floor.histeresis,
+        ))),
+        (Mode::AirMinFloor, Some(floor)) => {
+            if needed(floor, config.floor.min_temperature, config.floor.histeresis) {
+                Ok(Some(true))
+            } else {
+                //the floor is warm enough without the air sensor
+                Ok(Some(air_needed().unwrap_or(false)))
+            }
+        }
+        //the floor sensor is missing, fall back to the air control
+        (_, None) => air_needed().map(Some),
+    }
+}
+
+fn overheated<Temperature: Copy + PartialOrd + Add<Temperature, Output = Temperature>, Duration>(
+    config: &Config<Temperature, Duration>,
+    forward_temperature: Option<Temperature>,
+    floor_temperature: Option<Temperature>,
+) -> bool {
+    let over = |temperature: Option<Temperature>, max: Temperature| match temperature {
+        Some(temperature) => temperature >= max + config.error.overheat_margin,
+        None => false,
+    };
+    over(forward_temperature, config.max_forward_temperature)
+        || over(floor_temperature, config.max_floor_temperature)
+}
+
+const HISTORY_SIZE: usize = 8;
+
+/// The last errors with their age, and the number of errors by reason
+pub struct ErrorHistory<Duration> {
+    entries: [Option<(ErrorReason, Duration)>; HISTORY_SIZE],
+    next: usize,
+    counts: [u32; ERROR_REASON_COUNT],
+}
+
+impl<Duration: Copy + Default + Add<Duration, Output = Duration>> ErrorHistory<Duration> {
+    pub fn new() -> Self {
+        ErrorHistory {
+            entries: [None; HISTORY_SIZE],
+            next: 0,
+            counts: [0; ERROR_REASON_COUNT],
+        }
+    }
+
+    pub fn record(&mut self, reason: ErrorReason) {
+        self.entries[self.next] = Some((reason, Duration::default()));
+        self.next = (self.next + 1) % HISTORY_SIZE;
+        self.counts[reason.index()] += 1;
+    }
+
+    /// Ages the entries
+    pub fn update(&mut self, delta_time: Duration) {
+        for entry in self.entries.iter_mut() {
+            if let Some((reason, age)) = *entry {
+                *entry = Some((reason, age + delta_time));
+            }
+        }
+    }
+
+    /// The i-th latest error with its age (0 = the latest)
+    pub fn get(&self, i: usize) -> Option<(ErrorReason, Duration)> {
+        if i < HISTORY_SIZE {
+            self.entries[(self.next + HISTORY_SIZE - 1 - i) % HISTORY_SIZE]
+        } else {
+            None
+        }
+    }
+
+    pub fn count(&self, reason: ErrorReason) -> u32 {
+        self.counts[reason.index()]
+    }
+}
+
+/// Fixed forward target with its own limits (e.g. by the screed program), in 1/16 degree units
+#[derive(Clone, Copy, PartialEq)]
+pub struct ForwardOverride {
+    pub target: i32,
+    pub max_forward: i32,
+    pub max_floor: i32,
+}
+
+impl ForwardOverride {
+    /// The config with the limits of the override, the forward temperature is modulated by the valve
+    pub fn apply<Temperature: Clone + FixedPoint, Duration: Clone>(
+        &self,
+        config: &Config<Temperature, Duration>,
+    ) -> Config<Temperature, Duration> {
+        let mut config = config.clone();
+        config.max_forward_temperature = Temperature::from_sixteenths(self.max_forward);
+        config.max_floor_temperature = Temperature::from_sixteenths(self.max_floor);
+        config.heating_curve.modulation = heating_curve::Modulation::Valve;
+        config
+    }
+}
+
+/// The heating state machine with the selected control strategy
+pub struct Controller<Duration> {
+    pub state: State<Duration>,
+    pub pid: Pid<Duration>,
+    pub forward_target: Option<i32>, //by the heating curve in 1/16 degree units
+    pub history: ErrorHistory<Duration>,
+    pub suspended: bool, //no heating (e.g. open window), but the freeze protection is active
+    pub forward_override: Option<ForwardOverride>, //heating regardless of the room
+    valve_throttled: bool,
+    heat_throttled: bool,
+    circulation_suspect: Duration, //since the forward - return difference is too large
+}
+
+impl<Duration> Controller<Duration>
+where
+    Duration: Copy
+        + PartialOrd
+        + Default
+        + Into<u32>
+        + Add<Duration, Output = Duration>
+        + Sub<Duration, Output = Duration>
+        + Mul<u32, Output = Duration>
+        + Div<u32, Output = Duration>,
+{
+    pub fn new(state: State<Duration>) -> Self {
+        Controller {
+            state: state,
+            pid: Pid::new(),
+            forward_target: None,
+            history: ErrorHistory::new(),
+            suspended: false,
+            forward_override: None,
+            valve_throttled: false,
+            heat_throttled: false,
+            circulation_suspect: Duration::default(),
+        }
+    }
+
+    /// Leaves the (latched) error state
+    pub fn reset_error(&mut self) {
+        if self.state.error().is_some() {
+            self.state = State::Standby(Duration::default());
+        }
+    }
+
+    /// The valve output, closed by the heating curve when the forward temperature is too high
+    pub fn valve_open(&self) -> bool {
+        self.state.valve_open() && !self.valve_throttled
+    }
+
+    pub fn pump_running(&self) -> bool {
+        self.state.pump_running()
+    }
+
+    /// The heat request output, withdrawn by the heating curve when the forward temperature is too high
+    pub fn heat_request(&self) -> bool {
+        self.state.heat_request() && !self.heat_throttled
+    }
+
+    pub fn update<
+        Temperature: Copy
+            + PartialOrd
+            + FixedPoint
+            + Add<Temperature, Output = Temperature>
+            + Sub<Temperature, Output = Temperature>,
+    >(
+        &mut self,
+        config: &Config<Temperature, Duration>,
+        readings: &Readings<Temperature>,
+        delta_time: Duration,
+    ) {
+        //the override may be above the limits of the config (which are set for the finished floor)
+        let overridden;
+        let config = match self.forward_override {
+            Some(forward_override) => {
+                overridden = forward_override.apply(config);
+                &overridden
+            }
+            None => config,
+        };
+
+        let forward_temperature = readings.get(Role::Forward);
+        let return_temperature = readings.get(Role::Return);
+        let air_temperature = readings.get(Role::Air);
+        let outdoor_temperature = readings.get(Role::Outdoor);
+
+        let demand = match (
+            config.control,
+            config.target_air_temperature,
+            air_temperature,
+        ) {
+            _ if self.suspended => {
+                self.pid.reset();
+                Some(false)
+            }
+            _ if self.forward_override.is_some() => {
+                self.pid.reset();
+                Some(true)
+            }
+            (Control::Pid, Some(target), Some(air)) if config.mode == Mode::Air => {
+                Some(self.pid.update(
+                    &config.pid,
+                    target.to_sixteenths(),
+                    air.to_sixteenths(),
+                    delta_time,
+                ))
+            }
+            _ => {
+                //fall back to the histeresis control
+                self.pid.reset();
+                None
+            }
+        };
+
+        let was_error = self.state.error().is_some();
+        self.state = self
+            .state
+            .update_with_demand(config, readings, demand, delta_time);
+
+        //the heated water should return through the loop
+        self.circulation_suspect = match (&self.state, forward_temperature, return_temperature) {
+            (State::Heating(_), Some(forward), Some(ret))
+                if forward > ret + config.error.circulation_difference =>
+            {
+                self.circulation_suspect + delta_time
+            }
+            _ => Duration::default(),
+        };
+        if self.circulation_suspect > config.error.circulation_timeout {
+            self.circulation_suspect = Duration::default();
+            self.state =
+                State::Error((ErrorReason::CirculationFailure, Duration::default(), false));
+        }
+
+        self.history.update(delta_time);
+        if let (false, Some(reason)) = (was_error, self.state.error()) {
+            self.history.record(reason);
         }
+
+        self.forward_target = match (
+            self.forward_override,
+            config.heating_curve.enabled,
+            config.target_air_temperature,
+            outdoor_temperature,
+        ) {
+            (Some(forward_override), _, _, _) => Some(forward_override.target),
+            (None, true, Some(target), Some(outdoor)) => Some(heating_curve::forward_temperature(
+                &config.heating_curve,
+                &config.max_forward_temperature,
+                &target,
+                &outdoor,
+                air_temperature.as_ref(),
+            )),
+            _ => None,
+        };
+
+        //the defrost heating is never throttled
+        let throttled = match (self.forward_target, forward_temperature) {
+            (Some(target), Some(forward)) if !self.state.defrost() => {
+                let forward = forward.to_sixteenths();
+                if self.heat_throttled {
+                    forward > target - config.heating_curve.histeresis.to_sixteenths()
+                } else {
+                    forward >= target
+                }
+            }
+            _ => false,
+        };
+
+        self.heat_throttled = throttled;
+        self.valve_throttled =
+            throttled && config.heating_curve.modulation == heating_curve::Modulation::Valve;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use onewire::temperature::Temperature;
+
+    fn config() -> Config<Temperature, u32> {
+        Config {
+            control: Control::Pid,
+            mode: Mode::AirMinFloor,
+            floor: FloorConfig {
+                target_temperature: Temperature::from_celsius(24, 0),
+                min_temperature: Temperature::from_celsius(22, 0),
+                histeresis: Temperature::from_celsius(0, 8),
+            },
+            pid: PidConfig {
+                kp: 500,
+                ki: 200,
+                kd: -30,
+                cycle_period: 1800,
+                min_pulse: 300,
+            },
+            heating_curve: heating_curve::Config {
+                enabled: true,
+                slope: 5,
+                offset: Temperature::from_celsius(2, 0),
+                room_factor: -40,
+                min_forward_temperature: Temperature::from_celsius(22, 0),
+                histeresis: Temperature::from_celsius(2, 0),
+                modulation: heating_curve::Modulation::Valve,
+            },
+            max_forward_temperature: Temperature::from_celsius(40, 0),
+            max_floor_temperature: Temperature::from_celsius(29, 0),
+            target_air_temperature: None,
+            temperature_histeresis: Temperature::from_celsius(0, 2),
+            freeze_protection: FreezeProtectionConfig {
+                min_temperature: Temperature::from_celsius(5, 0),
+                safe_temperature: Temperature::from_celsius(8, 0),
+                check_interval: 4 * 3600,
+                check_duration: 240,
+            },
+            pre_circulation_duration: 240,
+            after_circulation_duration: 240,
+            error: ErrorConfig {
+                policies: [
+                    Policy::Retry,
+                    Policy::Retry,
+                    Policy::Latching,
+                    Policy::Latching,
+                ],
+                min_hold: 300,
+                overheat_margin: Temperature::from_celsius(5, 0),
+                circulation_difference: Temperature::from_celsius(20, 0),
+                circulation_timeout: 1800,
+            },
+        }
+    }
+
+    fn encode(config: &Config<Temperature, u32>) -> Vec<u8> {
+        let mut buffer = [0u8; 128];
+        let mut writer = Writer::new(&mut buffer);
+        config.encode(&mut writer).unwrap();
+        writer.data().to_vec()
+    }
+
+    #[test]
+    fn round_trip() {
+        let encoded = encode(&config());
+        let mut reader = Reader::new(&encoded);
+        let decoded = Config::<Temperature, u32>::decode(&mut reader, 2).unwrap();
+        assert!(reader.remaining() == 0);
+        assert!(encode(&decoded) == encoded);
+        assert!(Config::<Temperature, u32>::decode(&mut Reader::new(&encoded[1..]), 2).is_none());
+    }
+
+    #[test]
+    fn factors_of_version_1_are_migrated() {
+        //the same config in the version 1 layout: the gains and the curve factors as i32
+        let encoded = encode(&config());
+        let factors = [9, 11, 13, 24, 28]; //offsets of the high bytes of the i16 factors in the version 2 layout
+        let mut version_1 = Vec::new();
+        for (offset, byte) in encoded.iter().enumerate() {
+            version_1.push(*byte);
+            if factors.contains(&offset) {
+                let extension = if *byte & 0x80 != 0 { 0xFF } else { 0x00 };
+                version_1.extend_from_slice(&[extension, extension]);
+            }
+        }
+        assert!(version_1.len() == encoded.len() + 2 * factors.len());
+
+        let mut reader = Reader::new(&version_1);
+        let decoded = Config::<Temperature, u32>::decode(&mut reader, 1).unwrap();
+        assert!(reader.remaining() == 0);
+        assert!(decoded.pid.kp == 500 && decoded.pid.ki == 200 && decoded.pid.kd == -30);
+        assert!(decoded.heating_curve.slope == 5 && decoded.heating_curve.room_factor == -40);
+        //stored again in the current version
+        assert!(encode(&decoded) == encoded);
+    }
+
+    #[test]
+    fn large_factors_are_clamped() {
+        let mut config = config();
+        config.pid.kp = 100_000;
+        config.pid.kd = -100_000;
+        let encoded = encode(&config);
+        let decoded = Config::<Temperature, u32>::decode(&mut Reader::new(&encoded), 2).unwrap();
+        assert!(decoded.pid.kp == i16::MAX as i32 && decoded.pid.kd == i16::MIN as i32);
     }
 }
@@ -0,0 +1,352 @@
+//! Heat demand protocol between the zones and the central heat source coordinator.
+//! Every zone publishes its demand and the required forward temperature (ID_HEAT_DEMAND + zone)
+//! when it changes and periodically. The coordinator aggregates the demands, drives the boiler relay
+//! by the heat demands with min on/off times and broadcasts the supply status (ID_SUPPLY).
+//! The cooling demands are counted separately, they never switch the boiler on.
+//! The coordinator drops the demand of a zone which went silent, the zones fall back to
+//! the hardwired heat request line while the supply status is missing.
+//! The temperatures are given in 1/16 degree units.
+
+use crate::temperature::FixedPoint;
+use core::ops::Add;
+
+pub const MAX_ZONES: usize = 8;
+const NO_TEMPERATURE: u16 = 0x8000;
+
+fn encode_temperature(temperature: Option<i32>) -> [u8; 2] {
+    match temperature {
+        Some(temperature) => (temperature as i16).to_le_bytes(),
+        None => NO_TEMPERATURE.to_le_bytes(),
+    }
+}
+
+fn decode_temperature(data: u64) -> Option<i32> {
+    match data as u16 {
+        NO_TEMPERATURE => None,
+        value => Some(value as i16 as i32),
+    }
+}
+
+/// The demand of a zone
+#[derive(Clone, Copy, PartialEq)]
+pub struct Demand {
+    pub heat: bool,
+    pub cool: bool,                       //chilled water is requested
+    pub forward_temperature: Option<i32>, //required, None = any
+}
+
+impl Demand {
+    pub fn none() -> Self {
+        Demand {
+            heat: false,
+            cool: false,
+            forward_temperature: None,
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.heat || self.cool
+    }
+
+    /// ID_HEAT_DEMAND payload: the flags in the 1st byte (bit0 = heat, bit1 = cool),
+    /// the required forward temperature (i16, little endian, 0x8000 = any) in the next 2 bytes
+    pub fn encode(&self) -> [u8; 8] {
+        let forward = encode_temperature(self.forward_temperature);
+        [
+            self.heat as u8 | (self.cool as u8) << 1,
+            forward[0],
+            forward[1],
+            0,
+            0,
+            0,
+            0,
+            0,
+        ]
+    }
+
+    pub fn decode(data: u64) -> Self {
+        Demand {
+            heat: data & 1 != 0,
+            cool: data & 2 != 0,
+            forward_temperature: decode_temperature(data >> 8),
+        }
+    }
+}
+
+/// The status broadcasted by the coordinator
+#[derive(Clone, Copy, PartialEq)]
+pub struct Supply {
+    pub boiler_on: bool,
+    pub forward_temperature: Option<i32>, //setpoint of the heat source
+    pub zones: u8,                        //with heat demand
+    pub cooling_zones: u8,                //with cool demand
+}
+
+impl Supply {
+    /// ID_SUPPLY payload: the boiler relay state in the 1st byte, the forward temperature setpoint
+    /// (i16, little endian, 0x8000 = none) in the next 2 bytes, the number of the heating zones in the 4th byte,
+    /// the number of the cooling zones in the 5th byte
+    pub fn encode(&self) -> [u8; 8] {
+        let forward = encode_temperature(self.forward_temperature);
+        [
+            self.boiler_on as u8,
+            forward[0],
+            forward[1],
+            self.zones,
+            self.cooling_zones,
+            0,
+            0,
+            0,
+        ]
+    }
+
+    pub fn decode(data: u64) -> Self {
+        Supply {
+            boiler_on: data & 0xFF != 0,
+            forward_temperature: decode_temperature(data >> 8),
+            zones: (data >> 24) as u8,
+            cooling_zones: (data >> 32) as u8,
+        }
+    }
+}
+
+pub struct CoordinatorConfig<Temperature, Duration> {
+    pub min_on: Duration,
+    pub min_off: Duration,
+    pub zone_timeout: Duration, //the demand of a zone is dropped without refresh
+    pub default_forward_temperature: Temperature, //if none of the zones requires one
+    pub max_forward_temperature: Temperature,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Relay<Duration> {
+    Off(Duration), //since the switch off
+    On(Duration),  //since the switch on
+}
+
+pub struct Coordinator<Duration> {
+    pub relay: Relay<Duration>,
+    zones: [Option<(Demand, Duration)>; MAX_ZONES], //the last demand, its age
+}
+
+impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>>
+    Coordinator<Duration>
+{
+    /// The min off time is kept after a restart too
+    pub fn new() -> Self {
+        Coordinator {
+            relay: Relay::Off(Duration::default()),
+            zones: [None; MAX_ZONES],
+        }
+    }
+
+    pub fn demand_received(&mut self, zone: usize, demand: Demand) {
+        if zone < MAX_ZONES {
+            self.zones[zone] = Some((demand, Duration::default()));
+        }
+    }
+
+    /// The last demand of the zone, None if it is silent
+    pub fn zone(&self, zone: usize) -> Option<Demand> {
+        self.zones[zone].map(|(demand, _)| demand)
+    }
+
+    /// The number of the zones with heat demand (these switch the boiler)
+    pub fn demanding(&self) -> u8 {
+        self.zones
+            .iter()
+            .flatten()
+            .filter(|(demand, _)| demand.heat)
+            .count() as u8
+    }
+
+    /// The number of the zones with cool demand
+    pub fn cooling(&self) -> u8 {
+        self.zones
+            .iter()
+            .flatten()
+            .filter(|(demand, _)| demand.cool)
+            .count() as u8
+    }
+
+    pub fn boiler_on(&self) -> bool {
+        match self.relay {
+            Relay::On(_) => true,
+            Relay::Off(_) => false,
+        }
+    }
+
+    /// The highest forward temperature required by the heating zones, limited to the max
+    pub fn forward_temperature<Temperature: FixedPoint>(
+        &self,
+        config: &CoordinatorConfig<Temperature, Duration>,
+    ) -> Option<i32> {
+        let required = self
+            .zones
+            .iter()
+            .flatten()
+            .filter(|(demand, _)| demand.heat)
+            .map(|(demand, _)| {
+                demand
+                    .forward_temperature
+                    .unwrap_or(config.default_forward_temperature.to_sixteenths())
+            })
+            .max()?;
+        let max = config.max_forward_temperature.to_sixteenths();
+        Some(if required > max { max } else { required })
+    }
+
+    pub fn supply<Temperature: FixedPoint>(
+        &self,
+        config: &CoordinatorConfig<Temperature, Duration>,
+    ) -> Supply {
+        Supply {
+            boiler_on: self.boiler_on(),
+            forward_temperature: self.forward_temperature(config),
+            zones: self.demanding(),
+            cooling_zones: self.cooling(),
+        }
+    }
+
+    /// This should be called regurarily, returns true if the relay was switched
+    pub fn update<Temperature>(
+        &mut self,
+        config: &CoordinatorConfig<Temperature, Duration>,
+        delta_time: Duration,
+    ) -> bool {
+        for zone in self.zones.iter_mut() {
+            *zone = match *zone {
+                Some((demand, age)) if age + delta_time < config.zone_timeout => {
+                    Some((demand, age + delta_time))
+                }
+                _ => None,
+            };
+        }
+
+        let wanted = self.demanding() > 0;
+        let (relay, switched) = match self.relay {
+            Relay::Off(since) if wanted && since >= config.min_off => {
+                (Relay::On(Duration::default()), true)
+            }
+            Relay::On(since) if !wanted && since >= config.min_on => {
+                (Relay::Off(Duration::default()), true)
+            }
+            Relay::Off(since) => (Relay::Off(since + delta_time), false),
+            Relay::On(since) => (Relay::On(since + delta_time), false),
+        };
+        self.relay = relay;
+        switched
+    }
+}
+
+pub struct LinkConfig<Duration> {
+    pub period: Duration, //of the demand refresh, well below the zone timeout of the coordinator
+    pub coordinator_timeout: Duration, //the supply status is dropped after this
+}
+
+/// The zone side of the protocol
+pub struct DemandLink<Duration> {
+    supply: Option<(Supply, Duration)>, //the last status, its age
+    sent: Option<(Demand, Duration)>,   //the last published demand, its age
+}
+
+impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>>
+    DemandLink<Duration>
+{
+    pub fn new() -> Self {
+        DemandLink {
+            supply: None,
+            sent: None,
+        }
+    }
+
+    pub fn supply_received(&mut self, data: u64) {
+        self.supply = Some((Supply::decode(data), Duration::default()));
+    }
+
+    pub fn supply(&self) -> Option<Supply> {
+        self.supply.map(|(supply, _)| supply)
+    }
+
+    /// False means the zone should drive the hardwired heat request line
+    pub fn coordinator_present(&self) -> bool {
+        self.supply.is_some()
+    }
+
+    /// This should be called regurarily
+    pub fn update(&mut self, config: &LinkConfig<Duration>, delta_time: Duration) {
+        self.supply = match self.supply {
+            Some((supply, age)) if age + delta_time < config.coordinator_timeout => {
+                Some((supply, age + delta_time))
+            }
+            _ => None,
+        };
+        if let Some((demand, age)) = self.sent {
+            self.sent = Some((demand, age + delta_time));
+        }
+    }
+
+    /// Returns the demand if it should be sent: changed or the refresh period is over
+    pub fn publish(&mut self, config: &LinkConfig<Duration>, demand: Demand) -> Option<Demand> {
+        match self.sent {
+            Some((sent, age)) if sent == demand && age < config.period => None,
+            _ => {
+                self.sent = Some((demand, Duration::default()));
+                Some(demand)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use onewire::temperature::Temperature;
+
+    fn config() -> CoordinatorConfig<Temperature, u32> {
+        CoordinatorConfig {
+            min_on: 60,
+            min_off: 60,
+            zone_timeout: 300,
+            default_forward_temperature: Temperature::from_celsius(35, 0),
+            max_forward_temperature: Temperature::from_celsius(45, 0),
+        }
+    }
+
+    fn demand(heat: bool, cool: bool) -> Demand {
+        Demand {
+            heat: heat,
+            cool: cool,
+            forward_temperature: None,
+        }
+    }
+
+    #[test]
+    fn cooling_does_not_switch_the_boiler() {
+        let config = config();
+        let mut coordinator = Coordinator::<u32>::new();
+        coordinator.demand_received(0, demand(false, true));
+        coordinator.demand_received(1, demand(false, true));
+        for _ in 0..5 {
+            coordinator.update(&config, 30); //over the min off time
+        }
+        assert!(!coordinator.boiler_on());
+        assert!(coordinator.demanding() == 0);
+        assert!(coordinator.cooling() == 2);
+
+        coordinator.demand_received(2, demand(true, false));
+        assert!(coordinator.update(&config, 30));
+        assert!(coordinator.boiler_on());
+    }
+
+    #[test]
+    fn supply_reports_cooling_separately() {
+        let mut coordinator = Coordinator::<u32>::new();
+        coordinator.demand_received(0, demand(true, false));
+        coordinator.demand_received(1, demand(false, true));
+        let supply = coordinator.supply(&config());
+        assert!(supply.zones == 1 && supply.cooling_zones == 1);
+        assert!(supply.forward_temperature == Some(35 * 16));
+        assert!(Supply::decode(u64::from_le_bytes(supply.encode())) == supply);
+    }
+}
@@ -0,0 +1,111 @@
+//! Weather compensated heating curve: the desired forward temperature grows as the outdoor
+//! temperature drops, corrected by the missing room temperature.
+//! forward = target + offset + slope * (target - outdoor) + room_factor * (target - room)
+
+use crate::temperature::FixedPoint;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Modulation {
+    HeatRequest, //the heat request is withdrawn while the forward temperature is too high
+    Valve,       //the valve is closed too while the forward temperature is too high
+}
+
+#[derive(Clone)]
+pub struct Config<Temperature> {
+    pub enabled: bool,
+    pub slope: i32, //forward increase per outdoor degree drop in 1/10
+    pub offset: Temperature,
+    pub room_factor: i32, //forward increase per missing room degree in 1/10
+    pub min_forward_temperature: Temperature,
+    pub histeresis: Temperature,
+    pub modulation: Modulation,
+}
+
+/// Desired forward temperature in 1/16 degree units limited to the min..max forward temperature
+pub fn forward_temperature<Temperature: FixedPoint>(
+    config: &Config<Temperature>,
+    max_forward_temperature: &Temperature,
+    target_air_temperature: &Temperature,
+    outdoor_temperature: &Temperature,
+    air_temperature: Option<&Temperature>,
+) -> i32 {
+    let target = target_air_temperature.to_sixteenths();
+    let mut forward = target
+        + config.offset.to_sixteenths()
+        + config.slope * (target - outdoor_temperature.to_sixteenths()) / 10;
+
+    if let Some(air) = air_temperature {
+        forward += config.room_factor * (target - air.to_sixteenths()) / 10;
+    }
+
+    let min = config.min_forward_temperature.to_sixteenths();
+    let max = max_forward_temperature.to_sixteenths();
+    if forward > max {
+        max
+    } else if forward < min {
+        min
+    } else {
+        forward
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use onewire::temperature::Temperature;
+
+    fn celsius(degrees: i32) -> Temperature {
+        Temperature::from_sixteenths(degrees * 16)
+    }
+
+    fn config(slope: i32, offset: i32, room_factor: i32) -> Config<Temperature> {
+        Config {
+            enabled: true,
+            slope: slope,
+            offset: celsius(offset),
+            room_factor: room_factor,
+            min_forward_temperature: celsius(25),
+            histeresis: celsius(2),
+            modulation: Modulation::HeatRequest,
+        }
+    }
+
+    fn forward(config: &Config<Temperature>, outdoor: Temperature, air: Option<i32>) -> i32 {
+        let air = air.map(celsius);
+        forward_temperature(config, &celsius(45), &celsius(20), &outdoor, air.as_ref())
+    }
+
+    #[test]
+    fn slope() {
+        //20 + 1.2 * (20 - 0)
+        assert!(forward(&config(12, 0, 0), celsius(0), None) == 44 * 16);
+        //20 + 1.2 * (20 - 7.5)
+        let outdoor = Temperature::from_sixteenths(7 * 16 + 8);
+        assert!(forward(&config(12, 0, 0), outdoor, None) == 35 * 16);
+    }
+
+    #[test]
+    fn offset() {
+        //20 + 2 + 1.0 * (20 - 5)
+        assert!(forward(&config(10, 2, 0), celsius(5), None) == 37 * 16);
+        assert!(forward(&config(10, -3, 0), celsius(5), None) == 32 * 16);
+    }
+
+    #[test]
+    fn room_factor() {
+        //20 + 1.0 * (20 - 5) + 3.0 * (20 - 19)
+        assert!(forward(&config(10, 0, 30), celsius(5), Some(19)) == 38 * 16);
+        assert!(forward(&config(10, 0, 30), celsius(5), Some(21)) == 32 * 16);
+        //without an air sensor
+        assert!(forward(&config(10, 0, 30), celsius(5), None) == 35 * 16);
+    }
+
+    #[test]
+    fn limits() {
+        //20 + 2 + 1.0 * (20 - 30) is below the min
+        assert!(forward(&config(10, 2, 0), celsius(30), None) == 25 * 16);
+        //20 + 2 + 1.0 * (20 + 20) is above the max
+        assert!(forward(&config(10, 2, 0), celsius(-20), None) == 45 * 16);
+        assert!(forward(&config(10, 0, 30), celsius(5), Some(10)) == 45 * 16);
+    }
+}
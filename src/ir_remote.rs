@@ -1,4 +1,6 @@
-#[derive(Debug, PartialEq)]
+use crate::codec::{Overflow, Reader, Writer};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum IrCommands {
     N0,
     N1,
@@ -41,49 +43,199 @@ pub enum IrCommands {
     Unknown,
 }
 
-pub fn translate(data: u32) -> IrCommands {
-    match data >> 8 {
-        0x807F02 => IrCommands::Power,
-        0x807FAA => IrCommands::Power_,
+pub const COMMAND_COUNT: usize = 31; //without Unknown
+
+/// In the order of the declaration
+pub const COMMANDS: [IrCommands; COMMAND_COUNT] = [
+    IrCommands::N0,
+    IrCommands::N1,
+    IrCommands::N2,
+    IrCommands::N3,
+    IrCommands::N4,
+    IrCommands::N5,
+    IrCommands::N6,
+    IrCommands::N7,
+    IrCommands::N8,
+    IrCommands::N9,
+    IrCommands::Ok,
+    IrCommands::Up,
+    IrCommands::Down,
+    IrCommands::Left,
+    IrCommands::Right,
+    IrCommands::Home,
+    IrCommands::Back,
+    IrCommands::Menu,
+    IrCommands::Click,
+    IrCommands::Mute,
+    IrCommands::Backspace,
+    IrCommands::Power,
+    IrCommands::Red,
+    IrCommands::Green,
+    IrCommands::Yellow,
+    IrCommands::Blue,
+    IrCommands::Power_,
+    IrCommands::Set_,
+    IrCommands::TVIn_,
+    IrCommands::VolDown_,
+    IrCommands::VolUp_,
+];
+
+impl IrCommands {
+    /// Value of the numeric keys
+    pub fn digit(&self) -> Option<u8> {
+        match self {
+            IrCommands::N0 => Some(0),
+            IrCommands::N1 => Some(1),
+            IrCommands::N2 => Some(2),
+            IrCommands::N3 => Some(3),
+            IrCommands::N4 => Some(4),
+            IrCommands::N5 => Some(5),
+            IrCommands::N6 => Some(6),
+            IrCommands::N7 => Some(7),
+            IrCommands::N8 => Some(8),
+            IrCommands::N9 => Some(9),
+            _ => None,
+        }
+    }
 
-        0x807F9A => IrCommands::Set_,
-        0x807F1A => IrCommands::TVIn_,
-        0x807FEA => IrCommands::VolDown_,
-        0x807F6A => IrCommands::VolUp_,
+    /// Code for the key map encoding, Unknown has no code
+    pub fn code(self) -> Option<u8> {
+        match self {
+            IrCommands::Unknown => None,
+            command => Some(command as u8),
+        }
+    }
 
-        0x807Fc2 => IrCommands::Red,
-        0x807Ff0 => IrCommands::Green,
-        0x807F08 => IrCommands::Yellow,
-        0x807F18 => IrCommands::Blue,
+    pub fn from_code(code: u8) -> Option<Self> {
+        COMMANDS.get(code as usize).copied()
+    }
+}
 
-        0x807F88 => IrCommands::Home,
-        0x807F98 => IrCommands::Back,
-        0x807F32 => IrCommands::Menu,
-        0x807F00 => IrCommands::Click,
+/// The keys of the default remote by the NEC address and command (the data without the inverted command byte)
+static DEFAULT_KEYS: [(u32, IrCommands); COMMAND_COUNT] = [
+    (0x807F02, IrCommands::Power),
+    (0x807FAA, IrCommands::Power_),
+    (0x807F9A, IrCommands::Set_),
+    (0x807F1A, IrCommands::TVIn_),
+    (0x807FEA, IrCommands::VolDown_),
+    (0x807F6A, IrCommands::VolUp_),
+    (0x807Fc2, IrCommands::Red),
+    (0x807Ff0, IrCommands::Green),
+    (0x807F08, IrCommands::Yellow),
+    (0x807F18, IrCommands::Blue),
+    (0x807F88, IrCommands::Home),
+    (0x807F98, IrCommands::Back),
+    (0x807F32, IrCommands::Menu),
+    (0x807F00, IrCommands::Click),
+    (0x807Fc8, IrCommands::Ok),
+    (0x807F68, IrCommands::Up),
+    (0x807F58, IrCommands::Down),
+    (0x807F8A, IrCommands::Left),
+    (0x807F0A, IrCommands::Right),
+    (0x807F72, IrCommands::N1),
+    (0x807Fb0, IrCommands::N2),
+    (0x807F30, IrCommands::N3),
+    (0x807F52, IrCommands::N4),
+    (0x807F90, IrCommands::N5),
+    (0x807F10, IrCommands::N6),
+    (0x807F62, IrCommands::N7),
+    (0x807Fa0, IrCommands::N8),
+    (0x807F20, IrCommands::N9),
+    (0x807F82, IrCommands::Mute),
+    (0x807F80, IrCommands::N0),
+    (0x807F42, IrCommands::Backspace),
+];
+
+pub const MAX_KEYS: usize = 40;
+
+/// Key codes of the remote to the commands, so other remotes can be used
+pub struct KeyMap {
+    keys: [(u32, IrCommands); MAX_KEYS],
+    count: usize,
+}
+
+impl KeyMap {
+    pub fn new() -> Self {
+        KeyMap {
+            keys: [(0, IrCommands::Unknown); MAX_KEYS],
+            count: 0,
+        }
+    }
+
+    /// The keys of the default remote
+    pub fn with_defaults() -> Self {
+        let mut map = KeyMap::new();
+        for (code, command) in DEFAULT_KEYS.iter() {
+            map.set(*code, *command);
+        }
+        map
+    }
 
-        0x807Fc8 => IrCommands::Ok,
+    pub fn len(&self) -> usize {
+        self.count
+    }
 
-        0x807F68 => IrCommands::Up,
-        0x807F58 => IrCommands::Down,
-        0x807F8A => IrCommands::Left,
-        0x807F0A => IrCommands::Right,
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
 
-        0x807F72 => IrCommands::N1,
-        0x807Fb0 => IrCommands::N2,
-        0x807F30 => IrCommands::N3,
+    pub fn iter(&self) -> impl Iterator<Item = &(u32, IrCommands)> {
+        self.keys[..self.count].iter()
+    }
 
-        0x807F52 => IrCommands::N4,
-        0x807F90 => IrCommands::N5,
-        0x807F10 => IrCommands::N6,
+    /// Adds or replaces the key, returns false if the map is full
+    pub fn set(&mut self, code: u32, command: IrCommands) -> bool {
+        if let Some(key) = self.keys[..self.count]
+            .iter_mut()
+            .find(|(key_code, _)| *key_code == code)
+        {
+            key.1 = command;
+            return true;
+        }
+        if self.count >= MAX_KEYS {
+            return false;
+        }
+        self.keys[self.count] = (code, command);
+        self.count += 1;
+        true
+    }
 
-        0x807F62 => IrCommands::N7,
-        0x807Fa0 => IrCommands::N8,
-        0x807F20 => IrCommands::N9,
+    /// The command of the received NEC data
+    pub fn translate(&self, data: u32) -> IrCommands {
+        lookup(self.iter(), data)
+    }
 
-        0x807F82 => IrCommands::Mute,
-        0x807F80 => IrCommands::N0,
-        0x807F42 => IrCommands::Backspace,
+    /// The number of the keys, then the code (u32) and the command code (u8) of each
+    pub fn encode(&self, writer: &mut Writer) -> Result<(), Overflow> {
+        writer.u8(self.count as u8)?;
+        for (code, command) in self.iter() {
+            writer.u32(*code)?;
+            writer.u8(command.code().unwrap_or(u8::MAX))?;
+        }
+        Ok(())
+    }
 
-        _ => IrCommands::Unknown,
+    /// None if the data is short, a command is unknown or there are too many keys
+    pub fn decode(reader: &mut Reader) -> Option<Self> {
+        let mut map = KeyMap::new();
+        for _ in 0..reader.u8()? {
+            let code = reader.u32()?;
+            let command = IrCommands::from_code(reader.u8()?)?;
+            if !map.set(code, command) {
+                return None;
+            }
+        }
+        Some(map)
     }
 }
+
+fn lookup<'a, I: Iterator<Item = &'a (u32, IrCommands)>>(mut keys: I, data: u32) -> IrCommands {
+    keys.find(|(code, _)| *code == data >> 8)
+        .map(|(_, command)| *command)
+        .unwrap_or(IrCommands::Unknown)
+}
+
+/// The command of the default remote
+pub fn translate(data: u32) -> IrCommands {
+    lookup(DEFAULT_KEYS.iter(), data)
+}
@@ -1,20 +1,41 @@
 //#![deny(unsafe_code)]
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 pub mod ac_sense;
 pub mod ac_switch;
+pub mod backlight;
+pub mod codec;
+pub mod config_format;
+pub mod config_store;
+pub mod cooling;
 pub mod dac;
 pub mod display;
+pub mod exercise;
 pub mod floor_heating;
+pub mod heat_demand;
+pub mod heating_curve;
 pub mod ir;
 pub mod ir_remote;
 pub mod light_control;
 pub mod menu;
 pub mod messenger;
+pub mod open_window;
+pub mod optimum_start;
+pub mod pid;
+pub mod plausibility;
 pub mod pump;
+pub mod retained;
 pub mod rgb;
+pub mod schedule;
+pub mod screed;
+pub mod screens;
+pub mod sensors;
+pub mod statistics;
+pub mod temperature;
 pub mod timing;
 pub mod valve;
+pub mod virtual_display;
+pub mod zones;
 
 // #[cfg(test)]
 // mod test {
@@ -1,3 +1,5 @@
+use core::ops::Add;
+
 pub struct Menu<'a, M, C> {
     pub rows: &'a [Row<'a, M, C>],
 }
@@ -15,4 +17,203 @@ pub enum Content<'a, M, C> {
 pub struct Item<M, C> {
     pub update: fn(model: &mut M, command: C),
     pub view: fn(model: &M) -> &[u8],
+    pub entry: Option<NumericEntry<M>>, //direct numeric entry if supported
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    /// The last digit is the fraction: 215 => 21.5, the value is in tenths
+    Decimal,
+    /// Colon before the last two digits: 630 => 06:30, the value is 6 * 60 + 30
+    /// (minutes of hh:mm or seconds of mm:ss)
+    Time,
+    /// Plain number
+    Integer,
+}
+
+pub struct NumericEntry<M> {
+    pub format: Format,
+    pub min: i32,
+    pub max: i32,
+    pub set: fn(model: &mut M, value: i32),
+}
+
+impl<M> NumericEntry<M> {
+    /// Sets the value if it is valid, returns false otherwise
+    pub fn apply<D>(&self, model: &mut M, entry: &Entry<D>) -> bool {
+        if let Some(value) = entry.value() {
+            if self.min <= value && value <= self.max {
+                (self.set)(model, value);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Digits typed in so far
+pub struct Entry<Duration> {
+    pub format: Format,
+    value: u32,
+    digits: u8,
+    idle: Duration, //since the last key press
+}
+
+impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>> Entry<Duration> {
+    pub fn new(format: Format) -> Self {
+        Entry {
+            format: format,
+            value: 0,
+            digits: 0,
+            idle: Duration::default(),
+        }
+    }
+
+    /// Appends the digit if there is room for it
+    pub fn push(&mut self, digit: u8) {
+        self.idle = Duration::default();
+        if self.digits < self.max_digits() {
+            self.value = self.value * 10 + digit as u32;
+            self.digits += 1;
+        }
+    }
+
+    /// Removes the last digit, returns false if there was nothing to remove
+    pub fn backspace(&mut self) -> bool {
+        self.idle = Duration::default();
+        if self.digits == 0 {
+            return false;
+        }
+        self.value /= 10;
+        self.digits -= 1;
+        true
+    }
+
+    /// This should be called regurarily, returns true if the entry timed out
+    pub fn update(&mut self, timeout: Duration, delta_time: Duration) -> bool {
+        self.idle = self.idle + delta_time;
+        self.idle > timeout
+    }
+}
+
+impl<Duration> Entry<Duration> {
+    fn max_digits(&self) -> u8 {
+        match self.format {
+            Format::Decimal => 3,
+            Format::Time => 4,
+            Format::Integer => 4,
+        }
+    }
+
+    /// The typed value in the unit of the format, None if nothing was typed or invalid
+    pub fn value(&self) -> Option<i32> {
+        if self.digits == 0 {
+            return None;
+        }
+        match self.format {
+            Format::Time => {
+                let (hour, min) = (self.value / 100, self.value % 100);
+                if hour < 24 && min < 60 {
+                    Some((hour * 60 + min) as i32)
+                } else {
+                    None
+                }
+            }
+            _ => Some(self.value as i32),
+        }
+    }
+
+    /// Formats the typed digits followed by a cursor: "2.1_", "06:30_"
+    pub unsafe fn fmt(&self) -> &'static [u8] {
+        static mut TEXT: [u8; 6] = [0u8; 6];
+        let v = self.value;
+        let len = match self.format {
+            Format::Decimal => {
+                let mut i = 0;
+                if v >= 100 {
+                    TEXT[i] = '0' as u8 + (v / 100) as u8;
+                    i += 1;
+                }
+                TEXT[i] = '0' as u8 + ((v / 10) % 10) as u8;
+                TEXT[i + 1] = '.' as u8;
+                TEXT[i + 2] = '0' as u8 + (v % 10) as u8;
+                i + 3
+            }
+            Format::Time => {
+                TEXT[0] = '0' as u8 + (v / 1000) as u8;
+                TEXT[1] = '0' as u8 + ((v / 100) % 10) as u8;
+                TEXT[2] = ':' as u8;
+                TEXT[3] = '0' as u8 + ((v / 10) % 10) as u8;
+                TEXT[4] = '0' as u8 + (v % 10) as u8;
+                5
+            }
+            Format::Integer => {
+                let mut i = 0;
+                let mut d = 1000;
+                while d > 1 && v < d {
+                    d /= 10;
+                }
+                while d > 0 {
+                    TEXT[i] = '0' as u8 + ((v / d) % 10) as u8;
+                    i += 1;
+                    d /= 10;
+                }
+                i
+            }
+        };
+        TEXT[len] = '_' as u8;
+        &TEXT[..len + 1]
+    }
+}
+
+impl<'a, M, C> Menu<'a, M, C> {
+    /// Renders the rows around the selected one, the item values are right aligned.
+    /// The entry in progress is shown in place of the selected item value.
+    pub fn render<D: lcd_hal::Display, T>(
+        &self,
+        model: &M,
+        selected_row: usize,
+        entry: Option<&Entry<T>>,
+        display: &mut D,
+    ) -> Result<(), D::Error> {
+        display.clear()?;
+
+        let (cols, rows) = display.get_char_resolution();
+        let (colsx, _) = display.get_pixel_resolution();
+        let colc = colsx / cols;
+        let rows = rows as usize;
+
+        let start_index = if selected_row >= rows {
+            selected_row - rows + 1
+        } else {
+            0
+        };
+
+        for row in 0..rows {
+            let index = row + start_index;
+            if index >= self.rows.len() {
+                break;
+            }
+
+            display.set_position(0, row as u8)?;
+
+            display.print_char(if selected_row == index {
+                '>' as u8
+            } else {
+                ' ' as u8
+            })?;
+            display.print(self.rows[index].text)?;
+
+            if let Content::MenuItem(ref item) = self.rows[index].content {
+                display.print_char(':' as u8)?;
+                let content = match entry {
+                    Some(entry) if selected_row == index => unsafe { entry.fmt() },
+                    _ => (item.view)(model),
+                };
+                display.set_position(colsx - colc * content.len() as u8, row as u8)?;
+                display.print(content)?;
+            }
+        }
+        Ok(())
+    }
 }
@@ -22,6 +22,7 @@ pub const ID_OPEN: Id = Id::new_standard(0x600);
 pub const ID_IR: Id = Id::new_standard(0x500);
 pub const ID_SWITCH: Id = Id::new_standard(0x400);
 pub const ID_TEMPERATURE: Id = Id::new_standard(0x300);
+pub const ID_HUMIDITY: Id = Id::new_standard(0x280);
 pub const ID_LUX: Id = Id::new_standard(0x200);
 
 //actors
@@ -33,10 +34,42 @@ pub const ID_SOUND: Id = Id::new_standard(0x050);
 pub const ID_RGB: Id = Id::new_standard(0x040);
 pub const ID_LED: Id = Id::new_standard(0x030);
 
+//heating
+pub const ID_SUPPLY: Id = Id::new_standard(0x0B0);
+pub const ID_HEAT_DEMAND: Id = Id::new_standard(0x0C0); //+ the zone, see heat_demand::MAX_ZONES
+
 //common
 pub const ID_DATE: Id = Id::new_standard(0x020);
 pub const ID_TIME: Id = Id::new_standard(0x010);
 
+//ID_TEMPERATURE payload: signed 1/16 degree value in the lower 2 bytes (little endian),
+//the location of the sensor in the 3rd byte
+pub const LOCATION_OUTDOOR: u8 = 0;
+
+/// Decodes the temperature report to (1/16 degree value, location)
+pub fn decode_temperature(data: u64) -> (i32, u8) {
+    ((data & 0xFFFF) as u16 as i16 as i32, (data >> 16) as u8)
+}
+
+//ID_OPEN payload: 0 = closed, other = open in the 1st byte, the location (room) in the 2nd byte
+
+/// Decodes the window/door contact report to (open, location)
+pub fn decode_open(data: u64) -> (bool, u8) {
+    (data & 0xFF != 0, (data >> 8) as u8)
+}
+
+//ID_HUMIDITY payload: relative humidity in % in the 1st byte, the location in the 2nd byte
+
+/// Decodes the humidity report to (relative humidity %, location)
+pub fn decode_humidity(data: u64) -> (u8, u8) {
+    (data as u8, (data >> 8) as u8)
+}
+
+/// The ID of the heat demand of the zone (0..7)
+pub fn heat_demand_id(zone: u8) -> Id {
+    Id::new_standard(0x0C0 + zone as u32)
+}
+
 const CAN_CONFIG: Configuration = Configuration {
     time_triggered_communication_mode: false,
     automatic_bus_off_management: true,
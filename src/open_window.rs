@@ -0,0 +1,198 @@
+//! Open window detection: the heating is suspended while a window is open.
+//! The window is detected by a window contact (ID_OPEN CAN message) or by a fast drop of the air temperature.
+//! The detection by the temperature ends after the suspend time or when the air starts warming up again,
+//! the contact keeps the heating suspended until the window is closed.
+//! The temperatures are compared in 1/16 degree units.
+
+use crate::temperature::FixedPoint;
+use core::ops::Add;
+
+pub struct Config<Temperature, Duration> {
+    pub enabled: bool,     //detection by the air temperature
+    pub drop: Temperature, //within the drop period means an open window
+    pub drop_period: Duration,
+    pub recovery: Temperature, //rise from the lowest temperature means a closed window
+    pub suspend_duration: Duration, //max, if detected by the temperature
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Source {
+    Contact,
+    Temperature,
+}
+
+pub struct OpenWindow<Duration> {
+    contact: bool,                      //the window contact reported open
+    reference: Option<(i32, Duration)>, //air temperature at the start of the drop period, elapsed time
+    detected: Option<(i32, Duration)>,  //by the temperature: lowest air temperature, elapsed time
+}
+
+impl<Duration: Copy + PartialOrd + Default + Add<Duration, Output = Duration>>
+    OpenWindow<Duration>
+{
+    pub fn new() -> Self {
+        OpenWindow {
+            contact: false,
+            reference: None,
+            detected: None,
+        }
+    }
+
+    /// The heating should be suspended
+    pub fn open(&self) -> bool {
+        self.source().is_some()
+    }
+
+    pub fn source(&self) -> Option<Source> {
+        if self.contact {
+            Some(Source::Contact)
+        } else if self.detected.is_some() {
+            Some(Source::Temperature)
+        } else {
+            None
+        }
+    }
+
+    /// Time since the window was detected open by the temperature
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.detected.map(|(_, elapsed)| elapsed)
+    }
+
+    /// The window contact state received
+    pub fn set_contact(&mut self, open: bool) {
+        self.contact = open;
+        if !open {
+            //the window is closed, the temperature drop is over
+            self.detected = None;
+            self.reference = None;
+        }
+    }
+
+    /// This should be called regurarily with the air temperature
+    pub fn update<Temperature: FixedPoint>(
+        &mut self,
+        config: &Config<Temperature, Duration>,
+        air: Option<Temperature>,
+        delta_time: Duration,
+    ) {
+        let air = match air {
+            Some(air) if config.enabled => air.to_sixteenths(),
+            _ => {
+                self.reference = None;
+                self.detected = None;
+                return;
+            }
+        };
+
+        if let Some((lowest, elapsed)) = self.detected {
+            let elapsed = elapsed + delta_time;
+            self.detected = if elapsed >= config.suspend_duration
+                || air >= lowest + config.recovery.to_sixteenths()
+            {
+                //start the drop period again, so the same drop is not detected again
+                self.reference = Some((air, Duration::default()));
+                None
+            } else {
+                Some((if air < lowest { air } else { lowest }, elapsed))
+            };
+            return;
+        }
+
+        self.reference = match self.reference {
+            Some((reference, elapsed)) => {
+                if reference - air >= config.drop.to_sixteenths() {
+                    self.detected = Some((air, Duration::default()));
+                    None
+                } else {
+                    let elapsed = elapsed + delta_time;
+                    if elapsed >= config.drop_period || air > reference {
+                        Some((air, Duration::default()))
+                    } else {
+                        Some((reference, elapsed))
+                    }
+                }
+            }
+            None => Some((air, Duration::default())),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use onewire::temperature::Temperature;
+
+    fn config() -> Config<Temperature, u32> {
+        Config {
+            enabled: true,
+            drop: Temperature::from_sixteenths(16),
+            drop_period: 300,
+            recovery: Temperature::from_sixteenths(8),
+            suspend_duration: 1800,
+        }
+    }
+
+    fn update(open_window: &mut OpenWindow<u32>, sixteenths: i32) {
+        let air = Temperature::from_sixteenths(sixteenths);
+        open_window.update(&config(), Some(air), 60);
+    }
+
+    /// Detects a drop from 21 to 20 degree in two minutes
+    fn detect(open_window: &mut OpenWindow<u32>) {
+        for air in [21 * 16, 20 * 16 + 8, 20 * 16].iter() {
+            update(open_window, *air);
+        }
+        assert!(open_window.source() == Some(Source::Temperature));
+        assert!(open_window.elapsed() == Some(0));
+    }
+
+    #[test]
+    fn drop_ends_after_the_suspend_time() {
+        let mut open_window = OpenWindow::new();
+        detect(&mut open_window);
+        for _ in 0..29 {
+            update(&mut open_window, 20 * 16);
+        }
+        assert!(open_window.open());
+        update(&mut open_window, 20 * 16);
+        assert!(!open_window.open() && open_window.elapsed().is_none());
+
+        //the same drop is not detected again
+        update(&mut open_window, 20 * 16);
+        assert!(!open_window.open());
+    }
+
+    #[test]
+    fn drop_ends_when_warming_up() {
+        let mut open_window = OpenWindow::new();
+        detect(&mut open_window);
+        update(&mut open_window, 19 * 16);
+        update(&mut open_window, 19 * 16 + 7);
+        assert!(open_window.open()); //below the recovery from the lowest
+        update(&mut open_window, 19 * 16 + 8);
+        assert!(!open_window.open());
+    }
+
+    #[test]
+    fn slow_cooling_is_not_a_window() {
+        let mut open_window = OpenWindow::new();
+        for minute in 0..30 {
+            update(&mut open_window, 21 * 16 - 2 * minute); //0.125 degree per minute
+            assert!(!open_window.open());
+        }
+    }
+
+    #[test]
+    fn contact_keeps_it_open() {
+        let mut open_window = OpenWindow::new();
+        detect(&mut open_window);
+        open_window.set_contact(true);
+        assert!(open_window.source() == Some(Source::Contact));
+        for _ in 0..60 {
+            update(&mut open_window, 20 * 16);
+        }
+        assert!(open_window.source() == Some(Source::Contact));
+        open_window.set_contact(false);
+        assert!(!open_window.open());
+    }
+}
@@ -0,0 +1,209 @@
+//! Adaptive optimum start: the heating is started before the programmed comfort period,
+//! early enough to reach the target temperature at the programmed time.
+//! The heat-up rate is learned from the previous heat-ups separately for the ranges of the
+//! indoor - outdoor temperature difference (the rate drops as the heat loss grows).
+//! The temperatures are given in 1/16 degree units, the rates in 1/16 degree per hour.
+
+pub const BUCKET_COUNT: usize = 6;
+const BUCKET_WIDTH: i32 = 5 * 16; //of the indoor - outdoor difference
+const UNKNOWN_OUTDOOR_BUCKET: usize = 2; //used without outdoor temperature
+const MIN_RATE: i32 = 2; //1/8 degree per hour
+const LEARNING_WEIGHT: i32 = 4; //the new sample counts 1/4 in the learned rate
+
+pub struct Config<Duration> {
+    pub enabled: bool,
+    pub max_preheat: Duration,
+    pub default_rate: i32, //before learning
+}
+
+struct HeatUp {
+    bucket: usize,
+    start_temperature: i32,
+    target: i32,
+    elapsed: u32, //seconds
+}
+
+pub struct OptimumStart {
+    rates: [Option<i32>; BUCKET_COUNT], //learned rates
+    samples: [u16; BUCKET_COUNT],
+    heat_up: Option<HeatUp>, //in progress, it is measured
+}
+
+impl OptimumStart {
+    pub fn new() -> Self {
+        OptimumStart {
+            rates: [None; BUCKET_COUNT],
+            samples: [0; BUCKET_COUNT],
+            heat_up: None,
+        }
+    }
+
+    fn bucket(indoor: i32, outdoor: Option<i32>) -> usize {
+        match outdoor {
+            Some(outdoor) if indoor > outdoor => {
+                let bucket = ((indoor - outdoor) / BUCKET_WIDTH) as usize;
+                if bucket < BUCKET_COUNT {
+                    bucket
+                } else {
+                    BUCKET_COUNT - 1
+                }
+            }
+            Some(_) => 0,
+            None => UNKNOWN_OUTDOOR_BUCKET,
+        }
+    }
+
+    /// The learned rate of the bucket (if any) and the number of the samples
+    pub fn learned(&self, bucket: usize) -> (Option<i32>, u16) {
+        (self.rates[bucket], self.samples[bucket])
+    }
+
+    /// True while a heat-up is measured
+    pub fn learning(&self) -> bool {
+        self.heat_up.is_some()
+    }
+
+    pub fn rate<Duration>(
+        &self,
+        config: &Config<Duration>,
+        indoor: i32,
+        outdoor: Option<i32>,
+    ) -> i32 {
+        self.rates[Self::bucket(indoor, outdoor)].unwrap_or(config.default_rate)
+    }
+
+    /// Seconds required to heat up from the current to the target temperature
+    pub fn preheat_time<Duration: Copy + Into<u32>>(
+        &self,
+        config: &Config<Duration>,
+        current: i32,
+        target: i32,
+        outdoor: Option<i32>,
+    ) -> u32 {
+        if target <= current {
+            return 0;
+        }
+        let rate = self.rate(config, current, outdoor);
+        let rate = if rate < MIN_RATE { MIN_RATE } else { rate };
+        let seconds = (target - current) as u32 * 3600 / rate as u32;
+        let max_preheat: u32 = config.max_preheat.into();
+        if seconds > max_preheat {
+            max_preheat
+        } else {
+            seconds
+        }
+    }
+
+    /// Starts measuring the heat-up
+    pub fn start(&mut self, current: i32, target: i32, outdoor: Option<i32>) {
+        self.heat_up = if target > current {
+            Some(HeatUp {
+                bucket: Self::bucket(current, outdoor),
+                start_temperature: current,
+                target: target,
+                elapsed: 0,
+            })
+        } else {
+            None
+        };
+    }
+
+    /// This should be called regurarily with the current target and air temperature.
+    /// The measurement is dropped if the target has changed or it takes too long.
+    pub fn update<Duration: Copy + Into<u32>>(
+        &mut self,
+        config: &Config<Duration>,
+        target: Option<i32>,
+        air: Option<i32>,
+        delta_time: Duration,
+    ) {
+        let max_preheat: u32 = config.max_preheat.into();
+        let (bucket, measured) = match self.heat_up {
+            Some(ref mut heat_up) => {
+                heat_up.elapsed += delta_time.into();
+                if target != Some(heat_up.target) || heat_up.elapsed > 2 * max_preheat {
+                    self.heat_up = None;
+                    return;
+                }
+                match air {
+                    Some(air) if air >= heat_up.target && heat_up.elapsed > 0 => (
+                        heat_up.bucket,
+                        (air - heat_up.start_temperature) * 3600 / heat_up.elapsed as i32,
+                    ),
+                    _ => return,
+                }
+            }
+            None => return,
+        };
+
+        self.heat_up = None;
+        self.rates[bucket] = Some(match self.rates[bucket] {
+            Some(rate) => rate + (measured - rate) / LEARNING_WEIGHT,
+            None => measured,
+        });
+        self.samples[bucket] = self.samples[bucket].saturating_add(1);
+    }
+
+    /// Forgets the learned rates
+    pub fn reset(&mut self) {
+        *self = OptimumStart::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config<u32> {
+        Config {
+            enabled: true,
+            max_preheat: 4 * 3600,
+            default_rate: 16, //1 degree per hour
+        }
+    }
+
+    /// Heats up from 18 to 21 degree in the given minutes at 0 degree outdoor
+    fn heat_up(optimum_start: &mut OptimumStart, minutes: i32) {
+        optimum_start.start(18 * 16, 21 * 16, Some(0));
+        for minute in 1..=minutes {
+            let air = 18 * 16 + 3 * 16 * minute / minutes;
+            optimum_start.update(&config(), Some(21 * 16), Some(air), 60);
+        }
+    }
+
+    #[test]
+    fn lead_time_by_the_default_rate() {
+        let config = config();
+        let optimum_start = OptimumStart::new();
+        assert!(optimum_start.preheat_time(&config, 18 * 16, 21 * 16, Some(0)) == 3 * 3600);
+        assert!(optimum_start.preheat_time(&config, 14 * 16, 21 * 16, Some(0)) == 4 * 3600); //the max
+        assert!(optimum_start.preheat_time(&config, 21 * 16, 21 * 16, Some(0)) == 0);
+    }
+
+    #[test]
+    fn lead_time_by_the_learned_rate() {
+        let config = config();
+        let mut optimum_start = OptimumStart::new();
+        heat_up(&mut optimum_start, 90);
+        assert!(!optimum_start.learning());
+        assert!(optimum_start.learned(3) == (Some(32), 1)); //18 degree difference
+        assert!(optimum_start.preheat_time(&config, 18 * 16, 21 * 16, Some(0)) == 5400);
+        //the bucket without outdoor temperature is not learned yet
+        assert!(optimum_start.preheat_time(&config, 18 * 16, 21 * 16, None) == 3 * 3600);
+
+        //the new sample counts 1/4
+        heat_up(&mut optimum_start, 180);
+        assert!(optimum_start.learned(3) == (Some(28), 2));
+        assert!(optimum_start.preheat_time(&config, 18 * 16, 21 * 16, Some(0)) == 6171);
+    }
+
+    #[test]
+    fn measurement_is_dropped_on_target_change() {
+        let mut optimum_start = OptimumStart::new();
+        optimum_start.start(18 * 16, 21 * 16, Some(0));
+        assert!(optimum_start.learning());
+        optimum_start.update(&config(), Some(20 * 16), Some(19 * 16), 60);
+        assert!(!optimum_start.learning());
+        assert!(optimum_start.learned(3) == (None, 0));
+    }
+}
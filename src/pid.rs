@@ -0,0 +1,194 @@
+//! PID controller with time proportional output for slow processes (like screed floor heating)
+//! The temperatures are given in 1/16 degree units, the duty cycle is computed in per mille
+//! at the beginning of every cycle period, the output is on for duty * period in each cycle.
+
+use core::ops::{Add, Div, Mul, Sub};
+
+#[derive(Clone)]
+pub struct PidConfig<Duration> {
+    pub kp: i32,                //duty per mille / degree of error
+    pub ki: i32,                //duty per mille / (degree of error * hour)
+    pub kd: i32,                //duty per mille / (degree change per hour)
+    pub cycle_period: Duration, //of the time proportional output
+    pub min_pulse: Duration,    //shorter on or off pulses are skipped
+}
+
+pub struct Pid<Duration> {
+    integral: i64, //sum(error * dt) in 1/16 degree * seconds
+    last_measured: Option<i32>,
+    elapsed: Duration, //since the start of the cycle
+    on_time: Duration, //in the current cycle
+    duty: i32,         //per mille
+}
+
+const MAX_DUTY: i32 = 1000;
+
+impl<Duration> Pid<Duration>
+where
+    Duration: Copy
+        + PartialOrd
+        + Default
+        + Into<u32>
+        + Add<Duration, Output = Duration>
+        + Sub<Duration, Output = Duration>
+        + Mul<u32, Output = Duration>
+        + Div<u32, Output = Duration>,
+{
+    pub fn new() -> Self {
+        Pid {
+            integral: 0,
+            last_measured: None,
+            elapsed: Duration::default(),
+            on_time: Duration::default(),
+            duty: 0,
+        }
+    }
+
+    /// Forgets the history, the next update starts a new cycle
+    pub fn reset(&mut self) {
+        *self = Pid::new();
+    }
+
+    /// Duty cycle of the current period in per mille
+    pub fn duty(&self) -> i32 {
+        self.duty
+    }
+
+    /// This should be called regurarily, returns the state of the time proportional output
+    pub fn update(
+        &mut self,
+        config: &PidConfig<Duration>,
+        target: i32,
+        measured: i32,
+        delta_time: Duration,
+    ) -> bool {
+        let error = target - measured;
+        let dt: u32 = delta_time.into();
+
+        //anti windup: the integral term alone can not exceed the output range
+        if config.ki > 0 {
+            let limit = (MAX_DUTY as i64) * 16 * 3600 / (config.ki as i64);
+            let integral = self.integral + (error as i64) * (dt as i64);
+            self.integral = if integral > limit {
+                limit
+            } else if integral < -limit {
+                -limit
+            } else {
+                integral
+            };
+        } else {
+            self.integral = 0;
+        }
+
+        self.elapsed = self.elapsed + delta_time;
+
+        if self.last_measured.is_none() || self.elapsed >= config.cycle_period {
+            //the measured time since the last sample, the cycles stretch when the loop stalls
+            let elapsed: u32 = self.elapsed.into();
+
+            //change per hour in 1/16 degree units
+            let derivative = match self.last_measured {
+                Some(last) if elapsed > 0 => (measured - last) as i64 * 3600 / elapsed as i64,
+                _ => 0,
+            };
+
+            let output = (config.kp as i64) * (error as i64) / 16
+                + (config.ki as i64) * self.integral / (16 * 3600)
+                - (config.kd as i64) * derivative / 16;
+
+            self.duty = if output > MAX_DUTY as i64 {
+                MAX_DUTY
+            } else if output < 0 {
+                0
+            } else {
+                output as i32
+            };
+
+            let on_time = config.cycle_period * (self.duty as u32) / (MAX_DUTY as u32);
+            self.on_time = if on_time < config.min_pulse {
+                Duration::default()
+            } else if config.cycle_period - on_time < config.min_pulse {
+                config.cycle_period
+            } else {
+                on_time
+            };
+
+            self.last_measured = Some(measured);
+            self.elapsed = Duration::default();
+        }
+
+        self.elapsed < self.on_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(kp: i32, ki: i32, kd: i32) -> PidConfig<u32> {
+        PidConfig {
+            kp: kp,
+            ki: ki,
+            kd: kd,
+            cycle_period: 600,
+            min_pulse: 30,
+        }
+    }
+
+    #[test]
+    fn duty_saturates() {
+        let config = config(100, 0, 0);
+        let mut pid = Pid::<u32>::new();
+        assert!(pid.update(&config, 20 * 16, 0, 0)); //2000 per mille
+        assert!(pid.duty() == 1000);
+        assert!(pid.update(&config, 20 * 16, 0, 599)); //on for the whole cycle
+
+        pid.reset();
+        assert!(!pid.update(&config, 0, 20 * 16, 0));
+        assert!(pid.duty() == 0);
+    }
+
+    #[test]
+    fn output_is_on_for_the_duty() {
+        let config = config(100, 0, 0);
+        let mut pid = Pid::<u32>::new();
+        assert!(pid.update(&config, 4 * 16, 0, 0)); //400 per mille, 240 s
+        assert!(pid.update(&config, 4 * 16, 0, 239));
+        assert!(!pid.update(&config, 4 * 16, 0, 1));
+
+        pid.reset();
+        assert!(!pid.update(&config, 4, 0, 0)); //25 per mille, 15 s is shorter than the min pulse
+        assert!(pid.duty() == 25);
+    }
+
+    #[test]
+    fn integral_is_clamped() {
+        let config = config(0, 100, 0);
+        let mut pid = Pid::<u32>::new();
+        for _ in 0..600 {
+            pid.update(&config, 16, 0, 600); //1 degree of error for 100 hours
+        }
+        assert!(pid.duty() == 1000);
+
+        //the integral was held at the limit (576000), it unwinds at once
+        pid.update(&config, 0, 16, 600);
+        assert!(pid.duty() == 983);
+    }
+
+    #[test]
+    fn derivative_uses_the_elapsed_time() {
+        let config = config(0, 0, 160);
+        let mut pid = Pid::<u32>::new();
+        pid.update(&config, 0, 0, 0);
+        pid.update(&config, 0, -16, 300);
+        assert!(pid.duty() == 0); //in the middle of the cycle
+        pid.update(&config, 0, -16, 300);
+        assert!(pid.duty() == 960); //1 degree in 10 minutes
+
+        //a stalled loop: the same drop over a double cycle
+        pid.reset();
+        pid.update(&config, 0, 0, 0);
+        pid.update(&config, 0, -16, 1200);
+        assert!(pid.duty() == 480);
+    }
+}
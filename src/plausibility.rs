@@ -0,0 +1,260 @@
+//! Plausibility checks of the temperature sensor readings before they get to the controller:
+//! per role range, rate of change, the DS18B20 power on reset value (85.0 degree),
+//! stuck (repeated identical) readings and consecutive read (CRC) errors.
+//! The temperatures are compared in 1/16 degree units.
+
+use crate::sensors::{Role, ROLE_COUNT};
+use crate::temperature::FixedPoint;
+use core::ops::Add;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Health {
+    Ok,
+    NoData,     //not read yet
+    ReadError,  //too many consecutive read (CRC) errors
+    OutOfRange, //outside of the limits of the role
+    ResetValue, //85.0 degree after a power on reset
+    Jump,       //changed faster than possible
+    Stuck,      //the same value for too long
+}
+
+impl Health {
+    /// Code for the CAN status and the display (0 = ok)
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// One letter sign for the display (space if ok)
+    pub fn sign(self) -> u8 {
+        match self {
+            Health::Ok => b' ',
+            Health::NoData => b'-',
+            Health::ReadError => b'H',
+            Health::OutOfRange => b'T',
+            Health::ResetValue => b'R',
+            Health::Jump => b'U',
+            Health::Stuck => b'A',
+        }
+    }
+}
+
+pub struct Limits<Temperature> {
+    pub min: Temperature,
+    pub max: Temperature,
+    pub max_change: Temperature, //per minute
+}
+
+pub struct Config<Temperature, Duration> {
+    pub limits: [Limits<Temperature>; ROLE_COUNT], //by role index
+    pub stuck_duration: Duration,                  //of identical readings
+    pub max_read_errors: u32,                      //consecutive, tolerated with the last value
+}
+
+const RESET_VALUE: i32 = 85 * 16;
+
+pub struct Check<Duration> {
+    last: Option<i32>,     //last valid reading
+    last_raw: Option<i32>, //last reading (valid or not)
+    unchanged: Duration,   //since the raw reading has changed
+    read_errors: u32,      //consecutive
+    pub total_read_errors: u32,
+    health: Health,
+}
+
+impl<Duration: Copy + PartialOrd + Default + Into<u32> + Add<Duration, Output = Duration>>
+    Check<Duration>
+{
+    pub fn new() -> Self {
+        Check {
+            last: None,
+            last_raw: None,
+            unchanged: Duration::default(),
+            read_errors: 0,
+            total_read_errors: 0,
+            health: Health::NoData,
+        }
+    }
+
+    pub fn health(&self) -> Health {
+        self.health
+    }
+
+    /// Checks the new reading (None on read error), returns the value usable by the controller.
+    /// Sensors without a role are checked for the reset value, read errors and stuck values only.
+    pub fn update<Temperature: FixedPoint>(
+        &mut self,
+        config: &Config<Temperature, Duration>,
+        role: Option<Role>,
+        reading: Option<Temperature>,
+        delta_time: Duration,
+    ) -> Option<Temperature> {
+        let value = match reading {
+            Some(temperature) => temperature.to_sixteenths(),
+            None => {
+                self.read_errors += 1;
+                self.total_read_errors += 1;
+                if self.read_errors > config.max_read_errors {
+                    self.health = Health::ReadError;
+                    self.last = None;
+                }
+                //keep the last value over a few errors
+                return self.last.map(Temperature::from_sixteenths);
+            }
+        };
+        self.read_errors = 0;
+
+        if self.last_raw == Some(value) {
+            self.unchanged = self.unchanged + delta_time;
+        } else {
+            self.unchanged = Duration::default();
+        }
+        let previous = self.last_raw;
+        self.last_raw = Some(value);
+
+        self.health = self.check(config, role, value, previous, delta_time);
+
+        if self.health == Health::Ok {
+            self.last = Some(value);
+            Some(Temperature::from_sixteenths(value))
+        } else {
+            self.last = None;
+            None
+        }
+    }
+
+    fn check<Temperature: FixedPoint>(
+        &self,
+        config: &Config<Temperature, Duration>,
+        role: Option<Role>,
+        value: i32,
+        previous: Option<i32>,
+        delta_time: Duration,
+    ) -> Health {
+        //the reset value is accepted only if the temperature was around that
+        if value == RESET_VALUE {
+            match self.last {
+                Some(last) if (last - value).abs() <= 16 => {}
+                _ => return Health::ResetValue,
+            }
+        }
+
+        if self.unchanged >= config.stuck_duration {
+            return Health::Stuck;
+        }
+
+        if let Some(role) = role {
+            let limits = &config.limits[role.index()];
+            if value < limits.min.to_sixteenths() || value > limits.max.to_sixteenths() {
+                return Health::OutOfRange;
+            }
+
+            //compared to the previous reading, so a real step change is accepted from the next reading
+            if let Some(previous) = previous {
+                let seconds: u32 = delta_time.into();
+                let max_change = limits.max_change.to_sixteenths() * (seconds as i32 + 59) / 60;
+                if (value - previous).abs() > max_change {
+                    return Health::Jump;
+                }
+            }
+        }
+
+        Health::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use onewire::temperature::Temperature;
+
+    fn config() -> Config<Temperature, u32> {
+        let limits = || Limits {
+            min: Temperature::from_celsius(5, 0),
+            max: Temperature::from_celsius(90, 0),
+            max_change: Temperature::from_celsius(2, 0),
+        };
+        Config {
+            limits: [limits(), limits(), limits(), limits(), limits()],
+            stuck_duration: 3600,
+            max_read_errors: 2,
+        }
+    }
+
+    fn update(
+        check: &mut Check<u32>,
+        role: Option<Role>,
+        celsius: i32,
+        delta_time: u32,
+    ) -> Option<i32> {
+        let reading = Temperature::from_sixteenths(celsius * 16);
+        check
+            .update(&config(), role, Some(reading), delta_time)
+            .map(|temperature| temperature.to_sixteenths())
+    }
+
+    #[test]
+    fn reset_value() {
+        let mut check = Check::<u32>::new();
+        assert!(check.health() == Health::NoData);
+        assert!(update(&mut check, None, 85, 60).is_none());
+        assert!(check.health() == Health::ResetValue);
+
+        //accepted when the temperature was around that
+        assert!(update(&mut check, None, 84, 60) == Some(84 * 16));
+        assert!(update(&mut check, None, 85, 60) == Some(85 * 16));
+        assert!(check.health() == Health::Ok);
+    }
+
+    #[test]
+    fn stuck_reading() {
+        let mut check = Check::<u32>::new();
+        for _ in 0..6 {
+            assert!(update(&mut check, Some(Role::Air), 21, 600).is_some());
+        }
+        assert!(update(&mut check, Some(Role::Air), 21, 600).is_none()); //for an hour
+        assert!(check.health() == Health::Stuck);
+        assert!(update(&mut check, Some(Role::Air), 22, 600) == Some(22 * 16));
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut check = Check::<u32>::new();
+        assert!(update(&mut check, Some(Role::Forward), 95, 60).is_none());
+        assert!(check.health() == Health::OutOfRange);
+        assert!(update(&mut check, Some(Role::Forward), 2, 60).is_none());
+        assert!(check.health() == Health::OutOfRange);
+
+        //the sensors without a role have no limits
+        assert!(update(&mut check, None, 2, 60) == Some(2 * 16));
+    }
+
+    #[test]
+    fn jump() {
+        let mut check = Check::<u32>::new();
+        update(&mut check, Some(Role::Floor), 20, 60);
+        assert!(update(&mut check, Some(Role::Floor), 25, 60).is_none()); //2 degree per started minute
+        assert!(check.health() == Health::Jump);
+
+        //a real step change is accepted from the next reading
+        assert!(update(&mut check, Some(Role::Floor), 25, 60) == Some(25 * 16));
+        assert!(update(&mut check, Some(Role::Floor), 29, 61) == Some(29 * 16));
+    }
+
+    #[test]
+    fn read_errors() {
+        let config = config();
+        let mut check = Check::<u32>::new();
+        update(&mut check, None, 21, 60);
+        for _ in 0..2 {
+            let value = check.update(&config, None, None, 60);
+            assert!(value.map(|temperature| temperature.to_sixteenths()) == Some(21 * 16));
+            assert!(check.health() == Health::Ok);
+        }
+        assert!(check.update(&config, None, None, 60).is_none());
+        assert!(check.health() == Health::ReadError);
+        assert!(check.total_read_errors == 3);
+
+        assert!(update(&mut check, None, 21, 60) == Some(21 * 16));
+        assert!(check.update(&config, None, None, 60).is_some()); //the count restarted
+    }
+}
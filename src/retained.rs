@@ -0,0 +1,199 @@
+//! Runtime state retained over the resets (e.g. in the uninitialized RAM or the backup registers):
+//! the time offset, the floor heating state, the boot and reset counters, the uptime
+//! and the idle times of the exercised devices.
+//! The words are protected by a magic word and a crc, the state is dropped if any of them mismatch
+//! (e.g. after a power loss when kept in the RAM).
+
+use crate::codec::crc16;
+use crate::floor_heating::{ErrorReason, State};
+
+pub const RETAINED_WORDS: usize = 17;
+pub const EXERCISE_DEVICES: usize = 5; //the zone valves and the pump of the manifold unit, the floor unit uses the first two
+const MAGIC: u32 = 0x5245_5403; //the last byte is the layout version
+
+//the reset flags of the RCC_CSR register
+const PINRSTF: u32 = 1 << 26;
+const PORRSTF: u32 = 1 << 27;
+const SFTRSTF: u32 = 1 << 28;
+const IWDGRSTF: u32 = 1 << 29;
+const WWDGRSTF: u32 = 1 << 30;
+const LPWRRSTF: u32 = 1 << 31;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ResetReason {
+    PowerOn,
+    Pin,
+    Software,
+    IndependentWatchdog,
+    WindowWatchdog,
+    LowPower,
+    Unknown, //none of the flags is set
+}
+
+pub const RESET_REASON_COUNT: usize = 7;
+
+impl ResetReason {
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// From the flags of the RCC_CSR register, the power on reset sets the pin flag too
+    pub fn from_csr(csr: u32) -> Self {
+        if csr & LPWRRSTF != 0 {
+            ResetReason::LowPower
+        } else if csr & WWDGRSTF != 0 {
+            ResetReason::WindowWatchdog
+        } else if csr & IWDGRSTF != 0 {
+            ResetReason::IndependentWatchdog
+        } else if csr & SFTRSTF != 0 {
+            ResetReason::Software
+        } else if csr & PORRSTF != 0 {
+            ResetReason::PowerOn
+        } else if csr & PINRSTF != 0 {
+            ResetReason::Pin
+        } else {
+            ResetReason::Unknown
+        }
+    }
+
+    pub fn watchdog(self) -> bool {
+        self == ResetReason::IndependentWatchdog || self == ResetReason::WindowWatchdog
+    }
+}
+
+pub struct Retained<Duration> {
+    pub time_offset: Duration,
+    pub state: Option<State<Duration>>, //of the floor heating
+    pub boot_count: u32,
+    pub resets: [u16; RESET_REASON_COUNT], //by reason index
+    pub uptime: Duration,                  //of the current run
+    pub last_uptime: Duration,             //of the run before the last reset
+    pub exercise_idle: [Duration; EXERCISE_DEVICES], //otherwise every reset postpones the exercise
+}
+
+impl<Duration: Copy + Into<u32> + From<u32>> Retained<Duration> {
+    pub fn new() -> Self {
+        Retained {
+            time_offset: Duration::from(0),
+            state: None,
+            boot_count: 0,
+            resets: [0; RESET_REASON_COUNT],
+            uptime: Duration::from(0),
+            last_uptime: Duration::from(0),
+            exercise_idle: [Duration::from(0); EXERCISE_DEVICES],
+        }
+    }
+
+    /// Counts the boot, the uptime restarts
+    pub fn boot(&mut self, reason: ResetReason) {
+        self.boot_count = self.boot_count.wrapping_add(1);
+        let count = &mut self.resets[reason.index()];
+        *count = count.saturating_add(1);
+        self.last_uptime = self.uptime;
+        self.uptime = Duration::from(0);
+    }
+
+    pub fn save(&self) -> [u32; RETAINED_WORDS] {
+        let (state, state_duration) = match self.state {
+            Some(state) => encode_state(&state),
+            None => (0, 0),
+        };
+        let mut words = [
+            MAGIC,
+            self.time_offset.into(),
+            state,
+            state_duration,
+            self.boot_count,
+            self.uptime.into(),
+            self.last_uptime.into(),
+            self.resets[0] as u32 | (self.resets[1] as u32) << 16,
+            self.resets[2] as u32 | (self.resets[3] as u32) << 16,
+            self.resets[4] as u32 | (self.resets[5] as u32) << 16,
+            self.resets[6] as u32,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ];
+        for (i, idle) in self.exercise_idle.iter().enumerate() {
+            words[11 + i] = (*idle).into();
+        }
+        words[RETAINED_WORDS - 1] = checksum(&words) as u32;
+        words
+    }
+
+    /// None if the words are invalid (e.g. the RAM was not retained)
+    pub fn restore(words: &[u32; RETAINED_WORDS]) -> Option<Self> {
+        if words[0] != MAGIC || words[RETAINED_WORDS - 1] != checksum(words) as u32 {
+            return None;
+        }
+        let mut resets = [0u16; RESET_REASON_COUNT];
+        for (i, count) in resets.iter_mut().enumerate() {
+            *count = (words[7 + i / 2] >> (16 * (i % 2))) as u16;
+        }
+        let mut exercise_idle = [Duration::from(0); EXERCISE_DEVICES];
+        for (i, idle) in exercise_idle.iter_mut().enumerate() {
+            *idle = Duration::from(words[11 + i]);
+        }
+        Some(Retained {
+            time_offset: Duration::from(words[1]),
+            state: decode_state(words[2], words[3]),
+            boot_count: words[4],
+            resets: resets,
+            uptime: Duration::from(words[5]),
+            last_uptime: Duration::from(words[6]),
+            exercise_idle: exercise_idle,
+        })
+    }
+}
+
+/// Of the words before the last one
+fn checksum(words: &[u32; RETAINED_WORDS]) -> u16 {
+    let mut bytes = [0u8; (RETAINED_WORDS - 1) * 4];
+    for (chunk, word) in bytes.chunks_mut(4).zip(words.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    crc16(&bytes)
+}
+
+/// The state code with its flag (defrost or error reason) and the duration
+fn encode_state<Duration: Copy + Into<u32>>(state: &State<Duration>) -> (u32, u32) {
+    let (flag, duration) = match *state {
+        State::PrepareHeating((defrost, duration)) => (defrost as u32, duration.into()),
+        State::Heating(defrost) => (defrost as u32, 0),
+        State::AfterCirculation(duration)
+        | State::Standby(duration)
+        | State::FreezeProtectionCheckCirculation(duration) => (0, duration.into()),
+        State::Error((reason, duration, defrost)) => (
+            reason.code() as u32 | (defrost as u32) << 4,
+            duration.into(),
+        ),
+    };
+    (state.code() as u32 | flag << 8, duration)
+}
+
+fn decode_state<Duration: From<u32>>(word: u32, duration: u32) -> Option<State<Duration>> {
+    let flag = word >> 8;
+    let defrost = flag != 0;
+    let duration = Duration::from(duration);
+    match word & 0xFF {
+        1 => Some(State::PrepareHeating((defrost, duration))),
+        2 => Some(State::Heating(defrost)),
+        3 => Some(State::AfterCirculation(duration)),
+        4 => Some(State::Standby(duration)),
+        5 => Some(State::FreezeProtectionCheckCirculation(duration)),
+        6 => {
+            let reason = match flag & 0x0F {
+                1 => ErrorReason::MissingAirSensor,
+                2 => ErrorReason::MissingReturnSensor,
+                3 => ErrorReason::Overheat,
+                4 => ErrorReason::CirculationFailure,
+                _ => return None,
+            };
+            Some(State::Error((reason, duration, flag & 0x10 != 0)))
+        }
+        _ => None,
+    }
+}
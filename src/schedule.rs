@@ -0,0 +1,674 @@
+//! Weekly schedule of the target temperature with a variable number of switch points per day,
+//! the switch points of a day are kept sorted by their start time.
+//! The program modes (economy, party, fix, away) override the scheduled target.
+
+use crate::codec::{Overflow, Reader, Writer};
+use crate::temperature::FixedPoint;
+use crate::timing::WeekTime;
+use core::ops::Add;
+
+pub const MAX_SWITCH_POINTS: usize = 8; //per day
+pub const DAYS_PER_WEEK: usize = 7;
+pub const WEEKDAYS: usize = 5; //monday to friday
+const SECONDS_PER_DAY: u32 = 24 * 3600;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Error {
+    Full,          //no more switch points in the day
+    InvalidTime,   //hour > 23 or min > 59
+    DuplicateTime, //an other switch point starts at the same time
+    Unsorted,      //the switch points are not in the order of their start time
+    NotFound,      //no switch point with the index
+}
+
+#[derive(Clone, Copy)]
+pub struct SwitchPoint<Temperature> {
+    pub hour: u8,
+    pub min: u8,
+    pub target: Temperature,
+}
+
+impl<Temperature> SwitchPoint<Temperature> {
+    pub fn new(hour: u8, min: u8, target: Temperature) -> Self {
+        SwitchPoint {
+            hour: hour,
+            min: min,
+            target: target,
+        }
+    }
+
+    /// The start time in minutes from midnight
+    pub fn minutes(&self) -> u16 {
+        self.hour as u16 * 60 + self.min as u16
+    }
+
+    fn valid(&self) -> bool {
+        self.hour < 24 && self.min < 60
+    }
+}
+
+/// The switch points of a day
+#[derive(Clone, Copy)]
+pub struct Day<Temperature> {
+    points: [Option<SwitchPoint<Temperature>>; MAX_SWITCH_POINTS],
+    count: usize,
+}
+
+impl<Temperature: Copy> Day<Temperature> {
+    pub fn new() -> Self {
+        Day {
+            points: [None; MAX_SWITCH_POINTS],
+            count: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SwitchPoint<Temperature>> {
+        if index < self.count {
+            self.points[index].as_ref()
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SwitchPoint<Temperature>> {
+        self.points[..self.count].iter().flatten()
+    }
+
+    /// Inserts the switch point in the order of the start time, returns its index
+    pub fn add(&mut self, point: SwitchPoint<Temperature>) -> Result<usize, Error> {
+        if !point.valid() {
+            return Err(Error::InvalidTime);
+        }
+        if self.iter().any(|p| p.minutes() == point.minutes()) {
+            return Err(Error::DuplicateTime);
+        }
+        if self.count >= MAX_SWITCH_POINTS {
+            return Err(Error::Full);
+        }
+
+        let index = self
+            .iter()
+            .position(|p| p.minutes() > point.minutes())
+            .unwrap_or(self.count);
+        for i in (index..self.count).rev() {
+            self.points[i + 1] = self.points[i];
+        }
+        self.points[index] = Some(point);
+        self.count += 1;
+        Ok(index)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<SwitchPoint<Temperature>, Error> {
+        let point = *self.get(index).ok_or(Error::NotFound)?;
+        for i in index..self.count - 1 {
+            self.points[i] = self.points[i + 1];
+        }
+        self.count -= 1;
+        self.points[self.count] = None;
+        Ok(point)
+    }
+
+    /// Replaces the switch point, returns its new index (the day is kept sorted).
+    /// The day is unchanged on error.
+    pub fn set(&mut self, index: usize, point: SwitchPoint<Temperature>) -> Result<usize, Error> {
+        let previous = self.remove(index)?;
+        self.add(point).map_err(|error| {
+            let _ = self.add(previous);
+            error
+        })
+    }
+
+    /// Changes the target only, the order is not affected
+    pub fn set_target(&mut self, index: usize, target: Temperature) -> Result<(), Error> {
+        match self.points[..self.count].get_mut(index) {
+            Some(Some(point)) => {
+                point.target = target;
+                Ok(())
+            }
+            _ => Err(Error::NotFound),
+        }
+    }
+
+    /// Sorts the switch points by the start time (e.g. after loading them)
+    pub fn sort(&mut self) {
+        for i in 1..self.count {
+            let mut j = i;
+            while j > 0 && Self::minutes_at(&self.points[j - 1]) > Self::minutes_at(&self.points[j])
+            {
+                self.points.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut previous: Option<u16> = None;
+        for point in self.iter() {
+            if !point.valid() {
+                return Err(Error::InvalidTime);
+            }
+            match previous {
+                Some(previous) if previous == point.minutes() => return Err(Error::DuplicateTime),
+                Some(previous) if previous > point.minutes() => return Err(Error::Unsorted),
+                _ => {}
+            }
+            previous = Some(point.minutes());
+        }
+        Ok(())
+    }
+
+    /// The index of the last switch point started until the given minute of the day
+    fn active_at(&self, minutes: u16) -> Option<usize> {
+        match self
+            .iter()
+            .take_while(|point| point.minutes() <= minutes)
+            .count()
+        {
+            0 => None,
+            count => Some(count - 1),
+        }
+    }
+
+    fn minutes_at(point: &Option<SwitchPoint<Temperature>>) -> u16 {
+        point
+            .as_ref()
+            .map(|point| point.minutes())
+            .unwrap_or(u16::MAX)
+    }
+}
+
+/// A switch point of the week
+#[derive(Clone, Copy, PartialEq)]
+pub struct Slot {
+    pub weekday: u8, //0 = monday
+    pub index: usize,
+}
+
+pub struct Schedule<Temperature> {
+    days: [Day<Temperature>; DAYS_PER_WEEK],
+}
+
+impl<Temperature: Copy> Schedule<Temperature> {
+    pub fn new() -> Self {
+        Schedule {
+            days: [Day::new(); DAYS_PER_WEEK],
+        }
+    }
+
+    pub fn day(&self, weekday: u8) -> &Day<Temperature> {
+        &self.days[weekday as usize]
+    }
+
+    pub fn day_mut(&mut self, weekday: u8) -> &mut Day<Temperature> {
+        &mut self.days[weekday as usize]
+    }
+
+    pub fn point(&self, slot: Slot) -> Option<&SwitchPoint<Temperature>> {
+        self.days[slot.weekday as usize].get(slot.index)
+    }
+
+    pub fn copy_day(&mut self, from: u8, to: u8) {
+        self.days[to as usize] = self.days[from as usize];
+    }
+
+    /// Copies the day to the weekdays (monday to friday)
+    pub fn copy_to_weekdays(&mut self, from: u8) {
+        for weekday in 0..WEEKDAYS as u8 {
+            self.copy_day(from, weekday);
+        }
+    }
+
+    /// Returns the first invalid day
+    pub fn validate(&self) -> Result<(), (u8, Error)> {
+        for (weekday, day) in self.days.iter().enumerate() {
+            day.validate().map_err(|error| (weekday as u8, error))?;
+        }
+        Ok(())
+    }
+
+    /// The switch point active at the given time, the last one of the previous days before the first of the day
+    pub fn current(&self, now: &WeekTime) -> Option<Slot> {
+        let minutes = now.hour as u16 * 60 + now.min as u16;
+        if let Some(index) = self.days[now.weekday as usize].active_at(minutes) {
+            return Some(Slot {
+                weekday: now.weekday,
+                index: index,
+            });
+        }
+        (1..=DAYS_PER_WEEK)
+            .map(|days_back| (now.weekday as usize + DAYS_PER_WEEK - days_back) % DAYS_PER_WEEK)
+            .find(|weekday| !self.days[*weekday].is_empty())
+            .map(|weekday| Slot {
+                weekday: weekday as u8,
+                index: self.days[weekday].len() - 1,
+            })
+    }
+
+    /// The next switch point after the given time and the seconds until its start
+    pub fn next(&self, now: &WeekTime) -> Option<(Slot, u32)> {
+        let now_seconds = (now.hour as u32 * 60 + now.min as u32) * 60 + now.sec as u32;
+        for days_ahead in 0..=DAYS_PER_WEEK {
+            let weekday = (now.weekday as usize + days_ahead) % DAYS_PER_WEEK;
+            for (index, point) in self.days[weekday].iter().enumerate() {
+                let start = days_ahead as u32 * SECONDS_PER_DAY + point.minutes() as u32 * 60;
+                if start > now_seconds {
+                    let slot = Slot {
+                        weekday: weekday as u8,
+                        index: index,
+                    };
+                    return Some((slot, start - now_seconds));
+                }
+            }
+        }
+        None
+    }
+
+    /// The scheduled target at the given time
+    pub fn setpoint(&self, now: &WeekTime) -> Option<Temperature> {
+        self.current(now)
+            .and_then(|slot| self.point(slot))
+            .map(|point| point.target)
+    }
+
+    /// The start and the target of the next change after the given time
+    pub fn next_change(&self, now: &WeekTime) -> Option<(WeekTime, Temperature)> {
+        let (slot, _) = self.next(now)?;
+        let point = self.point(slot)?;
+        let start = WeekTime {
+            weekday: slot.weekday,
+            hour: point.hour,
+            min: point.min,
+            sec: 0,
+        };
+        Some((start, point.target))
+    }
+}
+
+impl<Temperature: Copy + FixedPoint> Schedule<Temperature> {
+    /// Workdays from monday, longer evening on friday, the weekend days start later
+    pub fn with_defaults() -> Self {
+        let mut program = Schedule::new();
+        let workday = [
+            (6, 15, 20),
+            (7, 30, 17),
+            (12, 30, 20),
+            (14, 30, 18),
+            (17, 0, 20),
+            (20, 0, 17),
+        ];
+        let weekend = [
+            (7, 15, 20),
+            (7, 30, 19),
+            (11, 30, 20),
+            (14, 30, 19),
+            (17, 0, 20),
+            (21, 0, 17),
+        ];
+        let point = |hour, min, celsius: i32| {
+            SwitchPoint::new(hour, min, Temperature::from_sixteenths(celsius * 16))
+        };
+        for &(hour, min, celsius) in workday.iter() {
+            let _ = program.day_mut(0).add(point(hour, min, celsius));
+        }
+        for &(hour, min, celsius) in weekend.iter() {
+            let _ = program.day_mut(5).add(point(hour, min, celsius));
+        }
+        program.copy_to_weekdays(0);
+        program.copy_day(5, 6);
+
+        //friday and saturday evening until 21:00, sunday until 20:00
+        let _ = program.day_mut(4).set(5, point(21, 0, 17));
+        let _ = program.day_mut(6).set(5, point(20, 0, 17));
+        program
+    }
+
+    /// By day: the number of the switch points, then the hour, min and target of each
+    pub fn encode(&self, writer: &mut Writer) -> Result<(), Overflow> {
+        for day in self.days.iter() {
+            writer.u8(day.len() as u8)?;
+            for point in day.iter() {
+                writer.u8(point.hour)?;
+                writer.u8(point.min)?;
+                writer.temperature(&point.target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// None if the data is short or a day is invalid
+    pub fn decode(reader: &mut Reader) -> Option<Self> {
+        let mut schedule = Schedule::new();
+        for day in schedule.days.iter_mut() {
+            for _ in 0..reader.u8()? {
+                let point = SwitchPoint::new(reader.u8()?, reader.u8()?, reader.temperature()?);
+                day.add(point).ok()?;
+            }
+        }
+        Some(schedule)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode<Temperature> {
+    Normal,               //as scheduled, a user override is kept until the next switch point
+    Economy(Temperature), //the scheduled target + the offset (which is negative)
+    Party(u8),            //the user override is kept until the midnight of the stored weekday
+    Fix(Temperature),     //the given target
+    Away((u32, u8)),      //the days left (counted down at midnight) and the hour of the return
+}
+
+impl<Temperature: Copy + Add<Temperature, Output = Temperature>> Mode<Temperature> {
+    /// The target by the mode from the scheduled target,
+    /// None if it is not decided by the mode (the user override or the away setback is used)
+    pub fn target(&self, scheduled: Temperature) -> Option<Temperature> {
+        match self {
+            Mode::Normal => Some(scheduled),
+            Mode::Economy(offset) => Some(scheduled + *offset),
+            Mode::Fix(target) => Some(*target),
+            Mode::Party(_) | Mode::Away(_) => None,
+        }
+    }
+
+    /// The mode for the new day: the party ends, the away days are counted down
+    pub fn midnight(self, weekday: u8) -> Self {
+        match self {
+            Mode::Party(day) if day != weekday => Mode::Normal,
+            Mode::Away((days, hour)) if days > 0 => Mode::Away((days - 1, hour)),
+            mode => mode,
+        }
+    }
+
+    /// The time of the return in the away mode (within a week, the days are counted down)
+    pub fn away_return(&self, now: &WeekTime) -> Option<WeekTime> {
+        match self {
+            Mode::Away((days, hour)) => Some(WeekTime {
+                weekday: ((now.weekday as u32 + days) % DAYS_PER_WEEK as u32) as u8,
+                hour: *hour,
+                min: 0,
+                sec: 0,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The kind of the mode, then its parameters
+    pub fn encode(&self, writer: &mut Writer) -> Result<(), Overflow>
+    where
+        Temperature: FixedPoint,
+    {
+        match self {
+            Mode::Normal => writer.u8(0),
+            Mode::Economy(offset) => {
+                writer.u8(1)?;
+                writer.temperature(offset)
+            }
+            Mode::Party(weekday) => {
+                writer.u8(2)?;
+                writer.u8(*weekday)
+            }
+            Mode::Fix(target) => {
+                writer.u8(3)?;
+                writer.temperature(target)
+            }
+            Mode::Away((days, hour)) => {
+                writer.u8(4)?;
+                writer.u32(*days)?;
+                writer.u8(*hour)
+            }
+        }
+    }
+
+    pub fn decode(reader: &mut Reader) -> Option<Self>
+    where
+        Temperature: FixedPoint,
+    {
+        match reader.u8()? {
+            0 => Some(Mode::Normal),
+            1 => Some(Mode::Economy(reader.temperature()?)),
+            2 => match reader.u8()? {
+                weekday if (weekday as usize) < DAYS_PER_WEEK => Some(Mode::Party(weekday)),
+                _ => None,
+            },
+            3 => Some(Mode::Fix(reader.temperature()?)),
+            4 => match (reader.u32()?, reader.u8()?) {
+                (days, hour) if hour < 24 => Some(Mode::Away((days, hour))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Seconds until the return in the away mode (0 after the return time)
+    pub fn away_remaining(&self, now: &WeekTime) -> Option<u32> {
+        match self {
+            Mode::Away((days, hour)) => {
+                let now = (now.hour as u32 * 60 + now.min as u32) * 60 + now.sec as u32;
+                let return_time = days * SECONDS_PER_DAY + *hour as u32 * 3600;
+                Some(return_time.saturating_sub(now))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(weekday: u8, hour: u8, min: u8) -> WeekTime {
+        WeekTime {
+            weekday: weekday,
+            hour: hour,
+            min: min,
+            sec: 0,
+        }
+    }
+
+    fn starts(day: &Day<i16>) -> [Option<u16>; 4] {
+        let mut starts = [None; 4];
+        for (start, point) in starts.iter_mut().zip(day.iter()) {
+            *start = Some(point.minutes());
+        }
+        starts
+    }
+
+    #[test]
+    fn add_keeps_the_order() {
+        let mut day = Day::new();
+        assert!(day.add(SwitchPoint::new(16, 0, 210)) == Ok(0));
+        assert!(day.add(SwitchPoint::new(6, 30, 200)) == Ok(0));
+        assert!(day.add(SwitchPoint::new(22, 0, 180)) == Ok(2));
+        assert!(day.add(SwitchPoint::new(9, 0, 190)) == Ok(1));
+        assert!(starts(&day) == [Some(390), Some(540), Some(960), Some(1320)]);
+        assert!(day.validate() == Ok(()));
+
+        assert!(day.add(SwitchPoint::new(9, 0, 200)) == Err(Error::DuplicateTime));
+        assert!(day.add(SwitchPoint::new(24, 0, 200)) == Err(Error::InvalidTime));
+        assert!(day.len() == 4);
+    }
+
+    #[test]
+    fn add_to_a_full_day() {
+        let mut day = Day::new();
+        for hour in 0..MAX_SWITCH_POINTS as u8 {
+            assert!(day.add(SwitchPoint::new(hour, 0, 200)).is_ok());
+        }
+        assert!(day.add(SwitchPoint::new(23, 0, 200)) == Err(Error::Full));
+    }
+
+    #[test]
+    fn set_moves_the_point() {
+        let mut day = Day::new();
+        day.add(SwitchPoint::new(6, 0, 200)).unwrap();
+        day.add(SwitchPoint::new(9, 0, 190)).unwrap();
+        day.add(SwitchPoint::new(16, 0, 210)).unwrap();
+
+        assert!(day.set(0, SwitchPoint::new(18, 0, 220)) == Ok(2));
+        assert!(starts(&day) == [Some(540), Some(960), Some(1080), None]);
+        assert!(day.get(2).unwrap().target == 220);
+    }
+
+    #[test]
+    fn set_rolls_back_on_error() {
+        let mut day = Day::new();
+        day.add(SwitchPoint::new(6, 0, 200)).unwrap();
+        day.add(SwitchPoint::new(9, 0, 190)).unwrap();
+        day.add(SwitchPoint::new(16, 0, 210)).unwrap();
+
+        assert!(day.set(0, SwitchPoint::new(16, 0, 220)) == Err(Error::DuplicateTime));
+        assert!(day.set(1, SwitchPoint::new(9, 60, 220)) == Err(Error::InvalidTime));
+        assert!(day.set(3, SwitchPoint::new(12, 0, 220)) == Err(Error::NotFound));
+        assert!(starts(&day) == [Some(360), Some(540), Some(960), None]);
+        assert!(day.get(0).unwrap().target == 200);
+        assert!(day.get(1).unwrap().target == 190);
+    }
+
+    #[test]
+    fn current_wraps_to_the_previous_days() {
+        let mut schedule = Schedule::new();
+        schedule
+            .day_mut(0)
+            .add(SwitchPoint::new(6, 0, 210))
+            .unwrap();
+        schedule
+            .day_mut(0)
+            .add(SwitchPoint::new(22, 0, 180))
+            .unwrap();
+        schedule
+            .day_mut(2)
+            .add(SwitchPoint::new(7, 0, 200))
+            .unwrap();
+
+        //monday
+        assert!(
+            schedule.current(&at(0, 6, 0))
+                == Some(Slot {
+                    weekday: 0,
+                    index: 0
+                })
+        );
+        assert!(
+            schedule.current(&at(0, 23, 0))
+                == Some(Slot {
+                    weekday: 0,
+                    index: 1
+                })
+        );
+        //from the last point of the sunday (which is the one of the wednesday)
+        assert!(
+            schedule.current(&at(0, 5, 59))
+                == Some(Slot {
+                    weekday: 2,
+                    index: 0
+                })
+        );
+        //the tuesday is empty
+        assert!(
+            schedule.current(&at(1, 12, 0))
+                == Some(Slot {
+                    weekday: 0,
+                    index: 1
+                })
+        );
+        assert!(
+            schedule.current(&at(2, 6, 0))
+                == Some(Slot {
+                    weekday: 0,
+                    index: 1
+                })
+        );
+        assert!(schedule.setpoint(&at(2, 6, 0)) == Some(180));
+        assert!(schedule.setpoint(&at(2, 7, 0)) == Some(200));
+
+        assert!(Schedule::<i16>::new().current(&at(3, 12, 0)).is_none());
+    }
+
+    #[test]
+    fn next_switch_point() {
+        let mut schedule = Schedule::new();
+        schedule
+            .day_mut(0)
+            .add(SwitchPoint::new(6, 0, 210))
+            .unwrap();
+        schedule
+            .day_mut(0)
+            .add(SwitchPoint::new(22, 0, 180))
+            .unwrap();
+        schedule
+            .day_mut(2)
+            .add(SwitchPoint::new(7, 0, 200))
+            .unwrap();
+
+        let now = WeekTime {
+            sec: 30,
+            ..at(0, 5, 59)
+        };
+        assert!(
+            schedule.next(&now)
+                == Some((
+                    Slot {
+                        weekday: 0,
+                        index: 0
+                    },
+                    30
+                ))
+        );
+        //not the one starting now
+        assert!(
+            schedule.next(&at(0, 6, 0))
+                == Some((
+                    Slot {
+                        weekday: 0,
+                        index: 1
+                    },
+                    16 * 3600
+                ))
+        );
+        assert!(
+            schedule.next(&at(0, 23, 0))
+                == Some((
+                    Slot {
+                        weekday: 2,
+                        index: 0
+                    },
+                    32 * 3600
+                ))
+        );
+        //wraps over the end of the week
+        assert!(
+            schedule.next(&at(2, 8, 0))
+                == Some((
+                    Slot {
+                        weekday: 0,
+                        index: 0
+                    },
+                    118 * 3600
+                ))
+        );
+        //the only point of the week is found a week later
+        let mut single = Schedule::new();
+        single.day_mut(3).add(SwitchPoint::new(12, 0, 200)).unwrap();
+        assert!(
+            single.next(&at(3, 12, 0))
+                == Some((
+                    Slot {
+                        weekday: 3,
+                        index: 0
+                    },
+                    7 * 24 * 3600
+                ))
+        );
+
+        let (start, target) = schedule.next_change(&at(1, 0, 0)).unwrap();
+        assert!(start.weekday == 2 && start.hour == 7 && start.min == 0);
+        assert!(target == 200);
+    }
+}
@@ -0,0 +1,556 @@
+//! Screed functional heating and drying programs (e.g. by EN 1264-4): the forward temperature target
+//! is stepped day by day along the profile, the achieved temperatures are logged by day.
+//! The progress fits in a few words (e.g. the backup registers of the rtc), so it survives the resets.
+//! The log is kept apart (e.g. as a record of the config store written at the day changes).
+//! While a program runs, the max forward and floor limits are taken from its profile with a safety margin
+//! (instead of the limits set for the finished floor) and the valve modulates the forward temperature.
+//! The temperatures are given in 1/16 degree units.
+
+use crate::codec::{Overflow, Reader, Writer};
+use crate::floor_heating::ForwardOverride;
+
+pub const MAX_LOG_DAYS: usize = 28;
+pub const PROGRESS_WORDS: usize = 4;
+pub const LOG_RECORD_SIZE: usize = 2 + MAX_LOG_DAYS * LOG_ENTRY_SIZE;
+const LOG_ENTRY_SIZE: usize = 10;
+const LIMIT_MARGIN: i32 = 5 * 16; //above the highest forward target of the profile
+const PROGRESS_CHECK: u16 = 0x5C3E;
+const SECONDS_PER_DAY: u32 = 24 * 3600;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Phase {
+    HeatUp,
+    Hold,
+    CoolDown,
+}
+
+/// The forward target is stepped linearly from..to over the days
+pub struct Step {
+    pub days: u8,
+    pub from: i32,
+    pub to: i32,
+}
+
+impl Step {
+    pub fn phase(&self) -> Phase {
+        if self.to > self.from {
+            Phase::HeatUp
+        } else if self.to < self.from {
+            Phase::CoolDown
+        } else {
+            Phase::Hold
+        }
+    }
+
+    fn target(&self, day: u8) -> i32 {
+        if self.days > 1 {
+            self.from + (self.to - self.from) * day as i32 / (self.days - 1) as i32
+        } else {
+            self.to
+        }
+    }
+}
+
+//functional heating: 3 days at 25, then 4 days at 45 degree
+static FUNCTIONAL_HEATING: [Step; 2] = [
+    Step {
+        days: 3,
+        from: 25 * 16,
+        to: 25 * 16,
+    },
+    Step {
+        days: 4,
+        from: 45 * 16,
+        to: 45 * 16,
+    },
+];
+
+//drying: +5 degree a day from 25 to 45, 10 days at 45, then -10 degree a day
+static DRYING: [Step; 3] = [
+    Step {
+        days: 5,
+        from: 25 * 16,
+        to: 45 * 16,
+    },
+    Step {
+        days: 10,
+        from: 45 * 16,
+        to: 45 * 16,
+    },
+    Step {
+        days: 3,
+        from: 35 * 16,
+        to: 15 * 16,
+    },
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Program {
+    FunctionalHeating,
+    Drying,
+}
+
+impl Program {
+    pub fn steps(self) -> &'static [Step] {
+        match self {
+            Program::FunctionalHeating => &FUNCTIONAL_HEATING,
+            Program::Drying => &DRYING,
+        }
+    }
+
+    pub fn days(self) -> u16 {
+        self.steps().iter().map(|step| step.days as u16).sum()
+    }
+
+    /// The highest forward target of the profile
+    pub fn max_target(self) -> i32 {
+        self.steps()
+            .iter()
+            .map(|step| step.from.max(step.to))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The step and the day within the step
+    fn step_at(self, day: u16) -> Option<(&'static Step, u8)> {
+        let mut first = 0;
+        for step in self.steps().iter() {
+            if day < first + step.days as u16 {
+                return Some((step, (day - first) as u8));
+            }
+            first += step.days as u16;
+        }
+        None
+    }
+
+    fn code(self) -> u16 {
+        self as u16 + 1
+    }
+
+    fn from_code(code: u16) -> Option<Self> {
+        match code {
+            1 => Some(Program::FunctionalHeating),
+            2 => Some(Program::Drying),
+            _ => None,
+        }
+    }
+}
+
+/// The target and the achieved temperatures of a day
+#[derive(Clone, Copy)]
+pub struct DayLog {
+    pub target: i32,
+    pub forward_min: Option<i32>,
+    pub forward_max: Option<i32>,
+    pub forward_average: Option<i32>,
+    pub floor_average: Option<i32>,
+}
+
+#[derive(Clone, Copy)]
+struct Average {
+    sum: i32,
+    count: i32,
+}
+
+impl Average {
+    fn new() -> Self {
+        Average { sum: 0, count: 0 }
+    }
+
+    fn add(&mut self, value: Option<i32>) {
+        if let Some(value) = value {
+            self.sum += value;
+            self.count += 1;
+        }
+    }
+
+    fn get(&self) -> Option<i32> {
+        if self.count > 0 {
+            Some(self.sum / self.count)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Runner {
+    program: Option<Program>,
+    running: bool, //false after the last day
+    day: u16,
+    seconds: u32,                        //within the day
+    log: [Option<DayLog>; MAX_LOG_DAYS], //by day
+    forward: Average,
+    floor: Average,
+    forward_min: Option<i32>,
+    forward_max: Option<i32>,
+}
+
+impl Runner {
+    pub fn new() -> Self {
+        Runner {
+            program: None,
+            running: false,
+            day: 0,
+            seconds: 0,
+            log: [None; MAX_LOG_DAYS],
+            forward: Average::new(),
+            floor: Average::new(),
+            forward_min: None,
+            forward_max: None,
+        }
+    }
+
+    /// Starts the program from the first day, the log is cleared
+    pub fn start(&mut self, program: Program) {
+        *self = Runner::new();
+        self.program = Some(program);
+        self.running = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// The program running
+    pub fn running(&self) -> Option<Program> {
+        if self.running {
+            self.program
+        } else {
+            None
+        }
+    }
+
+    /// The program of the log (running or finished)
+    pub fn program(&self) -> Option<Program> {
+        self.program
+    }
+
+    /// The current day from 0
+    pub fn day(&self) -> u16 {
+        self.day
+    }
+
+    /// Jumps to the given day of the running program (e.g. to continue a program started by hand)
+    pub fn set_day(&mut self, day: u16) {
+        if let Some(program) = self.running() {
+            if day < program.days() {
+                self.day = day;
+                self.seconds = 0;
+            }
+        }
+    }
+
+    pub fn phase(&self) -> Option<Phase> {
+        let (step, _) = self.running()?.step_at(self.day)?;
+        Some(step.phase())
+    }
+
+    /// The forward temperature target of the day, None if no program is running
+    pub fn target(&self) -> Option<i32> {
+        let (step, day) = self.running()?.step_at(self.day)?;
+        Some(step.target(day))
+    }
+
+    /// The forward target of the day with the limits by the profile, None if no program is running.
+    /// The screed is heated up to the forward temperature, so the floor limit is the same.
+    pub fn forward_override(&self) -> Option<ForwardOverride> {
+        let max = self.running()?.max_target() + LIMIT_MARGIN;
+        Some(ForwardOverride {
+            target: self.target()?,
+            max_forward: max,
+            max_floor: max,
+        })
+    }
+
+    pub fn log(&self, day: u16) -> Option<&DayLog> {
+        self.log.get(day as usize)?.as_ref()
+    }
+
+    /// This should be called regurarily with the measured temperatures, returns true if a new day was started
+    pub fn update<Duration: Into<u32>>(
+        &mut self,
+        forward: Option<i32>,
+        floor: Option<i32>,
+        delta_time: Duration,
+    ) -> bool {
+        let target = match self.target() {
+            Some(target) => target,
+            None => return false,
+        };
+
+        self.forward.add(forward);
+        self.floor.add(floor);
+        if let Some(forward) = forward {
+            self.forward_min = Some(self.forward_min.map_or(forward, |min| min.min(forward)));
+            self.forward_max = Some(self.forward_max.map_or(forward, |max| max.max(forward)));
+        }
+
+        self.seconds += delta_time.into();
+        if self.seconds < SECONDS_PER_DAY {
+            return false;
+        }
+
+        if let Some(entry) = self.log.get_mut(self.day as usize) {
+            *entry = Some(DayLog {
+                target: target,
+                forward_min: self.forward_min,
+                forward_max: self.forward_max,
+                forward_average: self.forward.get(),
+                floor_average: self.floor.get(),
+            });
+        }
+        self.forward = Average::new();
+        self.floor = Average::new();
+        self.forward_min = None;
+        self.forward_max = None;
+        self.seconds -= SECONDS_PER_DAY;
+        self.day += 1;
+        if self.target().is_none() {
+            //the program is completed
+            self.running = false;
+        }
+        true
+    }
+
+    /// The program, the day and the minutes of the day with a check word
+    pub fn save(&self) -> [u16; PROGRESS_WORDS] {
+        let program = match self.running() {
+            Some(program) => program.code(),
+            None => 0,
+        };
+        let minutes = (self.seconds / 60) as u16;
+        [
+            PROGRESS_CHECK ^ program ^ self.day ^ minutes,
+            program,
+            self.day,
+            minutes,
+        ]
+    }
+
+    /// Continues the saved program, returns false if the words are invalid or no program was running.
+    /// The log of the previous days is restored by decode_log.
+    pub fn restore(&mut self, words: &[u16; PROGRESS_WORDS]) -> bool {
+        let [check, program, day, minutes] = *words;
+        if check != PROGRESS_CHECK ^ program ^ day ^ minutes {
+            return false;
+        }
+        match Program::from_code(program) {
+            Some(program) if day < program.days() && (minutes as u32) * 60 < SECONDS_PER_DAY => {
+                self.start(program);
+                self.day = day;
+                self.seconds = minutes as u32 * 60;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The program and the log of the passed days, the missing values are encoded as i16::MIN
+    pub fn encode_log(&self, writer: &mut Writer) -> Result<(), Overflow> {
+        writer.u8(self.program.map_or(0, |program| program.code() as u8))?;
+        let days = self
+            .log
+            .iter()
+            .rposition(|entry| entry.is_some())
+            .map_or(0, |day| day + 1);
+        writer.u8(days as u8)?;
+        for entry in self.log[..days].iter() {
+            let values = match entry {
+                Some(log) => [
+                    Some(log.target),
+                    log.forward_min,
+                    log.forward_max,
+                    log.forward_average,
+                    log.floor_average,
+                ],
+                None => [None; 5],
+            };
+            for value in values.iter() {
+                writer.i16(value.map_or(i16::MIN, |value| value as i16))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Restores the log of the days before the current one, if it belongs to the restored program.
+    /// Without a restored program the log is kept as a finished one. Nothing is changed if the data is invalid.
+    pub fn decode_log(&mut self, reader: &mut Reader) -> Option<()> {
+        let program = Program::from_code(reader.u8()? as u16);
+        let days = reader.u8()? as usize;
+        let finished = match (self.program, program) {
+            (Some(current), Some(logged)) if current == logged => false,
+            (None, Some(_)) => true,
+            _ => return None,
+        };
+        if days > MAX_LOG_DAYS {
+            return None;
+        }
+        let passed = if finished { days } else { self.day as usize };
+        let mut log = [None; MAX_LOG_DAYS];
+        for (day, entry) in log.iter_mut().enumerate().take(days) {
+            let mut values = [None; 5];
+            for value in values.iter_mut() {
+                *value = match reader.i16()? {
+                    i16::MIN => None,
+                    value => Some(value as i32),
+                };
+            }
+            if let (Some(target), true) = (values[0], day < passed) {
+                *entry = Some(DayLog {
+                    target: target,
+                    forward_min: values[1],
+                    forward_max: values[2],
+                    forward_average: values[3],
+                    floor_average: values[4],
+                });
+            }
+        }
+        self.log = log;
+        if finished {
+            self.program = program;
+            self.day = days as u16;
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u32 = 24 * 3600;
+
+    /// Runs the day with the given forward and floor temperature (hourly updates)
+    fn run_day(runner: &mut Runner, forward: i32, floor: i32) -> bool {
+        let mut changed = false;
+        for _ in 0..24 {
+            changed |= runner.update(Some(forward), Some(floor), 3600u32);
+        }
+        changed
+    }
+
+    #[test]
+    fn drying_targets() {
+        let expected = [
+            25, 30, 35, 40, 45, //heat up by 5 degree a day
+            45, 45, 45, 45, 45, 45, 45, 45, 45, 45, //hold
+            35, 25, 15, //cool down
+        ];
+        let mut runner = Runner::new();
+        runner.start(Program::Drying);
+        assert!(Program::Drying.days() as usize == expected.len());
+        for (day, target) in expected.iter().enumerate() {
+            assert!(runner.day() as usize == day);
+            assert!(runner.target() == Some(target * 16));
+            run_day(&mut runner, target * 16, 0);
+        }
+        assert!(runner.target().is_none());
+    }
+
+    #[test]
+    fn step_phases() {
+        let phases: Vec<Phase> = DRYING.iter().map(|step| step.phase()).collect();
+        assert!(phases == [Phase::HeatUp, Phase::Hold, Phase::CoolDown]);
+        let single = Step {
+            days: 1,
+            from: 20 * 16,
+            to: 30 * 16,
+        };
+        assert!(single.target(0) == 30 * 16);
+    }
+
+    #[test]
+    fn day_change_and_completion() {
+        let mut runner = Runner::new();
+        assert!(!runner.update(Some(0), Some(0), DAY)); //not running
+        runner.start(Program::FunctionalHeating);
+        assert!(!runner.update(Some(24 * 16), Some(20 * 16), DAY - 1));
+        assert!(runner.update(Some(26 * 16), Some(22 * 16), 1u32));
+        assert!(runner.day() == 1);
+        let log = runner.log(0).unwrap();
+        assert!(log.target == 25 * 16);
+        assert!(log.forward_min == Some(24 * 16) && log.forward_max == Some(26 * 16));
+        assert!(log.forward_average == Some(25 * 16) && log.floor_average == Some(21 * 16));
+        assert!(runner.log(1).is_none());
+
+        for _ in 1..Program::FunctionalHeating.days() {
+            assert!(run_day(&mut runner, 45 * 16, 40 * 16));
+        }
+        assert!(runner.running().is_none());
+        assert!(runner.program() == Some(Program::FunctionalHeating));
+        assert!(runner.log(6).unwrap().target == 45 * 16);
+        assert!(runner.forward_override().is_none());
+        assert!(!run_day(&mut runner, 45 * 16, 40 * 16));
+    }
+
+    #[test]
+    fn progress_round_trip() {
+        let mut runner = Runner::new();
+        runner.start(Program::Drying);
+        runner.set_day(7);
+        runner.update(None, None, 90 * 60u32);
+        let words = runner.save();
+
+        let mut restored = Runner::new();
+        assert!(restored.restore(&words));
+        assert!(restored.running() == Some(Program::Drying));
+        assert!(restored.day() == 7 && restored.save() == words);
+
+        let mut corrupted = words;
+        corrupted[2] += 1;
+        assert!(!Runner::new().restore(&corrupted));
+        assert!(!Runner::new().restore(&Runner::new().save())); //no program
+    }
+
+    #[test]
+    fn log_round_trip() {
+        let mut runner = Runner::new();
+        runner.start(Program::Drying);
+        run_day(&mut runner, 25 * 16, 20 * 16);
+        runner.update(None, None, DAY); //no sensor for a day
+        run_day(&mut runner, 35 * 16, 30 * 16);
+        let mut buffer = [0u8; LOG_RECORD_SIZE];
+        let mut writer = Writer::new(&mut buffer);
+        runner.encode_log(&mut writer).unwrap();
+        let record = writer.data().to_vec();
+
+        //the progress and the log are restored after a reset
+        let mut restored = Runner::new();
+        assert!(restored.restore(&runner.save()));
+        assert!(restored.decode_log(&mut Reader::new(&record)).is_some());
+        for day in 0..3 {
+            let (log, original) = (restored.log(day).unwrap(), runner.log(day).unwrap());
+            assert!(log.target == original.target && log.forward_min == original.forward_min);
+            assert!(log.forward_average == original.forward_average);
+            assert!(log.floor_average == original.floor_average);
+        }
+        assert!(restored.log(1).unwrap().forward_max.is_none());
+
+        //the log of another program is dropped
+        let mut other = Runner::new();
+        other.start(Program::FunctionalHeating);
+        assert!(other.decode_log(&mut Reader::new(&record)).is_none());
+        assert!(other.decode_log(&mut Reader::new(&record[..5])).is_none());
+
+        //the log of the finished program is kept
+        let mut finished = Runner::new();
+        assert!(finished.decode_log(&mut Reader::new(&record)).is_some());
+        assert!(finished.running().is_none() && finished.program() == Some(Program::Drying));
+        assert!(finished.log(2).unwrap().target == 35 * 16);
+    }
+
+    #[test]
+    fn forward_override_by_the_profile() {
+        let mut runner = Runner::new();
+        assert!(runner.forward_override().is_none());
+        runner.start(Program::Drying);
+        runner.set_day(2);
+        let forward_override = runner.forward_override().unwrap();
+        assert!(forward_override.target == 35 * 16);
+        //the highest target of the profile with the margin
+        assert!(forward_override.max_forward == 50 * 16);
+        assert!(forward_override.max_floor == 50 * 16);
+        runner.stop();
+        assert!(runner.forward_override().is_none());
+    }
+}
@@ -0,0 +1,173 @@
+//! The home and error pages of the floor unit, rendered from plain data
+//! (so they can be snapshot tested on the host by the virtual display)
+
+use crate::display::*;
+use crate::floor_heating::{ErrorHistory, ErrorReason};
+use crate::schedule::Mode;
+use crate::timing::WeekTime;
+use core::ops::Add;
+use lcd_hal::Display;
+use onewire::temperature::Temperature;
+
+/// The second row of the home page
+#[derive(Clone, Copy)]
+pub enum Status {
+    Program(Mode<Temperature>),
+    Screed { day: u16, days: u16 }, //the running screed program overrides the mode
+}
+
+/// The target row of the home page, in the order of precedence
+#[derive(Clone, Copy)]
+pub enum Target<'a> {
+    Entry(&'a [u8]), //the digits typed in so far
+    OpenWindow,
+    Screed(Temperature), //the forward target of the screed program
+    Program {
+        target: Option<Temperature>,
+        next_change: Option<WeekTime>, //shown in the Normal and Economy modes only
+    },
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Icons {
+    pub valve: bool,
+    pub pump: bool,
+    pub heat_request: bool,
+    pub defrost: bool,
+}
+
+pub struct Home<'a> {
+    pub time: WeekTime,
+    pub status: Status,
+    pub icons: Icons,
+    pub target: Target<'a>,
+    pub air: Option<Temperature>,
+    pub forward: Option<Temperature>,
+    pub back: Option<Temperature>, //the return temperature
+    pub floor: Option<Temperature>,
+}
+
+pub fn error_text(reason: ErrorReason) -> &'static [u8] {
+    match reason {
+        ErrorReason::MissingAirSensor => b"Hiba: levego",
+        ErrorReason::MissingReturnSensor => b"Hiba: vissza",
+        ErrorReason::Overheat => b"Hiba: tulhevules",
+        ErrorReason::CirculationFailure => b"Hiba: keringes",
+    }
+}
+
+fn print_status<D: Display>(display: &mut D, status: &Status) -> Result<(), D::Error> {
+    display.set_position(0, 1)?;
+    match *status {
+        Status::Screed { day, days } => {
+            //the day of the screed program
+            display.print(b"Esz ")?;
+            print_u32(display, day as u32 + 1)?;
+            display.print_char('/' as u8)?;
+            print_u32(display, days as u32)
+        }
+        Status::Program(Mode::Normal) => display.print(b"Normal"),
+        Status::Program(Mode::Economy(offset)) => {
+            display.print(b"Eco ")?;
+            display.print(unsafe { fmt_temp(offset) })
+        }
+        Status::Program(Mode::Party(_day)) => display.print(b"Party"),
+        Status::Program(Mode::Fix(temp)) => {
+            display.print(b"Fix ")?;
+            display.print(unsafe { fmt_temp(temp) })
+        }
+        Status::Program(Mode::Away((days, _hour))) => {
+            //the days left until the return, the icons follow
+            display.print(b"Tavol ")?;
+            print_u32(display, days)?;
+            display.print_char('n' as u8)
+        }
+    }
+}
+
+fn print_target<D: Display>(display: &mut D, target: &Target) -> Result<(), D::Error> {
+    match *target {
+        Target::Entry(digits) => {
+            display.set_position(0, 4)?;
+            display.print(b"Cel:    ")?;
+            display.print(digits)
+        }
+        Target::OpenWindow => {
+            display.set_position(0, 4)?;
+            display.print(b"Ablak nyitva  ")
+        }
+        Target::Screed(target) => print_temp(display, 4, b"Elore:  ", &Some(target)),
+        Target::Program {
+            target,
+            next_change,
+        } => {
+            //the time of the next programmed change follows in the same row
+            print_temp(display, 4, b"Cel:", &target)?;
+            if let Some(start) = next_change {
+                display.set_position(60, 4)?;
+                display.print_char('>' as u8)?;
+                print_nn(display, start.hour)?;
+                display.print_char(':' as u8)?;
+                print_nn(display, start.min)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The time, the mode with the state icons, the air temperature with big digits,
+/// the target and the water and floor temperatures
+pub fn render_home<D: Display>(home: &Home, display: &mut D) -> Result<(), D::Error> {
+    display.set_position(0, 0)?;
+    print_time(display, home.time)?;
+
+    print_status(display, &home.status)?;
+
+    let icons = &home.icons;
+    print_icon(display, 64, 1, &ICON_VALVE, icons.valve)?;
+    print_icon(display, 72, 1, &ICON_PUMP, icons.pump)?;
+    print_icon(display, 80, 1, &ICON_HEAT_REQUEST, icons.heat_request)?;
+    if icons.defrost {
+        print_icon(display, 88, 1, &ICON_FROST, true)?;
+    } else {
+        print_icon(display, 88, 1, &ICON_FLAME, icons.heat_request)?;
+    }
+
+    //the air temperature is readable from distance:
+    print_big_temp(display, 2, &home.air, 2)?;
+
+    print_target(display, &home.target)?;
+
+    print_temp(display, 5, b"Elore:  ", &home.forward)?;
+    print_temp(display, 6, b"Vissza: ", &home.back)?;
+    print_temp(display, 7, b"Padlo:  ", &home.floor)
+}
+
+/// The current error and the latest ones with their age in hours
+pub fn render_errors<D, Duration>(
+    error: Option<ErrorReason>,
+    history: &ErrorHistory<Duration>,
+    display: &mut D,
+) -> Result<(), D::Error>
+where
+    D: lcd_hal::Display,
+    Duration: Copy + Default + Add<Duration, Output = Duration> + Into<u32>,
+{
+    display.set_position(0, 0)?;
+    if let Some(reason) = error {
+        display.print(error_text(reason))?;
+    } else {
+        display.print(b"Nincs hiba")?;
+    }
+
+    for i in 0..7 {
+        if let Some((reason, age)) = history.get(i) {
+            display.set_position(0, 1 + i as u8)?;
+            display.print(&error_text(reason)[6..])?;
+            display.print_char(' ' as u8)?;
+            print_u32(display, age.into() / 3600)?;
+            display.print_char('h' as u8)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! Temperature sensor registry: the 1-wire ROM code of the sensor assigned to each role,
+//! so replacing or adding a sensor can not swap the readings silently.
+
+use crate::codec::{Overflow, Reader, Writer};
+
+pub type Rom = [u8; 8];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Role {
+    Forward,
+    Return,
+    Floor,
+    Air,
+    Outdoor,
+}
+
+pub const ROLE_COUNT: usize = 5;
+pub const ROLES: [Role; ROLE_COUNT] = [
+    Role::Forward,
+    Role::Return,
+    Role::Floor,
+    Role::Air,
+    Role::Outdoor,
+];
+
+impl Role {
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The latest temperature readings by role
+#[derive(Clone, Copy)]
+pub struct Readings<Temperature> {
+    values: [Option<Temperature>; ROLE_COUNT],
+}
+
+impl<Temperature: Copy> Readings<Temperature> {
+    pub fn new() -> Self {
+        Readings {
+            values: [None; ROLE_COUNT],
+        }
+    }
+
+    pub fn get(&self, role: Role) -> Option<Temperature> {
+        self.values[role.index()]
+    }
+
+    pub fn set(&mut self, role: Role, value: Option<Temperature>) {
+        self.values[role.index()] = value;
+    }
+}
+
+pub struct Registry {
+    roms: [Option<Rom>; ROLE_COUNT],
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            roms: [None; ROLE_COUNT],
+        }
+    }
+
+    pub fn rom(&self, role: Role) -> Option<&Rom> {
+        self.roms[role.index()].as_ref()
+    }
+
+    pub fn role_of(&self, rom: &Rom) -> Option<Role> {
+        ROLES
+            .iter()
+            .find(|role| self.roms[role.index()].as_ref() == Some(rom))
+            .copied()
+    }
+
+    /// Assigns the sensor to the role, a sensor can have one role only
+    pub fn assign(&mut self, role: Role, rom: &Rom) {
+        if let Some(previous) = self.role_of(rom) {
+            self.roms[previous.index()] = None;
+        }
+        self.roms[role.index()] = Some(*rom);
+    }
+
+    pub fn clear(&mut self, role: Role) {
+        self.roms[role.index()] = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.iter().all(|rom| rom.is_none())
+    }
+
+    /// By role: 1 and the ROM code if assigned, 0 otherwise
+    pub fn encode(&self, writer: &mut Writer) -> Result<(), Overflow> {
+        for rom in self.roms.iter() {
+            match rom {
+                Some(rom) => {
+                    writer.u8(1)?;
+                    writer.bytes(rom)?;
+                }
+                None => writer.u8(0)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn decode(reader: &mut Reader) -> Option<Self> {
+        let mut registry = Registry::new();
+        for role in ROLES.iter() {
+            if reader.bool()? {
+                let mut rom: Rom = [0; 8];
+                rom.copy_from_slice(reader.bytes(8)?);
+                registry.assign(*role, &rom);
+            }
+        }
+        Some(registry)
+    }
+
+    /// The role has a sensor assigned, but it was not found on the bus
+    pub fn is_missing(&self, role: Role, found: &[Rom]) -> bool {
+        match self.rom(role) {
+            Some(rom) => !found.contains(rom),
+            None => false,
+        }
+    }
+
+    pub fn missing_count(&self, found: &[Rom]) -> usize {
+        ROLES
+            .iter()
+            .filter(|role| self.is_missing(**role, found))
+            .count()
+    }
+
+    /// Number of the found sensors without role
+    pub fn unknown_count(&self, found: &[Rom]) -> usize {
+        found
+            .iter()
+            .filter(|rom| self.role_of(rom).is_none())
+            .count()
+    }
+
+    /// Assigns the unknown sensors to the free roles in the order of discovery (e.g. at the first boot),
+    /// returns the number of the new assignments
+    pub fn assign_new(&mut self, found: &[Rom]) -> usize {
+        let mut count = 0;
+        for rom in found {
+            if self.role_of(rom).is_none() {
+                if let Some(role) = ROLES.iter().find(|role| self.roms[role.index()].is_none()) {
+                    self.roms[role.index()] = Some(*rom);
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
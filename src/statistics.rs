@@ -0,0 +1,205 @@
+//! Runtime statistics of the floor heating: time spent in the states, pump runtime,
+//! heat request time and valve cycles, collected by day and by week with the history of the last 7 days.
+//! The times are counted in seconds.
+
+use crate::floor_heating::State;
+
+pub const STATE_COUNT: usize = 6; //by the state code - 1
+pub const HISTORY_DAYS: usize = 7;
+
+#[derive(Clone, Copy)]
+pub struct Counters {
+    pub state_seconds: [u32; STATE_COUNT],
+    pub pump_seconds: u32,
+    pub heat_request_seconds: u32,
+    pub valve_cycles: u32, //openings
+}
+
+impl Counters {
+    pub fn new() -> Self {
+        Counters {
+            state_seconds: [0; STATE_COUNT],
+            pump_seconds: 0,
+            heat_request_seconds: 0,
+            valve_cycles: 0,
+        }
+    }
+
+    /// Measured time (the sum of the state times)
+    pub fn total_seconds(&self) -> u32 {
+        self.state_seconds.iter().sum()
+    }
+
+    /// The time of the state with the given code (see State::code)
+    pub fn state(&self, code: u8) -> u32 {
+        self.state_seconds[(code - 1) as usize]
+    }
+
+    /// Heat request time in percent of the measured time
+    pub fn active_percent(&self) -> u32 {
+        match self.total_seconds() {
+            0 => 0,
+            total => (self.heat_request_seconds as u64 * 100 / total as u64) as u32,
+        }
+    }
+
+    fn record(
+        &mut self,
+        state_code: u8,
+        pump: bool,
+        heat_request: bool,
+        opened: bool,
+        seconds: u32,
+    ) {
+        self.state_seconds[(state_code - 1) as usize] += seconds;
+        if pump {
+            self.pump_seconds += seconds;
+        }
+        if heat_request {
+            self.heat_request_seconds += seconds;
+        }
+        if opened {
+            self.valve_cycles += 1;
+        }
+    }
+}
+
+pub struct Statistics {
+    today: Counters,
+    week: Counters,
+    last_week: Counters,
+    history: [Counters; HISTORY_DAYS], //newest first
+    weekday: Option<u8>,               //of today
+    valve_open: bool,
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Statistics {
+            today: Counters::new(),
+            week: Counters::new(),
+            last_week: Counters::new(),
+            history: [Counters::new(); HISTORY_DAYS],
+            weekday: None,
+            valve_open: false,
+        }
+    }
+
+    pub fn today(&self) -> &Counters {
+        &self.today
+    }
+
+    pub fn week(&self) -> &Counters {
+        &self.week
+    }
+
+    pub fn last_week(&self) -> &Counters {
+        &self.last_week
+    }
+
+    /// The counters of the day before the given number of days (1 = yesterday)
+    pub fn day(&self, days_ago: usize) -> Option<&Counters> {
+        if days_ago >= 1 && days_ago <= HISTORY_DAYS {
+            Some(&self.history[days_ago - 1])
+        } else {
+            None
+        }
+    }
+
+    /// This should be called regurarily with the actual state and outputs,
+    /// the day is closed when the weekday changes, the week is closed on monday.
+    pub fn update<Duration: Into<u32>>(
+        &mut self,
+        state: &State<Duration>,
+        valve_open: bool,
+        pump_running: bool,
+        heat_request: bool,
+        weekday: u8,
+        delta_time: Duration,
+    ) {
+        if let Some(today) = self.weekday {
+            if today != weekday {
+                for i in (1..HISTORY_DAYS).rev() {
+                    self.history[i] = self.history[i - 1];
+                }
+                self.history[0] = self.today;
+                self.today = Counters::new();
+                if weekday == 0 {
+                    self.last_week = self.week;
+                    self.week = Counters::new();
+                }
+            }
+        }
+        self.weekday = Some(weekday);
+
+        let opened = valve_open && !self.valve_open;
+        self.valve_open = valve_open;
+
+        let seconds = delta_time.into();
+        let code = state.code();
+        self.today
+            .record(code, pump_running, heat_request, opened, seconds);
+        self.week
+            .record(code, pump_running, heat_request, opened, seconds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEATING: State<u32> = State::Heating(false);
+    const STANDBY: State<u32> = State::Standby(0);
+
+    /// An hour of heating and an hour of standby with the valve opened once
+    fn heat(statistics: &mut Statistics, weekday: u8) {
+        statistics.update(&HEATING, true, true, true, weekday, 3600);
+        statistics.update(&STANDBY, false, false, false, weekday, 3600);
+    }
+
+    #[test]
+    fn counters_of_the_day() {
+        let mut statistics = Statistics::new();
+        heat(&mut statistics, 2);
+        heat(&mut statistics, 2);
+        let today = statistics.today();
+        assert!(today.total_seconds() == 4 * 3600);
+        assert!(today.state(HEATING.code()) == 2 * 3600);
+        assert!(today.pump_seconds == 2 * 3600);
+        assert!(today.active_percent() == 50);
+        assert!(today.valve_cycles == 2);
+    }
+
+    #[test]
+    fn day_rollover() {
+        let mut statistics = Statistics::new();
+        heat(&mut statistics, 2);
+        statistics.update(&STANDBY, false, false, false, 3, 60);
+        assert!(statistics.today().total_seconds() == 60);
+        assert!(statistics.day(1).unwrap().total_seconds() == 2 * 3600);
+        assert!(statistics.day(2).unwrap().total_seconds() == 0);
+        assert!(statistics.week().total_seconds() == 2 * 3600 + 60);
+
+        //the oldest day is dropped
+        for weekday in 4..11 {
+            statistics.update(&STANDBY, false, false, false, weekday % 7, 60);
+        }
+        assert!(statistics.day(HISTORY_DAYS).unwrap().total_seconds() == 60);
+        assert!(statistics.day(HISTORY_DAYS + 1).is_none() && statistics.day(0).is_none());
+    }
+
+    #[test]
+    fn week_rollover_on_monday() {
+        let mut statistics = Statistics::new();
+        heat(&mut statistics, 5);
+        heat(&mut statistics, 6);
+        statistics.update(&STANDBY, false, false, false, 0, 60);
+        assert!(statistics.last_week().total_seconds() == 4 * 3600);
+        assert!(statistics.last_week().valve_cycles == 2);
+        assert!(statistics.week().total_seconds() == 60);
+
+        heat(&mut statistics, 1); //not on the other days
+        assert!(statistics.week().total_seconds() == 2 * 3600 + 60);
+        assert!(statistics.last_week().total_seconds() == 4 * 3600);
+    }
+}
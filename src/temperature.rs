@@ -0,0 +1,48 @@
+//! Conversion of the temperatures to plain fixed point numbers for computations
+
+use onewire::temperature::Temperature;
+
+pub trait FixedPoint: Sized {
+    /// In 1/16 degree Celsius units (the DS18B20 resolution)
+    fn to_sixteenths(&self) -> i32;
+    fn from_sixteenths(sixteenths: i32) -> Self;
+
+    /// Rounded to 1/10 degree Celsius units
+    fn to_tenths(&self) -> i32 {
+        round_div(self.to_sixteenths() * 10, 16)
+    }
+
+    fn from_tenths(tenths: i32) -> Self {
+        Self::from_sixteenths(round_div(tenths * 16, 10))
+    }
+}
+
+/// Division rounded to the nearest integer
+pub fn round_div(a: i32, b: i32) -> i32 {
+    if (a >= 0) == (b >= 0) {
+        (a + b / 2) / b
+    } else {
+        (a - b / 2) / b
+    }
+}
+
+impl FixedPoint for Temperature {
+    fn to_sixteenths(&self) -> i32 {
+        let magnitude = self.whole_degrees() as i32 * 16 + self.fraction_degrees() as i32;
+        if self.is_negative() {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    fn from_sixteenths(sixteenths: i32) -> Self {
+        let magnitude = sixteenths.abs();
+        let temp = Temperature::from_celsius((magnitude / 16) as _, (magnitude % 16) as _);
+        if sixteenths < 0 {
+            Temperature::from_celsius(0, 0) - temp
+        } else {
+            temp
+        }
+    }
+}
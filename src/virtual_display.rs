@@ -0,0 +1,117 @@
+//! In memory display with the geometry of the HX1230 (96x68 pixels, 6x8 font)
+//! Keeps the printed characters in a grid and the drawn graphics in a framebuffer,
+//! both can be rendered to text (the latter to plain PBM format) for comparison with golden files.
+
+use core::fmt::{Result, Write};
+
+pub const WIDTH: u8 = 96;
+pub const HEIGHT: u8 = 68;
+pub const CHAR_WIDTH: u8 = 6;
+pub const COLS: usize = (WIDTH / CHAR_WIDTH) as usize;
+pub const ROWS: usize = (HEIGHT / 8) as usize;
+const PAGES: usize = ((HEIGHT + 7) / 8) as usize;
+
+pub struct VirtualDisplay {
+    chars: [[u8; COLS]; ROWS],
+    pixels: [[u8; WIDTH as usize]; PAGES], //8 pixel high columns, LSB on the top
+    x: u8,
+    row: u8,
+}
+
+impl VirtualDisplay {
+    pub fn new() -> Self {
+        VirtualDisplay {
+            chars: [[b' '; COLS]; ROWS],
+            pixels: [[0u8; WIDTH as usize]; PAGES],
+            x: 0,
+            row: 0,
+        }
+    }
+
+    /// Character at the given text position
+    pub fn char_at(&self, col: usize, row: usize) -> u8 {
+        self.chars[row][col]
+    }
+
+    pub fn pixel(&self, x: u8, y: u8) -> bool {
+        (self.pixels[(y / 8) as usize][x as usize] >> (y % 8)) & 1 != 0
+    }
+
+    /// Renders the character grid row by row, cells covered by graphics only are shown as '#'
+    pub fn write_text<W: Write>(&self, out: &mut W) -> Result {
+        for row in 0..ROWS {
+            for col in 0..COLS {
+                let c = self.chars[row][col];
+                let x = col * CHAR_WIDTH as usize;
+                let drawn = self.pixels[row][x..x + CHAR_WIDTH as usize]
+                    .iter()
+                    .any(|column| *column != 0);
+                out.write_char(if c == b' ' && drawn { '#' } else { c as char })?;
+            }
+            out.write_char('\n')?;
+        }
+        Ok(())
+    }
+
+    /// Renders the graphics framebuffer in plain PBM (P1) format
+    pub fn write_pbm<W: Write>(&self, out: &mut W) -> Result {
+        write!(out, "P1\n{} {}\n", WIDTH, HEIGHT)?;
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                out.write_char(if self.pixel(x, y) { '1' } else { '0' })?;
+            }
+            out.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+impl lcd_hal::Display for VirtualDisplay {
+    type Error = ();
+
+    fn get_pixel_resolution(&self) -> (u8, u8) {
+        (WIDTH, HEIGHT)
+    }
+
+    fn get_char_resolution(&self) -> (u8, u8) {
+        (COLS as u8, ROWS as u8)
+    }
+
+    fn set_position(&mut self, x: u8, y: u8) -> core::result::Result<(), ()> {
+        self.x = x;
+        self.row = y;
+        Ok(())
+    }
+
+    fn print_char(&mut self, c: u8) -> core::result::Result<(), ()> {
+        let col = (self.x / CHAR_WIDTH) as usize;
+        if col < COLS && (self.row as usize) < ROWS {
+            self.chars[self.row as usize][col] = c;
+        }
+        self.x = self.x.saturating_add(CHAR_WIDTH);
+        Ok(())
+    }
+
+    fn print(&mut self, s: &[u8]) -> core::result::Result<(), ()> {
+        for c in s {
+            self.print_char(*c)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> core::result::Result<(), ()> {
+        *self = VirtualDisplay::new();
+        Ok(())
+    }
+
+    //the columns drawn by display::print_big and print_icon
+    fn print_image(&mut self, columns: &[u8]) -> core::result::Result<(), ()> {
+        for column in columns {
+            if self.x < WIDTH && (self.row as usize) < PAGES {
+                self.pixels[self.row as usize][self.x as usize] = *column;
+            }
+            self.x = self.x.saturating_add(1);
+        }
+        Ok(())
+    }
+}
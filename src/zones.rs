@@ -0,0 +1,194 @@
+//! Multiple floor heating zones (loops of a manifold) with one shared circulation pump and heat source.
+//! Every zone runs its own floor heating state machine, the pump and the heat request are the OR of the zones.
+//! The after circulation is started when the last heat request of the zones ends (the heat source stops),
+//! the valves of the zones heated last are kept open meanwhile, so the water is not pumped against closed valves.
+
+use crate::floor_heating::{Config, Controller, State};
+use crate::sensors::{Readings, Role};
+use crate::temperature::FixedPoint;
+use core::ops::{Add, Div, Mul, Sub};
+
+pub struct Zone<Temperature, Duration> {
+    pub config: Config<Temperature, Duration>,
+    pub controller: Controller<Duration>,
+    pub readings: Readings<Temperature>,
+    hold_open: bool, //the valve is kept open for the shared after circulation
+}
+
+impl<Temperature: Copy, Duration> Zone<Temperature, Duration>
+where
+    Duration: Copy
+        + PartialOrd
+        + Default
+        + Into<u32>
+        + Add<Duration, Output = Duration>
+        + Sub<Duration, Output = Duration>
+        + Mul<u32, Output = Duration>
+        + Div<u32, Output = Duration>,
+{
+    pub fn new(config: Config<Temperature, Duration>) -> Self {
+        Zone {
+            config: config,
+            controller: Controller::new(State::Standby(Duration::default())),
+            readings: Readings::new(),
+            hold_open: false,
+        }
+    }
+
+    pub fn valve_open(&self) -> bool {
+        self.controller.valve_open() || self.hold_open
+    }
+}
+
+pub struct SharedPump<Duration> {
+    heat_request: bool,
+    after_circulation: Option<Duration>, //since the last heat request ended
+}
+
+impl<Duration> SharedPump<Duration>
+where
+    Duration: Copy
+        + PartialOrd
+        + Default
+        + Into<u32>
+        + Add<Duration, Output = Duration>
+        + Sub<Duration, Output = Duration>
+        + Mul<u32, Output = Duration>
+        + Div<u32, Output = Duration>,
+{
+    pub fn new() -> Self {
+        SharedPump {
+            heat_request: false,
+            after_circulation: None,
+        }
+    }
+
+    /// The heat source should be requested
+    pub fn heat_request(&self) -> bool {
+        self.heat_request
+    }
+
+    pub fn after_circulation(&self) -> bool {
+        self.after_circulation.is_some()
+    }
+
+    /// This should be called regurarily, updates the zones and the shared outputs
+    pub fn update<
+        Temperature: Copy
+            + PartialOrd
+            + FixedPoint
+            + Add<Temperature, Output = Temperature>
+            + Sub<Temperature, Output = Temperature>,
+    >(
+        &mut self,
+        zones: &mut [Zone<Temperature, Duration>],
+        after_circulation_duration: Duration,
+        outdoor_temperature: Option<Temperature>,
+        delta_time: Duration,
+    ) {
+        for zone in zones.iter_mut() {
+            zone.readings.set(Role::Outdoor, outdoor_temperature);
+            zone.controller
+                .update(&zone.config, &zone.readings, delta_time);
+        }
+
+        let heat_request = zones.iter().any(|zone| zone.controller.heat_request());
+
+        if heat_request {
+            self.after_circulation = None;
+            //remember the heated zones, their valves are used for the after circulation
+            for zone in zones.iter_mut() {
+                zone.hold_open = zone.controller.heat_request();
+            }
+        } else if self.heat_request {
+            self.after_circulation = Some(Duration::default());
+        } else if let Some(elapsed) = self.after_circulation {
+            let elapsed = elapsed + delta_time;
+            self.after_circulation = if elapsed < after_circulation_duration {
+                Some(elapsed)
+            } else {
+                None
+            };
+        }
+
+        if self.after_circulation.is_none() && !heat_request {
+            for zone in zones.iter_mut() {
+                zone.hold_open = false;
+            }
+        }
+
+        self.heat_request = heat_request;
+    }
+
+    /// The pump runs while any of the zones or the shared after circulation needs it
+    pub fn pump_running<Temperature>(&self, zones: &[Zone<Temperature, Duration>]) -> bool {
+        self.heat_request
+            || self.after_circulation.is_some()
+            || zones.iter().any(|zone| zone.controller.pump_running())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use onewire::temperature::Temperature;
+
+    fn celsius(degrees: i32) -> Option<Temperature> {
+        Some(Temperature::from_sixteenths(degrees * 16))
+    }
+
+    fn zone(air: i32) -> Zone<Temperature, u32> {
+        let mut zone = Zone::new(Config {
+            target_air_temperature: celsius(21),
+            ..Config::with_defaults()
+        });
+        zone.readings.set(Role::Air, celsius(air));
+        zone
+    }
+
+    fn run(pump: &mut SharedPump<u32>, zones: &mut [Zone<Temperature, u32>], minutes: u32) {
+        for _ in 0..minutes {
+            pump.update(zones, 240, None, 60);
+        }
+    }
+
+    #[test]
+    fn after_circulation_holds_the_heated_valve() {
+        let mut zones = [zone(18), zone(22)];
+        let mut pump = SharedPump::new();
+        run(&mut pump, &mut zones, 10); //over the pre circulation
+        assert!(pump.heat_request() && pump.pump_running(&zones));
+        assert!(zones[0].valve_open() && !zones[1].valve_open());
+
+        zones[0].readings.set(Role::Air, celsius(23));
+        run(&mut pump, &mut zones, 1);
+        assert!(!pump.heat_request() && pump.after_circulation());
+        assert!(!zones[0].controller.valve_open()); //closed by its own state machine
+        assert!(zones[0].valve_open() && !zones[1].valve_open());
+
+        run(&mut pump, &mut zones, 3); //the 4th minute of the after circulation
+        assert!(zones[0].valve_open());
+        run(&mut pump, &mut zones, 1);
+        assert!(!pump.after_circulation());
+        assert!(!zones[0].valve_open());
+    }
+
+    #[test]
+    fn only_the_zones_heated_last_are_held() {
+        let mut zones = [zone(18), zone(18)];
+        let mut pump = SharedPump::new();
+        run(&mut pump, &mut zones, 10);
+        assert!(zones[0].valve_open() && zones[1].valve_open());
+
+        //the first zone stops while the other one still heats
+        zones[0].readings.set(Role::Air, celsius(23));
+        run(&mut pump, &mut zones, 1);
+        assert!(pump.heat_request() && !pump.after_circulation());
+        assert!(!zones[0].valve_open());
+
+        zones[1].readings.set(Role::Air, celsius(23));
+        run(&mut pump, &mut zones, 1);
+        assert!(pump.after_circulation());
+        assert!(!zones[0].valve_open() && zones[1].valve_open());
+    }
+}
@@ -0,0 +1,262 @@
+//! Snapshot tests of the UI screens rendered into the virtual display.
+//! The golden files are in tests/golden, run with UPDATE_GOLDEN=1 to rewrite them after a deliberate change.
+//! The crate builds for the MCU by default, so the host target should be given:
+//!
+//!   cargo test --target x86_64-unknown-linux-gnu
+//!
+use onewire::temperature::Temperature;
+use room_pill::{
+    display,
+    floor_heating::{ErrorHistory, ErrorReason},
+    menu::{Content, Entry, Format, Item, Menu, NumericEntry, Row},
+    schedule::Mode,
+    screens::{self, Home, Icons, Status, Target},
+    temperature::FixedPoint,
+    timing::{Duration, Seconds, WeekTime},
+    virtual_display::VirtualDisplay,
+};
+use std::{env, fs, path::PathBuf, sync::Mutex};
+
+//the formatting functions of the display module share static buffers
+static RENDER: Mutex<()> = Mutex::new(());
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(name)
+}
+
+fn check_golden(name: &str, actual: &str) {
+    let path = golden_path(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1)", path.display(), e));
+    assert!(
+        expected == actual,
+        "{} differs, the rendered screen:\n{}",
+        name,
+        actual
+    );
+}
+
+fn check_display(name: &str, display: &VirtualDisplay) {
+    let mut text = String::new();
+    display.write_text(&mut text).unwrap();
+    check_golden(&format!("{}.txt", name), &text);
+
+    let mut pbm = String::new();
+    display.write_pbm(&mut pbm).unwrap();
+    check_golden(&format!("{}.pbm", name), &pbm);
+}
+
+fn celsius(whole: i16, sixteenths: i16) -> Option<Temperature> {
+    Some(Temperature::from_celsius(whole, sixteenths))
+}
+
+fn monday(hour: u8, min: u8) -> WeekTime {
+    WeekTime {
+        weekday: 0,
+        hour: hour,
+        min: min,
+        sec: 0,
+    }
+}
+
+fn home<'a>(status: Status, target: Target<'a>) -> Home<'a> {
+    Home {
+        time: monday(6, 45),
+        status: status,
+        icons: Icons {
+            valve: true,
+            pump: true,
+            heat_request: true,
+            defrost: false,
+        },
+        target: target,
+        air: celsius(21, 8),
+        forward: celsius(34, 4),
+        back: celsius(29, 0),
+        floor: celsius(24, 12),
+    }
+}
+
+fn render_home(name: &str, home: &Home) {
+    let _lock = RENDER.lock().unwrap();
+    let mut display = VirtualDisplay::new();
+    screens::render_home(home, &mut display).unwrap();
+    check_display(name, &display);
+}
+
+#[test]
+fn home_normal() {
+    render_home(
+        "home_normal",
+        &home(
+            Status::Program(Mode::Normal),
+            Target::Program {
+                target: celsius(20, 0),
+                next_change: Some(monday(7, 30)),
+            },
+        ),
+    );
+}
+
+#[test]
+fn home_screed_with_missing_sensors() {
+    let mut home = home(
+        Status::Screed { day: 2, days: 10 },
+        Target::Screed(Temperature::from_celsius(30, 0)),
+    );
+    home.icons = Icons {
+        defrost: true,
+        ..Icons::default()
+    };
+    home.air = None;
+    home.floor = None;
+    render_home("home_screed", &home);
+}
+
+#[test]
+fn home_entry() {
+    render_home(
+        "home_entry",
+        &home(Status::Program(Mode::Party(0)), Target::Entry(b"21.5_")),
+    );
+}
+
+#[test]
+fn big_temperature_is_rounded() {
+    let _lock = RENDER.lock().unwrap();
+    let big = |sixteenths: i32| unsafe {
+        display::fmt_big_temp(Temperature::from_sixteenths(sixteenths)).to_vec()
+    };
+    assert!(big(21 * 16 + 15) == b" 21.9*C"); //21.9375
+    assert!(big(21 * 16 + 1) == b" 21.1*C"); //21.0625
+    assert!(big(5 * 16 + 9) == b"  5.6*C"); //5.5625
+    assert!(big(-1) == b" -0.1*C");
+    assert!(big(-(12 * 16 + 3)) == b"-12.2*C"); //-12.1875
+}
+
+#[test]
+fn errors() {
+    let mut history = ErrorHistory::<Duration<u32, Seconds>>::new();
+    history.record(ErrorReason::MissingAirSensor);
+    history.update(Duration::from_hms(26, 0, 0));
+    history.record(ErrorReason::Overheat);
+    history.update(Duration::from_hms(3, 10, 0));
+
+    let _lock = RENDER.lock().unwrap();
+    let mut display = VirtualDisplay::new();
+    screens::render_errors(Some(ErrorReason::Overheat), &history, &mut display).unwrap();
+    check_display("errors", &display);
+
+    let mut display = VirtualDisplay::new();
+    screens::render_errors(
+        None,
+        &ErrorHistory::<Duration<u32, Seconds>>::new(),
+        &mut display,
+    )
+    .unwrap();
+    check_display("errors_none", &display);
+}
+
+//a menu on a minimal model
+struct Model {
+    target: u8,
+    enabled: bool,
+}
+
+fn set_target(model: &mut Model, up: bool) {
+    model.target = if up {
+        model.target + 1
+    } else {
+        model.target - 1
+    };
+}
+
+fn view_target(model: &Model) -> &'static [u8] {
+    if model.target > 20 {
+        b"21.0"
+    } else {
+        b"20.0"
+    }
+}
+
+fn enter_target(model: &mut Model, value: i32) {
+    model.target = (value / 10) as u8;
+}
+
+fn set_enabled(model: &mut Model, _: bool) {
+    model.enabled = !model.enabled;
+}
+
+fn view_enabled(model: &Model) -> &'static [u8] {
+    if model.enabled {
+        b"Igen"
+    } else {
+        b"Nem"
+    }
+}
+
+static SUB_ROWS: [Row<Model, bool>; 1] = [Row {
+    text: b"Be",
+    content: Content::MenuItem(Item {
+        update: set_enabled,
+        view: view_enabled,
+        entry: None,
+    }),
+}];
+
+static ROWS: [Row<Model, bool>; 3] = [
+    Row {
+        text: b"Cel",
+        content: Content::MenuItem(Item {
+            update: set_target,
+            view: view_target,
+            entry: Some(NumericEntry {
+                format: Format::Decimal,
+                min: 50,
+                max: 300,
+                set: enter_target,
+            }),
+        }),
+    },
+    Row {
+        text: b"Beallitas",
+        content: Content::SubMenu(Menu { rows: &SUB_ROWS }),
+    },
+    Row {
+        text: b"Engedelyez",
+        content: Content::MenuItem(Item {
+            update: set_enabled,
+            view: view_enabled,
+            entry: None,
+        }),
+    },
+];
+
+#[test]
+fn menu() {
+    let menu = Menu { rows: &ROWS };
+    let model = Model {
+        target: 21,
+        enabled: false,
+    };
+
+    let _lock = RENDER.lock().unwrap();
+    let mut display = VirtualDisplay::new();
+    menu.render::<_, Duration<u32, Seconds>>(&model, 2, None, &mut display)
+        .unwrap();
+    check_display("menu", &display);
+
+    let mut entry = Entry::<Duration<u32, Seconds>>::new(Format::Decimal);
+    entry.push(2);
+    entry.push(2);
+    let mut display = VirtualDisplay::new();
+    menu.render(&model, 0, Some(&entry), &mut display).unwrap();
+    check_display("menu_entry", &display);
+}